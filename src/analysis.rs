@@ -0,0 +1,394 @@
+use crate::types::{
+    PercentileConfidence, PhaseSummary, RateSegment, SampleAdequacy, SegmentResult,
+    TimeSeriesPoint, WorstInterval,
+};
+
+// Relative RPS band around the steady-state rate a point must stay within
+const STEADY_STATE_TOLERANCE: f64 = 0.1;
+
+// Percentiles reported in the result, and how many samples must lie beyond a percentile
+// for it to be more than a single outlier
+const REPORTED_PERCENTILES: [f64; 3] = [50.0, 95.0, 99.0];
+const TAIL_SAMPLES: f64 = 1.0;
+
+// Interval error rate above baseline that counts as a spike, and how close to baseline
+// it must get again to count as recovered
+const ERROR_SPIKE_THRESHOLD: f64 = 0.1;
+const ERROR_RECOVERY_TOLERANCE: f64 = 0.02;
+
+pub struct SteadyState {
+    // Seconds from test start until RPS settled and stayed within the band
+    pub time_to_steady_state_seconds: f64,
+    // 1.0 for perfectly flat RPS once settled, dropping towards 0.0 as it wobbles
+    pub stability_score: f64,
+}
+
+// Finds when the achieved RPS settled, using the mean of the second half of the
+// run as the steady-state rate
+pub fn steady_state(points: &[TimeSeriesPoint], start_timestamp: i64) -> Option<SteadyState> {
+    if points.len() < 2 {
+        return None;
+    }
+
+    let tail = &points[points.len() / 2..];
+    let target = mean(tail.iter().map(|point| point.rps));
+    if target <= 0.0 {
+        return None;
+    }
+
+    let within_band =
+        |point: &TimeSeriesPoint| (point.rps - target).abs() <= target * STEADY_STATE_TOLERANCE;
+
+    // Earliest point after which every point stays inside the band
+    let settled = points
+        .iter()
+        .rposition(|point| !within_band(point))
+        .map_or(0, |last_outlier| last_outlier + 1);
+    let steady = points.get(settled..).filter(|steady| !steady.is_empty())?;
+
+    let steady_mean = mean(steady.iter().map(|point| point.rps));
+    let variance = mean(steady.iter().map(|point| (point.rps - steady_mean).powi(2)));
+    let coefficient_of_variation = variance.sqrt() / steady_mean;
+
+    Some(SteadyState {
+        time_to_steady_state_seconds: (steady[0].timestamp - start_timestamp).max(0) as f64,
+        stability_score: (1.0 - coefficient_of_variation).clamp(0.0, 1.0),
+    })
+}
+
+// Target and achieved rate per schedule segment, weighting each time-series point by
+// its interval. A point belongs to the segment its interval's midpoint falls in. Phase
+// summaries, when kept, are the segments' in order.
+pub fn segment_results(
+    points: &[TimeSeriesPoint],
+    start_timestamp: i64,
+    segments: &[RateSegment],
+    phases: &[PhaseSummary],
+) -> Vec<SegmentResult> {
+    let mut segment_start = 0.0;
+
+    segments
+        .iter()
+        .enumerate()
+        .map(|(index, segment)| {
+            let start_seconds = segment_start;
+            let end_seconds = start_seconds + segment.duration_seconds as f64;
+            segment_start = end_seconds;
+
+            let within: Vec<&TimeSeriesPoint> = points
+                .iter()
+                .filter(|point| {
+                    let midpoint =
+                        (point.timestamp - start_timestamp) as f64 - point.interval_seconds / 2.0;
+                    (start_seconds..end_seconds).contains(&midpoint)
+                })
+                .collect();
+            let seconds: f64 = within.iter().map(|point| point.interval_seconds).sum();
+            let weighted = |rate: &dyn Fn(&TimeSeriesPoint) -> f64| {
+                let total: f64 = within
+                    .iter()
+                    .map(|point| rate(point) * point.interval_seconds)
+                    .sum();
+                if seconds > 0.0 {
+                    total / seconds
+                } else {
+                    0.0
+                }
+            };
+
+            SegmentResult {
+                start_seconds,
+                duration_seconds: segment.duration_seconds,
+                target_rps: weighted(&|point| point.target_rps.unwrap_or(segment.rps)),
+                achieved_rps: weighted(&|point| point.interval_rps),
+                phase: phases.get(index).cloned(),
+            }
+        })
+        .collect()
+}
+
+// The interval with the highest p95 latency, ties broken by error rate
+pub fn worst_interval(points: &[TimeSeriesPoint]) -> Option<WorstInterval> {
+    points
+        .iter()
+        .max_by(|a, b| {
+            a.p95_response_time
+                .total_cmp(&b.p95_response_time)
+                .then(a.error_rate.total_cmp(&b.error_rate))
+        })
+        .map(|point| WorstInterval {
+            timestamp: point.timestamp,
+            p95: point.p95_response_time,
+            error_rate: point.error_rate,
+        })
+}
+
+// Flags percentiles computed from too few samples, e.g. p99 needs at least 100
+pub fn sample_adequacy(samples: u64) -> SampleAdequacy {
+    let percentiles: Vec<PercentileConfidence> = REPORTED_PERCENTILES
+        .iter()
+        .map(|&percentile| {
+            let minimum_samples = (TAIL_SAMPLES * 100.0 / (100.0 - percentile)).ceil() as u64;
+            PercentileConfidence {
+                percentile,
+                minimum_samples,
+                reliable: samples >= minimum_samples,
+            }
+        })
+        .collect();
+
+    SampleAdequacy {
+        samples,
+        suggested_minimum_samples: percentiles
+            .iter()
+            .map(|confidence| confidence.minimum_samples)
+            .max()
+            .unwrap_or(0),
+        percentiles,
+    }
+}
+
+// Time from the peak of the worst error-rate spike until the interval error rate
+// returns to baseline, the median across the run
+pub fn error_recovery_seconds(points: &[TimeSeriesPoint]) -> Option<f64> {
+    let mut rates: Vec<f64> = points.iter().map(|p| p.interval_error_rate).collect();
+    rates.sort_by(f64::total_cmp);
+    let baseline = *rates.get(rates.len() / 2)?;
+
+    let (peak_index, peak) = points
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.interval_error_rate.total_cmp(&b.interval_error_rate))?;
+    if peak.interval_error_rate < baseline + ERROR_SPIKE_THRESHOLD {
+        return None;
+    }
+
+    let mut elapsed = 0.0;
+    for point in &points[peak_index + 1..] {
+        elapsed += point.interval_seconds;
+        if point.interval_error_rate <= baseline + ERROR_RECOVERY_TOLERANCE {
+            return Some(elapsed);
+        }
+    }
+
+    None
+}
+
+// Halves the series by merging adjacent intervals until it fits within `max_points`
+pub fn downsample(points: &mut Vec<TimeSeriesPoint>, max_points: usize) {
+    while points.len() > max_points.max(1) {
+        *points = points
+            .chunks(2)
+            .map(|pair| match pair {
+                [first, second] => merge_points(first, second),
+                _ => pair[0].clone(),
+            })
+            .collect();
+    }
+}
+
+// Combines two consecutive intervals into one spanning both. Rates are weighted by
+// interval length, latency and error rate by the approximate request count.
+fn merge_points(first: &TimeSeriesPoint, second: &TimeSeriesPoint) -> TimeSeriesPoint {
+    let interval_seconds = first.interval_seconds + second.interval_seconds;
+    let first_requests = first.rps * first.interval_seconds;
+    let second_requests = second.rps * second.interval_seconds;
+    let requests = first_requests + second_requests;
+
+    let by_requests = |a: f64, b: f64| {
+        if requests > 0.0 {
+            (a * first_requests + b * second_requests) / requests
+        } else {
+            (a + b) / 2.0
+        }
+    };
+
+    let by_time = |a: f64, b: f64| {
+        if interval_seconds > 0.0 {
+            (a * first.interval_seconds + b * second.interval_seconds) / interval_seconds
+        } else {
+            (a + b) / 2.0
+        }
+    };
+
+    TimeSeriesPoint {
+        timestamp: second.timestamp,
+        interval_seconds,
+        rps: if interval_seconds > 0.0 {
+            requests / interval_seconds
+        } else {
+            (first.rps + second.rps) / 2.0
+        },
+        goodput_rps: by_time(first.goodput_rps, second.goodput_rps),
+        interval_rps: by_time(first.interval_rps, second.interval_rps),
+        target_rps: first
+            .target_rps
+            .zip(second.target_rps)
+            .map(|(a, b)| by_time(a, b)),
+        avg_response_time: by_requests(first.avg_response_time, second.avg_response_time),
+        min_response_time: merged_min(first, second),
+        max_response_time: first.max_response_time.max(second.max_response_time),
+        // Percentiles can't be recombined exactly; the weighted mean is a close stand-in
+        p95_response_time: by_requests(first.p95_response_time, second.p95_response_time),
+        error_rate: by_requests(first.error_rate, second.error_rate),
+        interval_error_rate: by_requests(first.interval_error_rate, second.interval_error_rate),
+        pool_saturated: first.pool_saturated || second.pool_saturated,
+        schedule_lag_ms: second.schedule_lag_ms.or(first.schedule_lag_ms),
+    }
+}
+
+// Smallest latency across both intervals, ignoring intervals without samples
+fn merged_min(first: &TimeSeriesPoint, second: &TimeSeriesPoint) -> f64 {
+    [first, second]
+        .iter()
+        .filter(|point| point.max_response_time > 0.0)
+        .map(|point| point.min_response_time)
+        .reduce(f64::min)
+        .unwrap_or(0.0)
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> f64 {
+    let (sum, count) = values.fold((0.0, 0usize), |(sum, count), value| {
+        (sum + value, count + 1)
+    });
+    if count == 0 {
+        0.0
+    } else {
+        sum / count as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One-second point at `rps` with a flat latency and error rate
+    fn point(timestamp: i64, rps: f64, latency: f64, error_rate: f64) -> TimeSeriesPoint {
+        TimeSeriesPoint {
+            timestamp,
+            interval_seconds: 1.0,
+            rps,
+            goodput_rps: rps * (1.0 - error_rate),
+            interval_rps: rps,
+            target_rps: Some(rps),
+            avg_response_time: latency,
+            min_response_time: latency,
+            max_response_time: latency,
+            p95_response_time: latency,
+            error_rate,
+            interval_error_rate: error_rate,
+            pool_saturated: false,
+            schedule_lag_ms: None,
+        }
+    }
+
+    #[test]
+    fn leaves_short_series_alone() {
+        let mut points: Vec<_> = (0..5).map(|t| point(t, 10.0, 20.0, 0.0)).collect();
+        downsample(&mut points, 5);
+        assert_eq!(points.len(), 5);
+    }
+
+    #[test]
+    fn halves_until_the_series_fits() {
+        let mut points: Vec<_> = (0..10).map(|t| point(t, 10.0, 20.0, 0.0)).collect();
+        downsample(&mut points, 3);
+
+        // 10 -> 5 -> 3, the odd point carried over as it was
+        assert_eq!(points.len(), 3);
+        assert_eq!(
+            points
+                .iter()
+                .map(|p| p.interval_seconds)
+                .collect::<Vec<_>>(),
+            [4.0, 4.0, 2.0]
+        );
+        assert_eq!(
+            points.iter().map(|p| p.timestamp).collect::<Vec<_>>(),
+            [3, 7, 9]
+        );
+        assert!(points.iter().all(|p| p.rps == 10.0));
+    }
+
+    #[test]
+    fn merged_points_weight_latency_by_requests() {
+        let mut points = vec![point(0, 30.0, 10.0, 0.0), point(1, 10.0, 50.0, 0.5)];
+        points[1].pool_saturated = true;
+        points[0].schedule_lag_ms = Some(4.0);
+
+        downsample(&mut points, 1);
+
+        let merged = &points[0];
+        assert_eq!(merged.interval_seconds, 2.0);
+        assert_eq!(merged.rps, 20.0);
+        assert_eq!(merged.avg_response_time, 20.0);
+        assert_eq!(merged.error_rate, 0.125);
+        assert_eq!(merged.min_response_time, 10.0);
+        assert_eq!(merged.max_response_time, 50.0);
+        assert_eq!(merged.target_rps, Some(20.0));
+        assert!(merged.pool_saturated);
+        assert_eq!(merged.schedule_lag_ms, Some(4.0));
+    }
+
+    #[test]
+    fn idle_intervals_dont_drag_the_minimum_to_zero() {
+        let mut points = vec![point(0, 0.0, 0.0, 0.0), point(1, 10.0, 30.0, 0.0)];
+        downsample(&mut points, 1);
+        assert_eq!(points[0].min_response_time, 30.0);
+    }
+
+    #[test]
+    fn steady_state_starts_once_the_ramp_is_over() {
+        // 10s ramp to 100 RPS, then 20s wobbling a little around it
+        let start = 1_000;
+        let ramp = (1..=10).map(|t| point(start + t, 10.0 * t as f64, 20.0, 0.0));
+        let steady = (11..=30).map(|t| point(start + t, 100.0 + (t % 3) as f64, 20.0, 0.0));
+        let points: Vec<_> = ramp.chain(steady).collect();
+
+        let steady_state = steady_state(&points, start).unwrap();
+
+        // The ramp's last point, at 100 RPS, already sits in the band
+        assert_eq!(steady_state.time_to_steady_state_seconds, 10.0);
+        assert!(steady_state.stability_score > 0.95);
+    }
+
+    #[test]
+    fn worst_interval_is_the_latency_spike() {
+        let mut points: Vec<_> = (0..30).map(|t| point(t, 50.0, 20.0, 0.0)).collect();
+        points[15].p95_response_time = 450.0;
+        points[15].error_rate = 0.05;
+        // An error burst at a normal latency doesn't outrank the spike
+        points[22].error_rate = 0.5;
+
+        let worst = worst_interval(&points).unwrap();
+
+        assert_eq!(worst.timestamp, 15);
+        assert_eq!(worst.p95, 450.0);
+        assert_eq!(worst.error_rate, 0.05);
+    }
+
+    #[test]
+    fn recovery_is_timed_from_the_error_peak_back_to_baseline() {
+        // A 1% error baseline, a burst peaking at t=12, back under 3% at t=16
+        let rates = [
+            (10, 0.2),
+            (11, 0.6),
+            (12, 0.9),
+            (13, 0.5),
+            (14, 0.2),
+            (15, 0.05),
+        ];
+        let mut points: Vec<_> = (0..30).map(|t| point(t, 50.0, 20.0, 0.01)).collect();
+        for (t, rate) in rates {
+            points[t].interval_error_rate = rate;
+        }
+
+        assert_eq!(error_recovery_seconds(&points), Some(4.0));
+    }
+
+    #[test]
+    fn no_recovery_time_without_a_spike() {
+        let points: Vec<_> = (0..30).map(|t| point(t, 50.0, 20.0, 0.05)).collect();
+        assert_eq!(error_recovery_seconds(&points), None);
+    }
+}
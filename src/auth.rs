@@ -0,0 +1,317 @@
+use crate::types::AuthConfig;
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use futures::future::BoxFuture;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+// Credentials are refreshed this long before they actually expire
+const REFRESH_MARGIN: Duration = Duration::from_secs(30);
+
+// Headers to attach to requests, and how long they stay valid (forever when None)
+pub struct Credentials {
+    pub headers: HeaderMap,
+    pub expires_in: Option<Duration>,
+}
+
+// A way of authenticating requests: `initial()` is called once, `refresh()` whenever
+// the credentials expire or the target answers 401
+pub trait AuthProvider: Send + Sync {
+    fn initial(&self) -> BoxFuture<'_, Result<Credentials>>;
+
+    fn refresh(&self) -> BoxFuture<'_, Result<Credentials>> {
+        self.initial()
+    }
+}
+
+pub fn provider(config: &AuthConfig, client: Client) -> Box<dyn AuthProvider> {
+    match config {
+        AuthConfig::Bearer { token } => Box::new(StaticHeader {
+            name: AUTHORIZATION.to_string(),
+            value: format!("Bearer {}", token),
+        }),
+        AuthConfig::Basic { username, password } => Box::new(StaticHeader {
+            name: AUTHORIZATION.to_string(),
+            value: format!(
+                "Basic {}",
+                BASE64.encode(format!("{}:{}", username, password))
+            ),
+        }),
+        AuthConfig::ApiKey { header, value } => Box::new(StaticHeader {
+            name: header.clone(),
+            value: value.clone(),
+        }),
+        AuthConfig::OAuthClientCredentials {
+            token_url,
+            client_id,
+            client_secret,
+            scope,
+        } => Box::new(ClientCredentials {
+            client,
+            token_url: token_url.clone(),
+            client_id: client_id.clone(),
+            client_secret: client_secret.clone(),
+            scope: scope.clone(),
+        }),
+    }
+}
+
+// Current credentials shared by every request of a test; the lock is held across a
+// refresh so concurrent requests wait for one refresh instead of each starting their own
+pub struct Auth {
+    provider: Box<dyn AuthProvider>,
+    current: Mutex<Option<(Credentials, Option<Instant>)>>,
+}
+
+impl Auth {
+    pub fn new(provider: Box<dyn AuthProvider>) -> Self {
+        Self {
+            provider,
+            current: Mutex::new(None),
+        }
+    }
+
+    pub async fn headers(&self) -> Result<HeaderMap> {
+        let mut current = self.current.lock().await;
+
+        let valid = current.as_ref().is_some_and(|(_, refresh_at)| {
+            refresh_at.is_none_or(|refresh_at| Instant::now() < refresh_at)
+        });
+        if !valid {
+            let credentials = match current.is_some() {
+                true => self.provider.refresh().await?,
+                false => self.provider.initial().await?,
+            };
+            let refresh_at = credentials
+                .expires_in
+                .map(|expires_in| Instant::now() + expires_in.saturating_sub(REFRESH_MARGIN));
+            *current = Some((credentials, refresh_at));
+        }
+
+        Ok(current
+            .as_ref()
+            .map(|(credentials, _)| credentials.headers.clone())
+            .unwrap_or_default())
+    }
+
+    // Forces a refresh before the next request, e.g. after a 401
+    pub async fn invalidate(&self) {
+        if let Some((_, refresh_at)) = self.current.lock().await.as_mut() {
+            *refresh_at = Some(Instant::now());
+        }
+    }
+}
+
+// Bearer tokens, basic credentials and API keys: a fixed header, Authorization for all
+// but API keys
+struct StaticHeader {
+    name: String,
+    value: String,
+}
+
+impl AuthProvider for StaticHeader {
+    fn initial(&self) -> BoxFuture<'_, Result<Credentials>> {
+        Box::pin(async move {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                HeaderName::from_bytes(self.name.as_bytes())?,
+                HeaderValue::from_str(&self.value)?,
+            );
+            Ok(Credentials {
+                headers,
+                expires_in: None,
+            })
+        })
+    }
+}
+
+// OAuth 2.0 client-credentials grant against `token_url`
+struct ClientCredentials {
+    client: Client,
+    token_url: String,
+    client_id: String,
+    client_secret: String,
+    scope: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+impl AuthProvider for ClientCredentials {
+    fn initial(&self) -> BoxFuture<'_, Result<Credentials>> {
+        Box::pin(async move {
+            let mut form = vec![
+                ("grant_type", "client_credentials"),
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.as_str()),
+            ];
+            if let Some(scope) = &self.scope {
+                form.push(("scope", scope.as_str()));
+            }
+
+            let token: TokenResponse = self
+                .client
+                .post(&self.token_url)
+                .form(&form)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await
+                .map_err(|e| anyhow!("invalid token response: {}", e))?;
+
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", token.access_token))?,
+            );
+            Ok(Credentials {
+                headers,
+                expires_in: token.expires_in.map(Duration::from_secs),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::{send_request, RequestPlan};
+    use crate::stats::Statistics;
+    use crate::test_support::{message, MockServer, Reply};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Hands out token-1, token-2, ... and only takes the second one
+    async fn token_server() -> MockServer {
+        let issued = AtomicUsize::new(0);
+        MockServer::start(move |request| match request.path.as_str() {
+            "/token" => {
+                let token = issued.fetch_add(1, Ordering::SeqCst) + 1;
+                Reply::ok().body(format!(r#"{{"access_token": "token-{}"}}"#, token))
+            }
+            _ => match request.header("authorization") {
+                Some("Bearer token-2") => Reply::ok(),
+                _ => Reply::status(401),
+            },
+        })
+        .await
+    }
+
+    // Sends one request to /api authenticated as `auth` says, with `fields` on top
+    async fn send_with_auth(
+        server: &MockServer,
+        auth: serde_json::Value,
+        fields: serde_json::Value,
+    ) -> Statistics {
+        let mut message = message(fields);
+        message.target_url = server.url("/api");
+        message.auth = serde_json::from_value(auth).unwrap();
+        let client = Client::new();
+        let auth = Auth::new(provider(message.auth.as_ref().unwrap(), client.clone()));
+        let stats = Mutex::new(Statistics::new());
+
+        let plan = RequestPlan::default();
+        send_request(
+            &client,
+            &message,
+            &stats,
+            plan,
+            &mut None,
+            Some(&auth),
+            None,
+        )
+        .await;
+
+        stats.into_inner()
+    }
+
+    // Sends one request to /api with OAuth against /token and `fields` on top
+    async fn send_with_oauth(server: &MockServer, fields: serde_json::Value) -> Statistics {
+        let auth = json!({
+            "type": "oauthClientCredentials",
+            "tokenUrl": server.url("/token"),
+            "clientId": "worker",
+            "clientSecret": "secret",
+        });
+        send_with_auth(server, auth, fields).await
+    }
+
+    fn authorizations(server: &MockServer) -> Vec<String> {
+        server
+            .received()
+            .iter()
+            .filter(|request| request.path == "/api")
+            .filter_map(|request| request.header("authorization").map(str::to_string))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn retry_after_a_401_goes_out_with_a_refreshed_token() {
+        let server = token_server().await;
+
+        let stats = send_with_oauth(
+            &server,
+            json!({ "retries": 1, "retryableStatusCodes": [401] }),
+        )
+        .await;
+
+        assert_eq!(stats.successful_requests, 1);
+        assert_eq!(stats.retries, 1);
+        assert_eq!(
+            authorizations(&server),
+            ["Bearer token-1", "Bearer token-2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn a_401_is_resent_once_without_retry_settings() {
+        let server = token_server().await;
+
+        let stats = send_with_oauth(&server, json!({})).await;
+
+        assert_eq!(stats.successful_requests, 1);
+        assert_eq!(stats.failed_requests, 0);
+        assert_eq!(
+            authorizations(&server),
+            ["Bearer token-1", "Bearer token-2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn static_credentials_go_out_in_their_header() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+        let cases = [
+            (
+                json!({ "type": "bearer", "token": "abc" }),
+                "authorization",
+                "Bearer abc",
+            ),
+            (
+                json!({ "type": "basic", "username": "ann", "password": "s3cret" }),
+                "authorization",
+                "Basic YW5uOnMzY3JldA==",
+            ),
+            (
+                json!({ "type": "apiKey", "header": "X-API-Key", "value": "k-123" }),
+                "x-api-key",
+                "k-123",
+            ),
+        ];
+
+        for (auth, header, expected) in cases {
+            let stats = send_with_auth(&server, auth.clone(), json!({})).await;
+
+            assert_eq!(stats.successful_requests, 1, "{}", auth);
+            let received = server.received().pop().unwrap();
+            assert_eq!(received.header(header), Some(expected), "{}", auth);
+        }
+    }
+}
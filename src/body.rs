@@ -0,0 +1,223 @@
+// Non-JSON encodings of the request body, chosen with `bodyType`. Form and multipart bodies
+// are JSON objects whose fields become the form's fields: strings as they are, anything
+// else as its JSON text. A multipart field given as an object with a `filename` is a file
+// part, holding its `content` or else `sizeBytes` of filler.
+use crate::types::BodyType;
+use anyhow::{bail, Result};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+const DEFAULT_RAW_CONTENT_TYPE: &str = "text/plain";
+const DEFAULT_FILE_CONTENT_TYPE: &str = "application/octet-stream";
+
+// Checked once up front, so every request's body can be encoded
+pub fn check(body_type: BodyType, body: &Value) -> Result<()> {
+    match (body_type, body) {
+        (BodyType::Json, _) | (BodyType::Raw, Value::String(_)) => Ok(()),
+        (BodyType::Raw, _) => bail!("a raw body must be a string"),
+        (BodyType::Form, Value::Object(fields)) => {
+            if fields.values().any(Value::is_object) {
+                bail!("form body fields can't be objects");
+            }
+            Ok(())
+        }
+        (BodyType::Multipart, Value::Object(fields)) => {
+            for (name, field) in fields {
+                if let Value::Object(part) = field {
+                    if !part.get("filename").is_some_and(Value::is_string) {
+                        bail!(
+                            "multipart field {} needs a filename to be a file part",
+                            name
+                        );
+                    }
+                }
+            }
+            Ok(())
+        }
+        (BodyType::Form | BodyType::Multipart, _) => bail!("a form body must be an object"),
+    }
+}
+
+pub fn form_fields(body: &Value) -> Vec<(String, String)> {
+    let Value::Object(fields) = body else {
+        return vec![];
+    };
+    fields
+        .iter()
+        .map(|(name, field)| (name.clone(), text(field)))
+        .collect()
+}
+
+// The raw body's text and content type
+pub fn raw(body: &Value, content_type: Option<&str>) -> (String, Vec<u8>) {
+    let content_type = content_type.unwrap_or(DEFAULT_RAW_CONTENT_TYPE);
+    (content_type.to_string(), text(body).into_bytes())
+}
+
+// The multipart body and its content type, with a fresh boundary
+pub fn multipart(body: &Value) -> (String, Vec<u8>) {
+    let boundary = format!("loadmaster-{}", Uuid::new_v4().simple());
+    let mut encoded = Vec::new();
+
+    if let Value::Object(fields) = body {
+        for (name, field) in fields {
+            encoded.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+            match field {
+                Value::Object(part) => file_part(&mut encoded, name, part),
+                _ => {
+                    encoded.extend_from_slice(
+                        format!(
+                            "Content-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                            escape(name)
+                        )
+                        .as_bytes(),
+                    );
+                    encoded.extend_from_slice(text(field).as_bytes());
+                }
+            }
+            encoded.extend_from_slice(b"\r\n");
+        }
+    }
+    encoded.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+    let content_type = format!("multipart/form-data; boundary={}", boundary);
+    (content_type, encoded)
+}
+
+fn file_part(encoded: &mut Vec<u8>, name: &str, part: &Map<String, Value>) {
+    let filename = part.get("filename").and_then(Value::as_str).unwrap_or(name);
+    let content_type = part
+        .get("contentType")
+        .and_then(Value::as_str)
+        .unwrap_or(DEFAULT_FILE_CONTENT_TYPE);
+    encoded.extend_from_slice(
+        format!(
+            "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+             Content-Type: {}\r\n\r\n",
+            escape(name),
+            escape(filename),
+            content_type
+        )
+        .as_bytes(),
+    );
+
+    match part.get("content") {
+        Some(content) => encoded.extend_from_slice(text(content).as_bytes()),
+        None => {
+            let size = part.get("sizeBytes").and_then(Value::as_u64).unwrap_or(0);
+            encoded.resize(encoded.len() + size as usize, b'x');
+        }
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        _ => value.to_string(),
+    }
+}
+
+// Quotes and line breaks can't appear inside a quoted parameter
+fn escape(name: &str) -> String {
+    name.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::request::{send_request, RequestPlan};
+    use crate::stats::Statistics;
+    use crate::test_support::{message, MockServer, Received, Reply};
+    use reqwest::Client;
+    use serde_json::{json, Value};
+    use tokio::sync::Mutex;
+
+    // What the mock received for one POST with the message `fields`
+    async fn sent(fields: Value) -> Received {
+        let server = MockServer::start(|_| Reply::ok()).await;
+        let mut message = message(fields);
+        message.target_url = server.url("/");
+        message.method = "POST".to_string();
+
+        let stats = Mutex::new(Statistics::new());
+        let plan = RequestPlan::default();
+        send_request(
+            &Client::new(),
+            &message,
+            &stats,
+            plan,
+            &mut None,
+            None,
+            None,
+        )
+        .await;
+        assert_eq!(stats.into_inner().successful_requests, 1);
+        server.received().remove(0)
+    }
+
+    #[tokio::test]
+    async fn json_bodies_are_sent_as_json() {
+        let received = sent(json!({ "body": { "name": "a b" } })).await;
+
+        assert_eq!(received.header("content-type"), Some("application/json"));
+        assert_eq!(received.body_text(), r#"{"name":"a b"}"#);
+    }
+
+    #[tokio::test]
+    async fn form_bodies_are_url_encoded() {
+        let received = sent(json!({
+            "bodyType": "form",
+            "body": { "name": "a b", "count": 2 },
+        }))
+        .await;
+
+        assert_eq!(
+            received.header("content-type"),
+            Some("application/x-www-form-urlencoded")
+        );
+        assert_eq!(received.body_text(), "count=2&name=a+b");
+    }
+
+    #[tokio::test]
+    async fn multipart_bodies_hold_fields_and_file_parts() {
+        let received = sent(json!({
+            "bodyType": "multipart",
+            "body": {
+                "name": "a",
+                "upload": { "filename": "data.bin", "sizeBytes": 4 },
+            },
+        }))
+        .await;
+
+        let content_type = received.header("content-type").unwrap();
+        let boundary = content_type
+            .strip_prefix("multipart/form-data; boundary=")
+            .unwrap();
+        let expected = format!(
+            "--{b}\r\n\
+             Content-Disposition: form-data; name=\"name\"\r\n\r\n\
+             a\r\n\
+             --{b}\r\n\
+             Content-Disposition: form-data; name=\"upload\"; filename=\"data.bin\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n\
+             xxxx\r\n\
+             --{b}--\r\n",
+            b = boundary
+        );
+        assert_eq!(received.body_text(), expected);
+    }
+
+    #[tokio::test]
+    async fn raw_bodies_are_sent_as_is_with_their_content_type() {
+        let received = sent(json!({
+            "bodyType": "raw",
+            "body": "<order id=\"1\"/>",
+            "bodyContentType": "application/xml",
+        }))
+        .await;
+
+        assert_eq!(received.header("content-type"), Some("application/xml"));
+        assert_eq!(received.body_text(), "<order id=\"1\"/>");
+    }
+}
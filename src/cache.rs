@@ -0,0 +1,101 @@
+// Opt-in local cache for identical GETs, so repeated hits on an idempotent URL load
+// what's behind the edge cache instead of the same resource over and over
+use reqwest::header::HeaderMap;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub struct ResponseCache {
+    ttl: Duration,
+    // Cache key -> when the cached response expires
+    entries: Mutex<HashMap<String, Instant>>,
+}
+
+impl ResponseCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // URL plus every header the request goes out with, in a stable order
+    pub fn key(url: &str, headers: &HeaderMap) -> String {
+        let mut headers: Vec<_> = headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_bytes()))
+            .collect();
+        headers.sort();
+        format!("{} {:?}", url, headers)
+    }
+
+    pub fn is_fresh(&self, key: &str) -> bool {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)
+            .is_some_and(|expires_at| Instant::now() < *expires_at)
+    }
+
+    // Caches `key` for the TTL, dropping entries that have expired since
+    pub fn store(&self, key: String) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, expires_at| now < *expires_at);
+        entries.insert(key, now + self.ttl);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderValue, AUTHORIZATION};
+    use std::thread::sleep;
+
+    const URL: &str = "http://localhost/items";
+
+    #[test]
+    fn stored_key_is_fresh_within_the_ttl() {
+        let cache = ResponseCache::new(Duration::from_secs(60));
+        let key = ResponseCache::key(URL, &HeaderMap::new());
+
+        assert!(!cache.is_fresh(&key));
+        cache.store(key.clone());
+        assert!(cache.is_fresh(&key));
+    }
+
+    #[test]
+    fn stored_key_goes_stale_after_the_ttl() {
+        let cache = ResponseCache::new(Duration::from_millis(20));
+        let key = ResponseCache::key(URL, &HeaderMap::new());
+
+        cache.store(key.clone());
+        sleep(Duration::from_millis(30));
+
+        assert!(!cache.is_fresh(&key));
+    }
+
+    #[test]
+    fn different_headers_get_different_keys() {
+        let mut alice = HeaderMap::new();
+        alice.insert(AUTHORIZATION, HeaderValue::from_static("Bearer alice"));
+        let mut bob = HeaderMap::new();
+        bob.insert(AUTHORIZATION, HeaderValue::from_static("Bearer bob"));
+
+        let key = ResponseCache::key(URL, &alice);
+        assert_ne!(key, ResponseCache::key(URL, &bob));
+        assert_ne!(key, ResponseCache::key(URL, &HeaderMap::new()));
+        assert_eq!(key, ResponseCache::key(URL, &alice.clone()));
+    }
+
+    #[test]
+    fn storing_purges_expired_keys() {
+        let cache = ResponseCache::new(Duration::from_millis(20));
+        cache.store("expired".to_string());
+        sleep(Duration::from_millis(30));
+
+        cache.store("fresh".to_string());
+
+        let entries = cache.entries.lock().unwrap();
+        assert_eq!(entries.keys().collect::<Vec<_>>(), ["fresh"]);
+    }
+}
@@ -0,0 +1,102 @@
+// Operator commands for tests already running on this worker
+use crate::types::CancelTestMessage;
+use anyhow::Result;
+use futures_lite::stream::StreamExt;
+use lapin::{options::*, types::FieldTable, Channel, ExchangeKind};
+use log::{error, info};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio_util::sync::CancellationToken;
+
+// Cancel messages are published to this fanout exchange, so every worker sees every one
+pub const CANCEL_EXCHANGE: &str = "cancel_tests";
+
+// Cancellation handle of every test this worker is running or about to run, by test id
+pub type Registry = Arc<Mutex<HashMap<String, CancellationToken>>>;
+
+// Binds a queue of this worker's own to the cancel exchange; it goes away with the
+// connection. Returns the queue's name.
+pub async fn declare_cancellations(channel: &Channel) -> Result<String> {
+    channel
+        .exchange_declare(
+            CANCEL_EXCHANGE,
+            ExchangeKind::Fanout,
+            ExchangeDeclareOptions {
+                durable: true,
+                ..Default::default()
+            },
+            FieldTable::default(),
+        )
+        .await?;
+    let queue = channel
+        .queue_declare(
+            "",
+            QueueDeclareOptions {
+                exclusive: true,
+                auto_delete: true,
+                ..Default::default()
+            },
+            FieldTable::default(),
+        )
+        .await?;
+    channel
+        .queue_bind(
+            queue.name().as_str(),
+            CANCEL_EXCHANGE,
+            "",
+            QueueBindOptions::default(),
+            FieldTable::default(),
+        )
+        .await?;
+    Ok(queue.name().to_string())
+}
+
+// Cancels tests named on this worker's cancel queue. Every worker gets every cancel, so
+// ones for tests running elsewhere are expected and only logged.
+pub async fn consume_cancellations(
+    channel: Channel,
+    queue: String,
+    consumer_tag: String,
+    registry: Registry,
+) {
+    let mut consumer = match channel
+        .basic_consume(
+            &queue,
+            &consumer_tag,
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await
+    {
+        Ok(consumer) => consumer,
+        Err(e) => {
+            error!("❌ Failed to consume {}: {}", queue, e);
+            return;
+        }
+    };
+
+    while let Some(delivery) = consumer.next().await {
+        let delivery = match delivery {
+            Ok(delivery) => delivery,
+            Err(e) => {
+                error!("❌ Cancel consumer error: {}", e);
+                continue;
+            }
+        };
+
+        match serde_json::from_slice::<CancelTestMessage>(&delivery.data) {
+            Ok(cancel) => match registry.lock().unwrap().get(&cancel.test_id) {
+                Some(token) => {
+                    info!("🛑 Cancelling load test {}", cancel.test_id);
+                    token.cancel();
+                }
+                None => info!("🛑 Load test {} isn't running here", cancel.test_id),
+            },
+            Err(e) => error!("❌ Rejected invalid cancel message: {}", e),
+        }
+
+        if let Err(e) = delivery.ack(BasicAckOptions::default()).await {
+            error!("Failed to ack cancel message: {}", e);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+// Rate coordination between shards of one test. Every second each shard tells the others
+// on the `rate_coordination` exchange how many requests it has sent, then re-targets its
+// own rate so the shards together catch up with `globalRps` even when one of them lags.
+use crate::stats::Statistics;
+use crate::types::RateCoordinationConfig;
+use anyhow::Result;
+use futures_lite::stream::StreamExt;
+use lapin::options::*;
+use lapin::types::FieldTable;
+use lapin::{BasicProperties, Channel, ExchangeKind};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tokio::time::interval;
+use uuid::Uuid;
+
+const EXCHANGE: &str = "rate_coordination";
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+// Peers silent for longer are assumed finished or gone: their requests still count,
+// but they no longer take a share of the rate
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ShardReport {
+    shard: String,
+    requests: u64,
+}
+
+pub struct Coordinator {
+    config: RateCoordinationConfig,
+    shard: String,
+    // This shard's current target rate, as f64 bits
+    rate: Arc<AtomicU64>,
+    // Latest report of every other shard, by shard id
+    peers: Arc<Mutex<HashMap<String, (u64, Instant)>>>,
+}
+
+impl Coordinator {
+    pub fn new(config: &RateCoordinationConfig, initial_rps: f64) -> Self {
+        Self {
+            config: config.clone(),
+            shard: Uuid::new_v4().simple().to_string(),
+            rate: Arc::new(AtomicU64::new(initial_rps.to_bits())),
+            peers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn rate(&self) -> Arc<AtomicU64> {
+        self.rate.clone()
+    }
+
+    pub fn global_rps(&self) -> f64 {
+        self.config.global_rps
+    }
+
+    // Requests sent by every shard heard from, this one's included
+    pub fn aggregate_requests(&self, own: u64) -> u64 {
+        let peers = self.peers.lock().unwrap();
+        own + peers.values().map(|(requests, _)| requests).sum::<u64>()
+    }
+
+    // Reports this shard's progress and rebalances its rate until the returned task is
+    // aborted
+    pub async fn start(
+        &self,
+        channel: &Channel,
+        stats: Arc<tokio::sync::Mutex<Statistics>>,
+        start_time: Instant,
+    ) -> Result<JoinHandle<()>> {
+        channel
+            .exchange_declare(
+                EXCHANGE,
+                ExchangeKind::Topic,
+                ExchangeDeclareOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+        let queue = channel
+            .queue_declare(
+                "",
+                QueueDeclareOptions {
+                    exclusive: true,
+                    auto_delete: true,
+                    ..Default::default()
+                },
+                FieldTable::default(),
+            )
+            .await?;
+        channel
+            .queue_bind(
+                queue.name().as_str(),
+                EXCHANGE,
+                &self.config.group,
+                QueueBindOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+        let mut reports = channel
+            .basic_consume(
+                queue.name().as_str(),
+                &format!("{}-coordination", self.shard),
+                BasicConsumeOptions {
+                    no_ack: true,
+                    ..Default::default()
+                },
+                FieldTable::default(),
+            )
+            .await?;
+
+        let channel = channel.clone();
+        let group = self.config.group.clone();
+        let global_rps = self.config.global_rps;
+        let shard = self.shard.clone();
+        let rate = self.rate.clone();
+        let peers = self.peers.clone();
+
+        Ok(tokio::spawn(async move {
+            let mut ticker = interval(REPORT_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    Some(Ok(delivery)) = reports.next() => {
+                        match serde_json::from_slice::<ShardReport>(&delivery.data) {
+                            Ok(report) if report.shard != shard => {
+                                peers
+                                    .lock()
+                                    .unwrap()
+                                    .insert(report.shard, (report.requests, Instant::now()));
+                            }
+                            Ok(_) => {}
+                            Err(e) => warn!("⚖️ Ignoring malformed shard report: {}", e),
+                        }
+                        continue;
+                    }
+                }
+
+                let own = {
+                    let stats = stats.lock().await;
+                    (stats.total_requests + stats.warmup_requests) as u64
+                };
+                let report = ShardReport {
+                    shard: shard.clone(),
+                    requests: own,
+                };
+                if let Ok(payload) = serde_json::to_vec(&report) {
+                    // Best effort: peers keep using the previous report until the next
+                    let _ = channel
+                        .basic_publish(
+                            EXCHANGE,
+                            &group,
+                            BasicPublishOptions::default(),
+                            &payload,
+                            BasicProperties::default(),
+                        )
+                        .await;
+                }
+
+                let (shards, sent) = {
+                    let peers = peers.lock().unwrap();
+                    let active = peers
+                        .values()
+                        .filter(|(_, heard)| heard.elapsed() < PEER_TIMEOUT)
+                        .count();
+                    let sent = own + peers.values().map(|(requests, _)| requests).sum::<u64>();
+                    (active as f64 + 1.0, sent)
+                };
+
+                // An even share of the global rate, plus this shard's share of whatever
+                // the group as a whole is behind (or ahead) by, made up over the next
+                // interval
+                let expected = global_rps * start_time.elapsed().as_secs_f64();
+                let deficit = expected - sent as f64;
+                let target = (global_rps + deficit / REPORT_INTERVAL.as_secs_f64()) / shards;
+                rate.store(target.clamp(0.0, global_rps).to_bits(), Ordering::Relaxed);
+            }
+        }))
+    }
+}
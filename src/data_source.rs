@@ -0,0 +1,181 @@
+// Rows of test data from a CSV, handed out round-robin so consecutive requests fill
+// their `{{column}}` placeholders from consecutive rows, wrapping around once all of them
+// have been used
+use crate::types::DataSourceConfig;
+use anyhow::{anyhow, bail, Result};
+use reqwest::Client;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub struct DataSource {
+    rows: Vec<HashMap<String, String>>,
+    next: AtomicUsize,
+}
+
+impl DataSource {
+    // The first record names the columns unless `columns` does
+    pub fn parse(csv: &str, columns: Option<&[String]>) -> Result<Self> {
+        let mut records = records(csv)?.into_iter();
+        let header = match columns {
+            Some(columns) => columns.to_vec(),
+            None => records
+                .next()
+                .ok_or_else(|| anyhow!("dataSource csv has no header row"))?,
+        };
+
+        let rows = records
+            .enumerate()
+            .map(|(index, record)| {
+                if record.len() != header.len() {
+                    bail!(
+                        "dataSource row {} has {} fields, expected {}",
+                        index + 1,
+                        record.len(),
+                        header.len()
+                    );
+                }
+                Ok(header.iter().cloned().zip(record).collect())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if rows.is_empty() {
+            bail!("dataSource has no rows");
+        }
+
+        Ok(Self {
+            rows,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    pub async fn load(config: &DataSourceConfig, client: &Client) -> Result<Self> {
+        let columns = config.columns.as_deref();
+        match (&config.csv, &config.csv_url) {
+            (Some(csv), _) => Self::parse(csv, columns),
+            (None, Some(url)) => {
+                let csv = client
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?;
+                Self::parse(&csv, columns)
+            }
+            // Already checked by `validate()`
+            (None, None) => bail!("dataSource needs csv or csvUrl"),
+        }
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn next_row(&self) -> &HashMap<String, String> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed);
+        &self.rows[index % self.rows.len()]
+    }
+}
+
+// RFC 4180 records: comma-separated, double-quoted fields may hold commas, line breaks
+// and doubled quotes. Blank lines are skipped.
+fn records(csv: &str) -> Result<Vec<Vec<String>>> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = csv.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+
+    if quoted {
+        bail!("dataSource csv has an unterminated quoted field");
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    records.retain(|record| !(record.len() == 1 && record[0].is_empty()));
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(source: &DataSource) -> Vec<(String, String)> {
+        let mut fields: Vec<_> = source.next_row().clone().into_iter().collect();
+        fields.sort();
+        fields
+    }
+
+    fn fields(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_quoted_fields_and_crlf() {
+        let records = records("a,\"b,c\",\"say \"\"hi\"\"\"\r\n\"two\nlines\",,x\r\n").unwrap();
+        assert_eq!(
+            records,
+            vec![vec!["a", "b,c", "say \"hi\""], vec!["two\nlines", "", "x"]]
+        );
+    }
+
+    #[test]
+    fn skips_blank_lines_and_keeps_a_final_unterminated_record() {
+        let records = records("a,b\n\n1,2\n\n3,4").unwrap();
+        assert_eq!(
+            records,
+            vec![vec!["a", "b"], vec!["1", "2"], vec!["3", "4"]]
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_quotes_and_ragged_rows() {
+        assert!(records("a,\"b\n").is_err());
+        assert!(DataSource::parse("id,name\n1\n", None).is_err());
+        assert!(DataSource::parse("id,name\n", None).is_err());
+        assert!(DataSource::parse("", None).is_err());
+    }
+
+    #[test]
+    fn hands_out_rows_round_robin() {
+        let source = DataSource::parse("id,name\n1,ann\n2,bo\n", None).unwrap();
+
+        assert_eq!(source.row_count(), 2);
+        assert_eq!(row(&source), fields(&[("id", "1"), ("name", "ann")]));
+        assert_eq!(row(&source), fields(&[("id", "2"), ("name", "bo")]));
+        assert_eq!(row(&source), fields(&[("id", "1"), ("name", "ann")]));
+    }
+
+    #[test]
+    fn named_columns_make_every_record_a_row() {
+        let columns = ["user".to_string()];
+        let source = DataSource::parse("alice\nbob\n", Some(&columns)).unwrap();
+
+        assert_eq!(source.row_count(), 2);
+        assert_eq!(row(&source), fields(&[("user", "alice")]));
+    }
+}
@@ -0,0 +1,116 @@
+use hdrhistogram::Histogram;
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use std::net::ToSocketAddrs;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+// Resolver that records how long each lookup takes. Lookups happen when a new
+// connection is opened, so pooled requests don't add samples.
+pub struct TimedResolver {
+    inner: Arc<dyn Resolve>,
+    lookup_times: Arc<Mutex<Histogram<u64>>>,
+}
+
+impl TimedResolver {
+    pub fn new(lookup_times: Arc<Mutex<Histogram<u64>>>) -> Self {
+        Self {
+            inner: Arc::new(SystemResolver),
+            lookup_times,
+        }
+    }
+
+    // Times `inner`'s lookups instead of the system resolver's
+    #[cfg(test)]
+    pub fn wrapping(inner: Arc<dyn Resolve>, lookup_times: Arc<Mutex<Histogram<u64>>>) -> Self {
+        Self {
+            inner,
+            lookup_times,
+        }
+    }
+}
+
+impl Resolve for TimedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let lookup = self.inner.resolve(name);
+        let lookup_times = self.lookup_times.clone();
+
+        Box::pin(async move {
+            let start = Instant::now();
+            let addrs = lookup.await?;
+
+            lookup_times
+                .lock()
+                .unwrap()
+                .record(start.elapsed().as_micros() as u64)
+                .ok();
+
+            Ok(addrs)
+        })
+    }
+}
+
+struct SystemResolver;
+
+impl Resolve for SystemResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+
+            // getaddrinfo blocks, so keep it off the runtime threads
+            let addrs =
+                tokio::task::spawn_blocking(move || (host.as_str(), 0).to_socket_addrs()).await??;
+
+            Ok(Box::new(addrs) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::Statistics;
+    use crate::test_support::{MockServer, Reply};
+    use reqwest::Client;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+    use tokio::time::sleep;
+
+    // Resolves every name to `address` after `delay`
+    struct SlowResolver {
+        address: SocketAddr,
+        delay: Duration,
+    }
+
+    impl Resolve for SlowResolver {
+        fn resolve(&self, _: Name) -> Resolving {
+            let (address, delay) = (self.address, self.delay);
+            Box::pin(async move {
+                sleep(delay).await;
+                Ok(Box::new(std::iter::once(address)) as Addrs)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_lookups_show_in_the_dns_time() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+        let url: reqwest::Url = server.url("/").parse().unwrap();
+        let address = SocketAddr::new("127.0.0.1".parse().unwrap(), url.port().unwrap());
+        let stats = Statistics::new();
+
+        let slow = SlowResolver {
+            address,
+            delay: Duration::from_millis(50),
+        };
+        let resolver = TimedResolver::wrapping(Arc::new(slow), stats.dns_times.clone());
+        let client = Client::builder()
+            .dns_resolver(Arc::new(resolver))
+            .build()
+            .unwrap();
+        let target = format!("http://slow.test:{}/", address.port());
+        client.get(&target).send().await.unwrap();
+
+        assert!(stats.get_dns_time_percentile(95.0) >= 50.0);
+    }
+}
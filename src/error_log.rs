@@ -0,0 +1,101 @@
+use log::warn;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// Occurrences of each distinct error logged individually before switching to summaries
+const LOGGED_PER_ERROR: u64 = 5;
+
+// How often suppressed errors are summarized
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(10);
+
+// Logs request errors without flooding: the first few of each kind verbatim, then a
+// periodic "N more" line per kind
+pub struct ErrorLog {
+    seen: HashMap<String, u64>,
+    suppressed: HashMap<String, u64>,
+    last_summary: Instant,
+}
+
+impl ErrorLog {
+    pub fn new() -> Self {
+        Self {
+            seen: HashMap::new(),
+            suppressed: HashMap::new(),
+            last_summary: Instant::now(),
+        }
+    }
+
+    pub fn record(&mut self, error: &str) {
+        if let Some(line) = self.line(error) {
+            warn!("{}", line);
+        }
+    }
+
+    // Summarizes suppressed errors once the interval has passed
+    pub fn summarize_if_due(&mut self) {
+        if self.last_summary.elapsed() >= SUMMARY_INTERVAL {
+            self.summarize();
+        }
+    }
+
+    pub fn summarize(&mut self) {
+        for line in self.summary() {
+            warn!("{}", line);
+        }
+    }
+
+    // What to log for this occurrence of `error`; None once it's being suppressed
+    fn line(&mut self, error: &str) -> Option<String> {
+        let seen = self.seen.entry(error.to_string()).or_insert(0);
+        *seen += 1;
+
+        if *seen <= LOGGED_PER_ERROR {
+            return Some(format!("❌ Request failed: {}", error));
+        }
+        *self.suppressed.entry(error.to_string()).or_insert(0) += 1;
+        None
+    }
+
+    // One line per kind of error suppressed since the last summary
+    fn summary(&mut self) -> Vec<String> {
+        self.last_summary = Instant::now();
+
+        self.suppressed
+            .drain()
+            .map(|(error, count)| format!("❌ {} more \"{}\" errors", count, error))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_errors_are_summarized_rather_than_logged_each_time() {
+        let mut log = ErrorLog::new();
+
+        let logged: Vec<String> = (0..1000).filter_map(|_| log.line("timeout")).collect();
+        assert_eq!(logged.len(), LOGGED_PER_ERROR as usize);
+        assert!(logged
+            .iter()
+            .all(|line| line == "❌ Request failed: timeout"));
+
+        assert_eq!(log.summary(), ["❌ 995 more \"timeout\" errors"]);
+        // Counted afresh after each summary
+        log.line("timeout");
+        assert_eq!(log.summary(), ["❌ 1 more \"timeout\" errors"]);
+        assert!(log.summary().is_empty());
+    }
+
+    #[test]
+    fn each_kind_of_error_gets_its_own_allowance() {
+        let mut log = ErrorLog::new();
+        for _ in 0..LOGGED_PER_ERROR {
+            log.line("timeout");
+        }
+
+        assert!(log.line("timeout").is_none());
+        assert!(log.line("connection_refused").is_some());
+    }
+}
@@ -0,0 +1,363 @@
+// Tiny arithmetic language for `loadExpression`, e.g. `100 + 50*sin(t/10)`.
+// Only numbers, the variable `t`, `pi`, + - * / ^, parentheses and a fixed set of
+// math functions are accepted, so evaluating an expression can't do anything else.
+use anyhow::{anyhow, bail, Result};
+
+// Expressions arrive with every test message, so their size and nesting are bounded to
+// keep parsing (and evaluating) well within a worker thread's stack
+const MAX_SOURCE_LENGTH: usize = 1024;
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct Expression {
+    root: Node,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Number(f64),
+    Time,
+    Negate(Box<Node>),
+    Binary(Box<Node>, Operator, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Function {
+    Sin,
+    Cos,
+    Abs,
+    Sqrt,
+    Exp,
+    Ln,
+    Floor,
+    Min,
+    Max,
+}
+
+impl Function {
+    fn parse(name: &str) -> Option<(Self, usize)> {
+        Some(match name {
+            "sin" => (Function::Sin, 1),
+            "cos" => (Function::Cos, 1),
+            "abs" => (Function::Abs, 1),
+            "sqrt" => (Function::Sqrt, 1),
+            "exp" => (Function::Exp, 1),
+            "ln" => (Function::Ln, 1),
+            "floor" => (Function::Floor, 1),
+            "min" => (Function::Min, 2),
+            "max" => (Function::Max, 2),
+            _ => return None,
+        })
+    }
+
+    fn apply(&self, args: &[f64]) -> f64 {
+        match self {
+            Function::Sin => args[0].sin(),
+            Function::Cos => args[0].cos(),
+            Function::Abs => args[0].abs(),
+            Function::Sqrt => args[0].sqrt(),
+            Function::Exp => args[0].exp(),
+            Function::Ln => args[0].ln(),
+            Function::Floor => args[0].floor(),
+            Function::Min => args[0].min(args[1]),
+            Function::Max => args[0].max(args[1]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Identifier(String),
+    Symbol(char),
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self> {
+        if source.len() > MAX_SOURCE_LENGTH {
+            bail!(
+                "loadExpression is longer than {} characters",
+                MAX_SOURCE_LENGTH
+            );
+        }
+
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            depth: 0,
+        };
+
+        let root = parser.expression()?;
+        if let Some(token) = parser.peek() {
+            bail!("unexpected {:?} in loadExpression", token);
+        }
+
+        Ok(Self { root })
+    }
+
+    // Value at `t` seconds into the test
+    pub fn evaluate(&self, t: f64) -> f64 {
+        evaluate(&self.root, t)
+    }
+}
+
+fn evaluate(node: &Node, t: f64) -> f64 {
+    match node {
+        Node::Number(value) => *value,
+        Node::Time => t,
+        Node::Negate(inner) => -evaluate(inner, t),
+        Node::Binary(left, operator, right) => {
+            let (left, right) = (evaluate(left, t), evaluate(right, t));
+            match operator {
+                Operator::Add => left + right,
+                Operator::Subtract => left - right,
+                Operator::Multiply => left * right,
+                Operator::Divide => left / right,
+                Operator::Power => left.powf(right),
+            }
+        }
+        Node::Call(function, args) => {
+            let args: Vec<f64> = args.iter().map(|arg| evaluate(arg, t)).collect();
+            function.apply(&args)
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = source.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit() || **c == '.') {
+                number.push(c);
+                chars.next();
+            }
+            let value = number
+                .parse()
+                .map_err(|_| anyhow!("invalid number {:?} in loadExpression", number))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_ascii_alphabetic() {
+            let mut identifier = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphanumeric()) {
+                identifier.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Identifier(identifier));
+        } else if "+-*/^(),".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            bail!("unexpected character {:?} in loadExpression", c);
+        }
+    }
+
+    Ok(tokens)
+}
+
+// Recursive descent, lowest precedence first: + -, then * /, then unary minus, then ^
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    // Every recursion passes through `unary`, which counts how deep it is
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<()> {
+        if !self.eat(symbol) {
+            bail!("expected '{}' in loadExpression", symbol);
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self) -> Result<Node> {
+        let mut node = self.term()?;
+        loop {
+            let operator = if self.eat('+') {
+                Operator::Add
+            } else if self.eat('-') {
+                Operator::Subtract
+            } else {
+                return Ok(node);
+            };
+            node = Node::Binary(Box::new(node), operator, Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Node> {
+        let mut node = self.unary()?;
+        loop {
+            let operator = if self.eat('*') {
+                Operator::Multiply
+            } else if self.eat('/') {
+                Operator::Divide
+            } else {
+                return Ok(node);
+            };
+            node = Node::Binary(Box::new(node), operator, Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node> {
+        if self.depth >= MAX_DEPTH {
+            bail!(
+                "loadExpression is nested more than {} levels deep",
+                MAX_DEPTH
+            );
+        }
+        self.depth += 1;
+        let node = match self.eat('-') {
+            true => self.unary().map(|inner| Node::Negate(Box::new(inner))),
+            false => self.power(),
+        };
+        self.depth -= 1;
+        node
+    }
+
+    fn power(&mut self) -> Result<Node> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            // Right-associative: 2^3^2 is 2^(3^2)
+            return Ok(Node::Binary(
+                Box::new(base),
+                Operator::Power,
+                Box::new(self.unary()?),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Node> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Node::Number(value)),
+            Some(Token::Symbol('(')) => {
+                let node = self.expression()?;
+                self.expect(')')?;
+                Ok(node)
+            }
+            Some(Token::Identifier(name)) => match name.as_str() {
+                "t" => Ok(Node::Time),
+                "pi" => Ok(Node::Number(std::f64::consts::PI)),
+                _ => {
+                    let (function, arity) = Function::parse(&name)
+                        .ok_or_else(|| anyhow!("unknown name {:?} in loadExpression", name))?;
+
+                    self.expect('(')?;
+                    let mut args = vec![self.expression()?];
+                    while self.eat(',') {
+                        args.push(self.expression()?);
+                    }
+                    self.expect(')')?;
+
+                    if args.len() != arity {
+                        bail!("{} takes {} argument(s) in loadExpression", name, arity);
+                    }
+                    Ok(Node::Call(function, args))
+                }
+            },
+            Some(token) => bail!("unexpected {:?} in loadExpression", token),
+            None => bail!("loadExpression ended unexpectedly"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(source: &str, t: f64) -> f64 {
+        Expression::parse(source).unwrap().evaluate(t)
+    }
+
+    #[test]
+    fn follows_precedence_and_associativity() {
+        assert_eq!(value("1 + 2 * 3", 0.0), 7.0);
+        assert_eq!(value("(1 + 2) * 3", 0.0), 9.0);
+        assert_eq!(value("10 - 4 - 3", 0.0), 3.0);
+        assert_eq!(value("2 ^ 3 ^ 2", 0.0), 512.0);
+        assert_eq!(value("-2 ^ 2", 0.0), -4.0);
+        assert_eq!(value("8 / 2 / 2", 0.0), 2.0);
+    }
+
+    #[test]
+    fn evaluates_time_constants_and_functions() {
+        assert_eq!(value("100 + 10*t", 3.0), 130.0);
+        assert!((value("sin(pi/2)", 0.0) - 1.0).abs() < 1e-12);
+        assert_eq!(value("min(t, 5) + max(t, 5)", 2.0), 7.0);
+        assert_eq!(value("floor(t) + abs(-1) + sqrt(4)", 2.7), 5.0);
+        assert!((value("ln(exp(2))", 0.0) - 2.0).abs() < 1e-12);
+        assert!((value("100 + 50*sin(t/5)", 5.0) - (100.0 + 50.0 * 1f64.sin())).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        for source in [
+            "",
+            "1 +",
+            "(1 + 2",
+            "1 + 2)",
+            "x + 1",
+            "foo(1)",
+            "sin(1, 2)",
+            "min(1)",
+            "1 $ 2",
+            "1 2",
+        ] {
+            assert!(Expression::parse(source).is_err(), "{:?} parsed", source);
+        }
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        let nested = format!("{}t{}", "(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH));
+        assert!(Expression::parse(&nested).is_err());
+
+        let shallow = format!("{}t{}", "(".repeat(10), ")".repeat(10));
+        assert!(Expression::parse(&shallow).is_ok());
+    }
+
+    #[test]
+    fn rejects_long_runs_of_unary_minus() {
+        let negated = format!("{}t", "-".repeat(MAX_DEPTH));
+        assert!(Expression::parse(&negated).is_err());
+    }
+
+    #[test]
+    fn rejects_overlong_source() {
+        let long = format!("t{}", "+1".repeat(MAX_SOURCE_LENGTH));
+        assert!(Expression::parse(&long).is_err());
+    }
+}
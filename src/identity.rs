@@ -0,0 +1,55 @@
+// Virtual-user identities, dataSource rows and bootstrap values: `{{name}}` placeholders in
+// URLs, header values and JSON body strings are filled in from the identity a user holds
+// for the whole test, from the row a request was handed, or from what the bootstrap
+// requests captured
+use crate::types::LoadTestMessage;
+use serde_json::Value;
+use std::collections::HashMap;
+
+// The message as seen by one virtual user
+pub fn bind(message: &LoadTestMessage, identity: &HashMap<String, String>) -> LoadTestMessage {
+    let mut bound = message.clone();
+    bound.target_url = substitute(&bound.target_url, identity);
+
+    for value in bound.headers.iter_mut().flat_map(HashMap::values_mut) {
+        *value = substitute(value, identity);
+    }
+
+    let endpoints = bound.endpoints.iter_mut().chain(bound.steps.iter_mut());
+    for endpoint in endpoints.flatten() {
+        endpoint.url = substitute(&endpoint.url, identity);
+        for value in endpoint.headers.iter_mut().flat_map(HashMap::values_mut) {
+            *value = substitute(value, identity);
+        }
+        if let Some(body) = &mut endpoint.body {
+            substitute_json(body, identity);
+        }
+    }
+
+    if let Some(body) = &mut bound.body {
+        substitute_json(body, identity);
+    }
+
+    bound
+}
+
+fn substitute(template: &str, identity: &HashMap<String, String>) -> String {
+    identity
+        .iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{{{}}}}}", name), value)
+        })
+}
+
+fn substitute_json(value: &mut Value, identity: &HashMap<String, String>) {
+    match value {
+        Value::String(text) => *text = substitute(text, identity),
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| substitute_json(item, identity)),
+        Value::Object(fields) => fields
+            .values_mut()
+            .for_each(|field| substitute_json(field, identity)),
+        _ => {}
+    }
+}
@@ -1,56 +1,1001 @@
-use crate::stats::Statistics;
-use crate::types::{LoadTestMessage, Metric, TestResult, TimeSeriesPoint};
-use anyhow::Result;
+use crate::analysis;
+use crate::auth::{self, Auth};
+use crate::cache::ResponseCache;
+use crate::pacing::{exponential_interval, sample_think_time, seeded_rng};
+use crate::coordination::Coordinator;
+use crate::data_source::DataSource;
+use crate::dns::TimedResolver;
+use crate::identity;
+use crate::proto;
+use crate::rate::RateProfile;
+use crate::request::{send_request, RequestPlan};
+use crate::session::StickySession;
+use crate::signing;
+use crate::sse;
+use crate::stats::{note_connection, push_warning, Connections, Statistics};
+use crate::tls::{self, Negotiated};
+use crate::trace::Trace;
+use crate::types::{
+    AbortReason, ArrivalDistribution, CapacityProbeConfig, ConcurrencyModel, EndpointConfig,
+    HttpVersion, LoadMode, LoadTestMessage, Metric, PreflightConfig, Protocol, ResultEncoding,
+    RetryConfig, TestProgress, TestResult, TestStatus, TimeSeriesPoint, VerifyRecoveryConfig,
+    DEFAULT_MAX_TIME_SERIES_POINTS, DEFAULT_REQUEST_TIMEOUT_MS, DEFAULT_TRACE_REQUESTS,
+    DEFAULT_WARMUP_SECONDS,
+};
+use crate::worker;
+use anyhow::{anyhow, bail, Result};
 use chrono::Utc;
-use lapin::{options::*, Channel};
-use log::info;
-use reqwest::{Client, Method};
+use futures::future::{join_all, BoxFuture};
+use lapin::types::{AMQPValue, FieldTable};
+use lapin::{options::*, BasicProperties, Channel};
+use log::{info, warn};
+use rand::rngs::StdRng;
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::Semaphore;
-use tokio::time::sleep;
+use tokio::sync::{watch, AcquireError, Mutex, OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, sleep, sleep_until, timeout};
+use tokio_util::sync::CancellationToken;
+
+// How often a `loadExpression` evaluating to zero or below is re-checked
+const EXPRESSION_IDLE_POLL: Duration = Duration::from_millis(100);
+
+// Schedule lag that counts as falling behind, and how many consecutive time-series
+// points of it mean the target rate is out of reach
+const SCHEDULE_LAG_THRESHOLD_MS: u64 = 1000;
+const SUSTAINED_LAG_POINTS: u32 = 3;
+
+// How often the shared request pool is checked for saturation
+const POOL_SAMPLE_INTERVAL: Duration = Duration::from_millis(10);
+
+// Consecutive probes within the baseline that count as recovered; over so few samples
+// the p95 is the slowest of them
+const RECOVERY_PROBES: u32 = 5;
+const DEFAULT_RECOVERY_PROBE_INTERVAL_MS: u64 = 500;
+
+// Requests to see before `abortOnErrorRate` is judged, unless abortMinSamples says otherwise
+const DEFAULT_ABORT_MIN_SAMPLES: u32 = 100;
+
+// Escalating capacity probe: how long each rate is held, what it starts and stops at
+// unless capacityProbe says otherwise, and the share of a rate the target must actually
+// serve for it to count as sustained
+const DEFAULT_PROBE_STEP_SECONDS: u32 = 2;
+const DEFAULT_PROBE_START_RPS: f64 = 10.0;
+const DEFAULT_PROBE_MAX_RPS: f64 = 10_000.0;
+const DEFAULT_PROBE_MAX_ERROR_RATE: f64 = 0.01;
+const PROBE_KEEP_UP: f64 = 0.9;
+
+// How often permits are added to the shared pool during `rampUpSeconds`
+const RAMP_UP_STEP: Duration = Duration::from_millis(100);
+
+// AMQP delivery mode for messages the broker writes to disk
+const PERSISTENT_DELIVERY_MODE: u8 = 2;
+
+// How often a progress heartbeat is published while the test runs
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+// How long a result that couldn't be published waits for the worker to reconnect
+const RESULT_REPUBLISH_WAIT: Duration = Duration::from_secs(300);
+
+// Queues an executor publishes to
+pub struct Queues {
+    pub results: String,
+    pub metrics: String,
+    pub plans: String,
+    pub traces: String,
+    pub progress: String,
+}
+
+// Where an executor's messages go
+pub trait Outbox: Send + Sync {
+    // Publishes to `queue` through the default exchange
+    fn publish<'a>(
+        &'a self,
+        queue: &'a str,
+        payload: &'a [u8],
+        properties: BasicProperties,
+    ) -> BoxFuture<'a, lapin::Result<()>>;
+
+    // The worker's current broker channel, updated when it reconnects; None when
+    // messages don't go to a broker
+    fn broker(&self) -> Option<watch::Receiver<Channel>> {
+        None
+    }
+}
+
+// Follows the worker's current channel, so a test outlives a reconnect
+pub struct Broker(pub watch::Receiver<Channel>);
+
+impl Outbox for Broker {
+    fn publish<'a>(
+        &'a self,
+        queue: &'a str,
+        payload: &'a [u8],
+        properties: BasicProperties,
+    ) -> BoxFuture<'a, lapin::Result<()>> {
+        Box::pin(async move {
+            let current = self.0.borrow().clone();
+            let options = BasicPublishOptions::default();
+            current
+                .basic_publish("", queue, options, payload, properties)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn broker(&self) -> Option<watch::Receiver<Channel>> {
+        Some(self.0.clone())
+    }
+}
 
 pub struct LoadTestExecutor {
     message: LoadTestMessage,
-    channel: Channel,
-    results_queue: String,
-    metrics_queue: String,
+    outbox: Arc<dyn Outbox>,
+    queues: Queues,
+    // Status -> queue overrides for results
+    result_routes: HashMap<String, String>,
+    signing_secret: Option<Vec<u8>>,
+    region: Option<String>,
+    persistent_results: bool,
+    persistent_metrics: bool,
+    rate_profile: Option<RateProfile>,
+    // Shares this shard's progress with the test's other shards under rateCoordination
+    coordinator: Option<Coordinator>,
+    // Loaded once the test starts
+    data_source: Option<Arc<DataSource>>,
+    // Highest rate capacityProbe found the target sustaining
+    capacity_estimate: Option<f64>,
+    // Cancelled from outside, through the cancel_tests exchange
+    cancel: CancellationToken,
 }
 
 impl LoadTestExecutor {
     pub fn new(
         message: LoadTestMessage,
-        channel: Channel,
-        results_queue: String,
-        metrics_queue: String,
+        outbox: Arc<dyn Outbox>,
+        queues: Queues,
+        result_routes: HashMap<String, String>,
     ) -> Self {
+        // Until other shards are heard from, this one's own share, or all of it
+        let coordinator = message.rate_coordination.as_ref().map(|config| {
+            let initial_rps = match message.requests_per_second {
+                0 => config.global_rps,
+                rps => rps as f64,
+            };
+            Coordinator::new(config, initial_rps)
+        });
+
         Self {
+            outbox,
+            queues,
+            result_routes,
+            signing_secret: worker::signing_secret(),
+            region: worker::region(),
+            persistent_results: worker::persistent_results(),
+            persistent_metrics: worker::persistent_metrics(),
+            rate_profile: coordinator
+                .as_ref()
+                .map(|coordinator| RateProfile::Coordinated(coordinator.rate()))
+                .or_else(|| RateProfile::from_message(&message)),
+            coordinator,
+            data_source: None,
+            capacity_estimate: None,
+            cancel: CancellationToken::new(),
             message,
-            channel,
-            results_queue,
-            metrics_queue,
         }
     }
 
-    pub async fn execute(self) -> Result<()> {
-        let start_time = Instant::now();
-        let stats = Arc::new(tokio::sync::Mutex::new(Statistics::new()));
-        
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    pub async fn execute(mut self) -> Result<()> {
+        let mut progress = Progress::new(self.cancel.child_token(), Duration::ZERO);
+        let stats = Arc::new(Mutex::new(Statistics::with_percentile_backend(
+            self.message.percentile_backend.unwrap_or_default(),
+        )));
+
         // Create HTTP client
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()?;
+        let dns_times = stats.lock().await.dns_times.clone();
+        let mut client_builder = Client::builder()
+            .timeout(Duration::from_millis(
+                self.message
+                    .request_timeout_ms
+                    .unwrap_or(DEFAULT_REQUEST_TIMEOUT_MS),
+            ))
+            .dns_resolver(Arc::new(TimedResolver::new(dns_times)));
+        client_builder = match self.message.http_version.unwrap_or_default() {
+            HttpVersion::Http1 => client_builder
+                .http1_only()
+                .pool_max_idle_per_host(self.message.effective_concurrency() as usize),
+            HttpVersion::Http2 => client_builder.http2_prior_knowledge(),
+        };
+        let client = client_builder.build()?;
 
-        // Semaphore to limit concurrent requests
-        let semaphore = Arc::new(Semaphore::new(self.message.concurrent_users as usize));
+        let cache = self
+            .message
+            .response_cache
+            .as_ref()
+            .map(|config| Arc::new(ResponseCache::new(Duration::from_millis(config.ttl_ms))));
 
-        // Calculate delay between requests to achieve target RPS
-        let delay_between_requests = if self.message.requests_per_second > 0 {
-            Duration::from_millis(1000 / self.message.requests_per_second as u64)
-        } else {
-            Duration::from_millis(10)
+        // Credentials shared by every request, refreshed as they expire
+        let auth = self
+            .message
+            .auth
+            .as_ref()
+            .map(|config| Arc::new(Auth::new(auth::provider(config, client.clone()))));
+
+        info!("🔌 Concurrency model: {}", self.message.concurrency_model());
+
+        // Echo what will actually run, so clamped or defaulted values are visible
+        if let Err(e) = self.publish_plan().await {
+            warn!("📋 Failed to publish resolved test plan: {}", e);
+        }
+
+        let invalid_regex = self
+            .message
+            .body_assertions
+            .as_ref()
+            .and_then(|assertions| assertions.regex.as_ref()?.compiled.as_ref().err());
+        if let Some(error) = invalid_regex {
+            warn!("🧩 Invalid bodyAssertions.regex, skipping test: {}", error);
+            progress.abort_reason = Some(AbortReason::SetupError);
+
+            let mut stats = stats.lock().await;
+            stats.warn(&format!("bodyAssertions.regex is invalid: {}", error));
+            let result = self.build_result(&stats, &progress, false);
+            self.publish_result(&result).await?;
+            return Ok(());
+        }
+
+        // Everything before the first measured request, bounded so a hung dependency
+        // can't hold on to this worker
+        let setup_timeout = self.message.setup_timeout_seconds;
+        let setup = self.setup(&client, auth.as_deref(), &stats);
+        let setup_failure = match setup_timeout {
+            Some(seconds) => timeout(Duration::from_secs(seconds as u64), setup)
+                .await
+                .unwrap_or_else(|_| {
+                    warn!("⏳ Setup didn't finish within {}s, skipping test", seconds);
+                    Some(AbortReason::SetupTimeout)
+                }),
+            None => setup.await,
+        };
+        if let Some(reason) = setup_failure {
+            progress.abort_reason = Some(reason);
+
+            let result = self.build_result(&*stats.lock().await, &progress, false);
+            self.publish_result(&result).await?;
+            return Ok(());
+        }
+
+        // Measurement starts here; lookups and connections made during setup aren't
+        // part of the run
+        {
+            let mut stats = stats.lock().await;
+            stats.dns_times.lock().unwrap().reset();
+            let known = stats.connections.lock().unwrap().len();
+            stats.known_connections = known;
+        }
+        progress = Progress::new(self.cancel.child_token(), self.excluded_warmup());
+        if self.message.excluded_warmup_seconds.is_some() {
+            stats.lock().await.measure_from = Some(progress.measure_start);
+        }
+
+        if let Some(trace) = &self.message.trace {
+            let capacity = trace.max_requests.unwrap_or(DEFAULT_TRACE_REQUESTS);
+            stats.lock().await.trace = Some(Trace::new(capacity as usize));
+        }
+
+        let heartbeat = self.heartbeat(stats.clone(), progress.start_time);
+        let phases = self.phase_boundaries(stats.clone(), progress.start_time).await;
+
+        // Without the exchange the shard keeps to its initial rate on its own
+        let mut coordination = None;
+        if let Some(coordinator) = &self.coordinator {
+            let started = match self.outbox.broker() {
+                Some(channel) => {
+                    let channel = channel.borrow().clone();
+                    coordinator
+                        .start(&channel, stats.clone(), progress.start_time)
+                        .await
+                }
+                None => Err(anyhow!("not connected to a broker")),
+            };
+            match started {
+                Ok(task) => coordination = Some(task),
+                Err(e) => stats
+                    .lock()
+                    .await
+                    .warn(&format!("rate coordination unavailable: {}", e)),
+            }
+        }
+
+        // Execute load test
+        let protocol = self.message.protocol.unwrap_or_default();
+        let model = self.message.concurrency_model.unwrap_or_default();
+        match (protocol, self.message.iterations_per_user, model) {
+            (Protocol::Sse, _, _) => {
+                self.run_sse(&client, &stats, auth, &mut progress).await;
+            }
+            // Iterations are always sent from persistent virtual users
+            (Protocol::Http, Some(iterations), _) => {
+                self.run_per_user(&client, &stats, auth, cache, Some(iterations), &mut progress)
+                    .await;
+            }
+            (Protocol::Http, None, ConcurrencyModel::UserLoop) => {
+                self.run_per_user(&client, &stats, auth, cache, None, &mut progress)
+                    .await;
+            }
+            (Protocol::Http, None, ConcurrencyModel::TaskPerRequest) => {
+                self.run_closed(&client, &stats, auth, cache, &mut progress).await?;
+            }
+        }
+
+        heartbeat.abort();
+        if let Some(phases) = phases {
+            phases.abort();
+            // The phase the run ended in
+            stats.lock().await.end_phase();
+        }
+        if let Some(coordination) = coordination {
+            coordination.abort();
+        }
+
+        if self.cancel.is_cancelled() {
+            progress.abort_reason.get_or_insert(AbortReason::Cancelled);
+        }
+
+        // Last chance for metrics buffered during a broker outage
+        self.flush_metrics(&mut progress.metric_buffer).await;
+
+        let total_duration = progress.start_time.elapsed();
+        let mut final_stats = stats.lock().await;
+        final_stats.error_log.summarize();
+
+        match progress.abort_reason {
+            Some(reason) => warn!(
+                "🛑 Test aborted ({:?}): {} requests in {:.2}s",
+                reason,
+                final_stats.total_requests,
+                total_duration.as_secs_f64()
+            ),
+            None => info!(
+                "✅ Test completed: {} requests in {:.2}s",
+                final_stats.total_requests,
+                total_duration.as_secs_f64()
+            ),
+        }
+
+        // Create final test result
+        let mut result = self.build_result(&final_stats, &progress, false);
+
+        // Probing comes after the result is built so it doesn't dilute the run's rates
+        if let (Some(recovery), None) = (&self.message.verify_recovery, progress.abort_reason) {
+            result.recovered_within_seconds = self.verify_recovery(&client, recovery).await;
+            result.recovery_failed = result.recovered_within_seconds.is_none();
+        }
+
+        // Send result to queue
+        self.publish_result(&result).await?;
+
+        info!("📤 Test result sent to queue");
+
+        if let Some(trace) = &final_stats.trace {
+            let timeline = trace.to_chrome_json(&self.message.test_id, progress.start_time);
+            self.outbox
+                .publish(
+                    &self.queues.traces,
+                    &serde_json::to_vec(&timeline)?,
+                    BasicProperties::default().with_content_type("application/json".into()),
+                )
+                .await?;
+
+            info!("🧵 Request timeline sent to {}", self.queues.traces);
+        }
+
+        Ok(())
+    }
+
+    fn build_result(&self, stats: &Statistics, progress: &Progress, partial: bool) -> TestResult {
+        let elapsed = progress.measure_start.elapsed();
+        let requests_per_second = stats.total_requests as f64 / elapsed.as_secs_f64();
+        let steady_state = analysis::steady_state(&progress.time_series_data, progress.started_at);
+        let status = match (progress.abort_reason, partial) {
+            (Some(_), _) => TestStatus::Aborted,
+            (None, true) => TestStatus::Running,
+            (None, false) => TestStatus::Completed,
+        };
+
+        let mut result = TestResult {
+            test_id: self.message.test_id.clone(),
+            partial,
+            status,
+            abort_reason: progress.abort_reason,
+            total_requests: stats.total_requests,
+            successful_requests: stats.successful_requests,
+            failed_requests: stats.failed_requests,
+            average_response_time: stats.get_average(),
+            time_weighted_avg_latency: stats.get_time_weighted_average(),
+            outcome_transitions: stats.outcome_transitions,
+            flakiness_score: stats.get_flakiness_score(),
+            compressed_response_fraction: stats.get_compressed_response_fraction(),
+            capacity_estimate_rps: self.capacity_estimate,
+            network_error_fraction: stats.get_network_error_fraction(),
+            status_code_latencies: stats.get_status_code_latencies(),
+            global_target_rps: self.coordinator.as_ref().map(Coordinator::global_rps),
+            aggregate_rps: self.coordinator.as_ref().map(|coordinator| {
+                let own = (stats.total_requests + stats.warmup_requests) as u64;
+                coordinator.aggregate_requests(own) as f64
+                    / progress.start_time.elapsed().as_secs_f64()
+            }),
+            min_response_time: stats.get_min(),
+            max_response_time: stats.get_max(),
+            p50_response_time: stats.get_percentile(50.0),
+            p95_response_time: stats.get_percentile(95.0),
+            p99_response_time: stats.get_percentile(99.0),
+            queue_time_p95: stats.get_queue_time_percentile(95.0),
+            schedule_error_p50: stats.get_schedule_error_percentile(50.0),
+            schedule_error_p95: stats.get_schedule_error_percentile(95.0),
+            schedule_error_p99: stats.get_schedule_error_percentile(99.0),
+            ttfb_p50: stats.get_ttfb_percentile(50.0),
+            ttfb_p95: stats.get_ttfb_percentile(95.0),
+            ttfb_p99: stats.get_ttfb_percentile(99.0),
+            dns_time_p95: stats.get_dns_time_percentile(95.0),
+            connections_established: stats.get_connections_established(),
+            warnings: self.warnings(stats, progress),
+            pool_saturation_percent: stats.get_pool_saturation_percent(),
+            in_flight_distribution: stats.get_in_flight_distribution(),
+            cache_busted: self.message.cache_bust.is_some(),
+            cold_start_ms: stats.cold_start_ms,
+            warmup_requests: stats.warmup_requests,
+            breakdown_by_dimension: stats.get_dimension_breakdown(),
+            region: self.region.clone(),
+            cancelled: self.cancel.is_cancelled(),
+            cache_hits: stats.cache_hits,
+            cache_hit_rate: stats.get_cache_hit_rate(),
+            avg_request_bytes: stats.get_average_bytes().map(|(sent, _)| sent),
+            avg_response_bytes: stats.get_average_bytes().map(|(_, received)| received),
+            bytes_per_successful_request: stats.get_bytes_per_success(),
+            total_bytes: stats.bytes_received,
+            throughput_bytes_per_second: stats.bytes_received as f64 / elapsed.as_secs_f64(),
+            decompressed_bytes: stats.bytes_decompressed,
+            recovered_within_seconds: None,
+            recovery_failed: false,
+            sse: (self.message.protocol == Some(Protocol::Sse))
+                .then(|| stats.get_sse_summary(elapsed.as_secs_f64())),
+            effective_concurrency: self.message.effective_concurrency(),
+            achieved_concurrency: requests_per_second * stats.get_average() / 1000.0,
+            concurrency_model: self.message.concurrency_model(),
+            requests_per_second,
+            goodput_rps: stats.successful_requests as f64 / elapsed.as_secs_f64(),
+            error_rate: stats.error_rate(),
+            status_code_distribution: stats.get_status_codes(),
+            latency_by_tls_version: stats.get_tls_latencies(),
+            error_distribution: stats.get_errors(),
+            fault_injected_requests: stats.fault_injected_requests,
+            fault_injected_failures: stats.fault_injected_failures,
+            fault_distribution: stats.get_faults(),
+            backend_distribution: stats.get_backends(),
+            sticky_violations: stats.sticky_violations,
+            validation_failures: stats.validation_failures,
+            validation_webhook_errors: stats.validation_webhook_errors,
+            total_retries: stats.retries,
+            dropped_metrics: progress.metric_buffer.dropped,
+            endpoint_results: stats.get_endpoints(),
+            group_results: stats.get_groups(),
+            sample_adequacy: analysis::sample_adequacy(stats.get_latency_samples()),
+            tail_composition: vec![
+                stats.get_tail_composition(95.0),
+                stats.get_tail_composition(99.0),
+            ],
+            segment_results: self
+                .message
+                .rate_schedule
+                .as_ref()
+                .map(|segments| {
+                    analysis::segment_results(
+                        &progress.time_series_data,
+                        progress.started_at,
+                        segments,
+                        &stats.phase_summaries,
+                    )
+                })
+                .unwrap_or_default(),
+            time_to_steady_state_seconds: steady_state
+                .as_ref()
+                .map(|steady| steady.time_to_steady_state_seconds),
+            ramp_stability_score: steady_state.as_ref().map(|steady| steady.stability_score),
+            worst_interval: analysis::worst_interval(&progress.time_series_data),
+            error_recovery_seconds: analysis::error_recovery_seconds(&progress.time_series_data),
+            body_size_summary: stats.get_body_size_summary(),
+            latency_histogram: stats.get_latency_histogram(),
+            latency_digest: stats.get_latency_digest(),
+            time_series_data: progress.time_series_data.clone(),
+            worker_version: worker::WORKER_VERSION.to_string(),
+            capabilities: worker::capabilities(),
+        };
+
+        result.apply_verbosity(self.message.result_verbosity.unwrap_or_default());
+        result
+    }
+
+    // Data source, preflight check, first credentials, prewarming, bootstrap and
+    // capacity probe; returns why the test can't go ahead, if it can't
+    async fn setup(
+        &mut self,
+        client: &Client,
+        auth: Option<&Auth>,
+        stats: &Mutex<Statistics>,
+    ) -> Option<AbortReason> {
+        // Connections the setup requests open stay in the pool for the test
+        let connections = stats.lock().await.connections.clone();
+
+        if let Some(config) = &self.message.data_source {
+            match DataSource::load(config, client).await {
+                Ok(data_source) => {
+                    info!("🗂️ Loaded {} dataSource rows", data_source.row_count());
+                    self.data_source = Some(Arc::new(data_source));
+                }
+                Err(e) => {
+                    warn!("🗂️ Failed to load dataSource, skipping test: {}", e);
+                    let warning = format!("dataSource couldn't be loaded: {}", e);
+                    stats.lock().await.warn(&warning);
+                    return Some(AbortReason::SetupError);
+                }
+            }
+        }
+
+        // Don't load a target that's already down
+        if let Some(preflight) = &self.message.preflight {
+            if let Err(e) = self.run_preflight(client, preflight, &connections).await {
+                warn!("🩺 Preflight check failed, skipping test: {}", e);
+                return Some(AbortReason::PreflightFailed);
+            }
+            info!("🩺 Preflight check passed");
+        }
+
+        // Fetch credentials up front; failures show up per request later on
+        if let Some(auth) = auth {
+            if let Err(e) = auth.headers().await {
+                warn!("🔑 Failed to obtain initial credentials: {}", e);
+            }
+        }
+
+        if let Some(count) = self.message.prewarm_connections {
+            self.prewarm(client, count, &connections).await;
+        }
+
+        if let Some(steps) = self.message.bootstrap.clone() {
+            match self.bootstrap(client, auth, steps, &connections).await {
+                Ok(globals) => {
+                    info!("🥾 Bootstrap captured {} values", globals.len());
+                    self.message = identity::bind(&self.message, &globals);
+                }
+                Err(step) => {
+                    warn!("🥾 Bootstrap request {} failed, skipping test", step);
+                    stats.lock().await.warn(&format!("bootstrap request {} failed", step));
+                    return Some(AbortReason::SetupError);
+                }
+            }
+        }
+
+        // What each https origin negotiates in a probe handshake of its own, to group
+        // latency by TLS version
+        let timeout = Duration::from_millis(
+            self.message
+                .request_timeout_ms
+                .unwrap_or(DEFAULT_REQUEST_TIMEOUT_MS),
+        );
+        for origin in tls::origins(&self.message) {
+            let probed = origin.clone();
+            let negotiated = tokio::task::spawn_blocking(move || tls::probe(&probed, timeout))
+                .await
+                .unwrap_or_else(|e| Err(e.into()));
+            match negotiated {
+                Ok(negotiated) => {
+                    let Negotiated { version, cipher } = &negotiated;
+                    info!("🔒 {} negotiated {} {}", origin, version, cipher);
+                    stats.lock().await.tls_origins.insert(origin, negotiated);
+                }
+                Err(e) => warn!("🔒 Failed to read {}'s TLS parameters: {}", origin, e),
+            }
+        }
+
+        if let Some(probe) = self.message.capacity_probe.clone() {
+            self.capacity_estimate = self
+                .probe_capacity(client, auth, &probe, &connections)
+                .await;
+            match (self.capacity_estimate, probe.load_percent) {
+                (Some(estimate), Some(percent)) => {
+                    let rps = (estimate * percent / 100.0).round().max(1.0) as u32;
+                    info!("🔭 Running at {}% of the estimated capacity: {} RPS", percent, rps);
+                    self.message.requests_per_second = rps;
+                }
+                (None, _) => stats
+                    .lock()
+                    .await
+                    .warn("capacityProbe: the target didn't sustain the starting rate"),
+                (Some(_), None) => {}
+            }
+        }
+
+        None
+    }
+
+    async fn run_preflight(
+        &self,
+        client: &Client,
+        preflight: &PreflightConfig,
+        connections: &Connections,
+    ) -> Result<()> {
+        let response = client.get(&preflight.url).send().await?;
+        note_connection(connections, &response);
+        let status = response.status();
+
+        let healthy = match preflight.expected_status {
+            Some(expected) => status.as_u16() == expected,
+            None => status.is_success(),
+        };
+        if !healthy {
+            bail!("{} returned {}", preflight.url, status);
+        }
+
+        Ok(())
+    }
+
+    // Sends the bootstrap requests one after another, each with what the earlier ones
+    // captured, and returns everything captured; the failed request's name otherwise.
+    // Nothing here is recorded.
+    async fn bootstrap(
+        &self,
+        client: &Client,
+        auth: Option<&Auth>,
+        steps: Vec<EndpointConfig>,
+        connections: &Connections,
+    ) -> std::result::Result<HashMap<String, String>, String> {
+        let names: Vec<String> = steps.iter().map(|step| step.name.clone()).collect();
+        // The test-wide checks and perturbations are for the load, not for setup; a
+        // step only has to get through and yield its values
+        let flow = LoadTestMessage {
+            endpoints: Some(steps),
+            expected_status_codes: None,
+            body_assertions: None,
+            cache_bust: None,
+            fault_injection: None,
+            retry: RetryConfig::default(),
+            validation_webhook: None,
+            ..self.message.clone()
+        };
+        let stats = Mutex::new(Statistics::sharing(connections));
+        let mut globals = HashMap::new();
+
+        for (step, name) in names.into_iter().enumerate() {
+            let message = identity::bind(&flow, &globals);
+            let plan = RequestPlan {
+                endpoint: Some(step),
+                ..RequestPlan::default()
+            };
+            match send_request(client, &message, &stats, plan, &mut None, auth, None).await {
+                Some(values) => globals.extend(values),
+                None => return Err(name),
+            }
+        }
+
+        Ok(globals)
+    }
+
+    // Sends the test's request at rates doubling from `startRps`, each held for a step,
+    // until the target stops keeping up. Returns the highest rate it served within the
+    // error and latency limits; None if it couldn't manage the first. Nothing here is
+    // recorded.
+    async fn probe_capacity(
+        &self,
+        client: &Client,
+        auth: Option<&Auth>,
+        probe: &CapacityProbeConfig,
+        connections: &Connections,
+    ) -> Option<f64> {
+        let step = Duration::from_secs(
+            probe.step_seconds.unwrap_or(DEFAULT_PROBE_STEP_SECONDS) as u64,
+        );
+        let max_rps = probe.max_rps.unwrap_or(DEFAULT_PROBE_MAX_RPS);
+        let max_error_rate = probe.max_error_rate.unwrap_or(DEFAULT_PROBE_MAX_ERROR_RATE);
+        // Capacity as far as this test's own concurrency can reach
+        let limits = Semaphore::new(self.message.effective_concurrency() as usize);
+        let mut rps = probe.start_rps.unwrap_or(DEFAULT_PROBE_START_RPS);
+        let mut estimate = None;
+
+        info!("🔭 Probing capacity from {} RPS", rps);
+
+        while rps <= max_rps {
+            let stats = Mutex::new(Statistics::sharing(connections));
+            let requests = (rps * step.as_secs_f64()).ceil() as u32;
+            let gap = Duration::from_secs_f64(1.0 / rps);
+            let started = Instant::now();
+            // When the last request actually went out, in microseconds since `started`
+            let last_sent = AtomicU64::new(0);
+
+            join_all((0..requests).map(|i| {
+                let (stats, limits, last_sent) = (&stats, &limits, &last_sent);
+                async move {
+                    sleep_until((started + gap * i).into()).await;
+                    let Ok(_permit) = limits.acquire().await else {
+                        return;
+                    };
+                    last_sent.fetch_max(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+                    let plan = RequestPlan::default();
+                    send_request(client, &self.message, stats, plan, &mut None, auth, None).await;
+                }
+            }))
+            .await;
+
+            // Falling behind schedule stretches the span the requests were served over
+            let sending = Duration::from_micros(last_sent.load(Ordering::Relaxed)) + gap;
+            let stats = stats.lock().await;
+            let achieved = stats.successful_requests as f64 / sending.max(step).as_secs_f64();
+            let error_rate = stats.failed_requests as f64 / stats.total_requests.max(1) as f64;
+            let p95 = stats.get_percentile(95.0);
+            info!(
+                "🔭 {} RPS offered: {:.1} served, {:.1}% errors, p95 {:.0}ms",
+                rps,
+                achieved,
+                error_rate * 100.0,
+                p95
+            );
+
+            let sustained = achieved >= rps * PROBE_KEEP_UP
+                && error_rate <= max_error_rate
+                && probe.max_p95_ms.is_none_or(|max_p95_ms| p95 <= max_p95_ms);
+            if !sustained {
+                break;
+            }
+            estimate = Some(achieved);
+            rps *= 2.0;
+        }
+
+        if let Some(estimate) = estimate {
+            info!("🔭 Estimated capacity: {:.1} RPS", estimate);
+        }
+        estimate
+    }
+
+    // Probes the target at a low rate until RECOVERY_PROBES in a row come back
+    // successfully within the baseline, returning how long that took. None if the
+    // window ran out first.
+    async fn verify_recovery(
+        &self,
+        client: &Client,
+        recovery: &VerifyRecoveryConfig,
+    ) -> Option<f64> {
+        let url = recovery.url.as_ref().unwrap_or(&self.message.target_url);
+        let window = Duration::from_secs(recovery.window_seconds as u64);
+        let probe_interval = Duration::from_millis(
+            recovery
+                .probe_interval_ms
+                .unwrap_or(DEFAULT_RECOVERY_PROBE_INTERVAL_MS),
+        );
+        let started = Instant::now();
+        let mut ticker = interval(probe_interval);
+        let mut healthy_probes = 0;
+
+        info!("🩹 Verifying recovery for up to {}s", recovery.window_seconds);
+
+        while started.elapsed() < window {
+            ticker.tick().await;
+
+            let probe_start = Instant::now();
+            let healthy = match client.get(url).send().await {
+                Ok(response) => {
+                    response.status().is_success()
+                        && probe_start.elapsed().as_secs_f64() * 1000.0 <= recovery.baseline_p95_ms
+                }
+                Err(_) => false,
+            };
+            healthy_probes = if healthy { healthy_probes + 1 } else { 0 };
+
+            if healthy_probes == RECOVERY_PROBES {
+                let seconds = started.elapsed().as_secs_f64();
+                info!("🩹 Target recovered within {:.1}s", seconds);
+                return Some(seconds);
+            }
+        }
+
+        warn!("🩹 Target didn't return to baseline within {}s", recovery.window_seconds);
+        None
+    }
+
+    // Opens connections with concurrent HEAD requests so the pool is already full when
+    // measurement starts. Nothing here is recorded but the connections.
+    async fn prewarm(&self, client: &Client, count: u32, connections: &Connections) {
+        // The pool keeps at most this many idle connections; HTTP/2 shares one
+        let count = count.min(self.message.effective_concurrency());
+        let target_url = &self.message.target_url;
+
+        let opened = join_all((0..count).map(|_| async move {
+            let response = client.head(target_url).send().await?;
+            note_connection(connections, &response);
+            response.bytes().await
+        }))
+        .await
+        .into_iter()
+        .filter(|response| response.is_ok())
+        .count();
+
+        info!("🔥 Prewarmed {}/{} connections", opened, count);
+    }
+
+    async fn publish_plan(&self) -> Result<()> {
+        let plan = self.message.resolved_plan();
+
+        self.outbox
+            .publish(
+                &self.queues.plans,
+                &serde_json::to_vec(&plan)?,
+                BasicProperties::default().with_content_type("application/json".into()),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn publish_result(&self, result: &TestResult) -> Result<()> {
+        let (queue, payload) = self.routed_result(result)?;
+
+        // A result is what the test was for, so one the broker doesn't take while the
+        // worker is disconnected goes out again on the next connection
+        let mut reconnects = self.outbox.broker();
+        loop {
+            let published = self
+                .outbox
+                .publish(queue, &payload, self.result_properties(&payload))
+                .await;
+
+            let Err(e) = published else {
+                return Ok(());
+            };
+            warn!("📦 Result queue unavailable, waiting to republish: {}", e);
+            let reconnected = match &mut reconnects {
+                Some(channel) => {
+                    let changed = timeout(RESULT_REPUBLISH_WAIT, channel.changed()).await;
+                    matches!(changed, Ok(Ok(())))
+                }
+                None => false,
+            };
+            if !reconnected {
+                return Err(e.into());
+            }
+        }
+    }
+
+    // Checkpoints get a single attempt: the next one, or the final result, supersedes
+    // one the broker didn't take
+    async fn publish_checkpoint(&self, checkpoint: &TestResult) -> Result<()> {
+        let (queue, payload) = self.routed_result(checkpoint)?;
+        self.outbox
+            .publish(queue, &payload, self.result_properties(&payload))
+            .await?;
+        Ok(())
+    }
+
+    // The queue a result goes to, by its status, and its encoded payload
+    fn routed_result(&self, result: &TestResult) -> Result<(&str, Vec<u8>)> {
+        let payload = self.encode::<_, proto::TestResult>(result)?;
+        let queue = self
+            .result_routes
+            .get(result.status.as_str())
+            .unwrap_or(&self.queues.results);
+        Ok((queue, payload))
+    }
+
+    // Publish properties plus, when a secret is configured, an HMAC over the payload so
+    // consumers can verify it came from a trusted worker untouched
+    fn result_properties(&self, payload: &[u8]) -> BasicProperties {
+        let properties = self.publish_properties(self.persistent_results);
+        let Some(secret) = &self.signing_secret else {
+            return properties;
+        };
+
+        let mut headers = FieldTable::default();
+        headers.insert(
+            signing::SIGNATURE_HEADER.into(),
+            AMQPValue::LongString(signing::sign(secret, payload).into()),
+        );
+        headers.insert(
+            signing::SIGNATURE_ALGORITHM_HEADER.into(),
+            AMQPValue::LongString(signing::SIGNATURE_ALGORITHM.into()),
+        );
+        properties.with_headers(headers)
+    }
+
+    // Serializes a payload as JSON, or as its protobuf mirror when the test asks for it
+    fn encode<T, P>(&self, value: &T) -> Result<Vec<u8>>
+    where
+        T: Serialize,
+        P: for<'a> From<&'a T> + prost::Message,
+    {
+        Ok(match self.message.result_encoding.unwrap_or_default() {
+            ResultEncoding::Json => serde_json::to_vec(value)?,
+            ResultEncoding::Protobuf => P::from(value).encode_to_vec(),
+        })
+    }
+
+    fn publish_properties(&self, persistent: bool) -> BasicProperties {
+        let encoding = self.message.result_encoding.unwrap_or_default();
+        let properties =
+            BasicProperties::default().with_content_type(encoding.content_type().into());
+        match persistent {
+            true => properties.with_delivery_mode(PERSISTENT_DELIVERY_MODE),
+            false => properties,
+        }
+    }
+
+    // One task per request, bounded by a semaphore of `concurrent_users` permits. In
+    // the open model the semaphore caps in-flight requests without slowing arrivals.
+    async fn run_closed(
+        &self,
+        client: &Client,
+        stats: &Arc<Mutex<Statistics>>,
+        auth: Option<Arc<Auth>>,
+        cache: Option<Arc<ResponseCache>>,
+        progress: &mut Progress,
+    ) -> Result<()> {
+        let requests = self.send_closed(
+            client,
+            stats,
+            auth,
+            cache,
+            progress.start_time,
+            progress.cancel.clone(),
+        );
+        tokio::pin!(requests);
+
+        // Send metrics every second of wall-clock time while requests go out, however
+        // few there are or however long they take
+        let mut ticker = interval(Duration::from_secs(1));
+        ticker.tick().await;
+
+        let outcome = loop {
+            tokio::select! {
+                outcome = &mut requests => break outcome,
+                _ = ticker.tick() => {
+                    let active_users = self.message.ramped_users(progress.start_time.elapsed());
+                    self.emit_metrics(stats, progress, active_users).await;
+                }
+            }
         };
 
+        if let Some(reason) = outcome? {
+            progress.abort_reason.get_or_insert(reason);
+        }
+
+        Ok(())
+    }
+
+    // Issues the closed model's requests until a limit is hit or the run is cancelled,
+    // then waits for the ones in flight. Returns why it stopped early, if it did.
+    async fn send_closed(
+        &self,
+        client: &Client,
+        stats: &Arc<Mutex<Statistics>>,
+        auth: Option<Arc<Auth>>,
+        cache: Option<Arc<ResponseCache>>,
+        start_time: Instant,
+        cancel: CancellationToken,
+    ) -> Result<Option<AbortReason>> {
+        // Semaphores to limit concurrent requests
+        let limits = ConcurrencyLimits::new(&self.message);
+        let saturation_sampler = limits.sample_saturation(stats.clone());
+        let ramp_up = limits.ramp_up(&self.message, start_time);
+
+        // Calculate delay between requests to achieve target RPS
+        let delay_between_requests =
+            Duration::from_millis(1000 / self.message.requests_per_second.max(1) as u64);
+
         info!(
             "🎯 Target: {} requests @ {} RPS with {} concurrent users",
             self.message.total_requests,
@@ -58,158 +1003,2937 @@ impl LoadTestExecutor {
             self.message.concurrent_users
         );
 
-        let mut handles = vec![];
-        let test_duration = Duration::from_secs(self.message.duration_seconds as u64);
-        let mut time_series_data = vec![];
+        if let Some(expression) = &self.message.load_expression {
+            info!("📈 RPS follows loadExpression: {}", expression);
+        }
+
+        if let Some(segments) = &self.message.rate_schedule {
+            info!("📈 RPS follows a rateSchedule of {} segments", segments.len());
+        }
+
+        if self.message.mode == Some(LoadMode::OpenArrivalRate) {
+            info!("🌊 Open model: arrivals keep to schedule, concurrency only caps in-flight");
+        }
+
+        let mut handles: Vec<JoinHandle<()>> = vec![];
+        let mut rng = seeded_rng(self.message.seed, 0);
+        let arrivals = self.message.arrival_distribution.unwrap_or_default();
+
+        // When each request should have gone out according to the pacing schedule
+        let mut scheduled_at = start_time;
+        let uniform_interval = (self.message.requests_per_second > 0)
+            .then(|| Duration::from_secs_f64(1.0 / self.message.requests_per_second as f64));
+
+        // A zero request count means "no cap": keep sending until the duration elapses.
+        // A zero duration means "no time limit": stop once the request count is reached.
+        let request_cap = (self.message.total_requests > 0).then_some(self.message.total_requests);
+        let open = self.message.mode == Some(LoadMode::OpenArrivalRate);
+        let test_duration = self.test_duration();
+        let mut abort_reason = None;
+
+        // Closed uniform pacing sends in batches, so its requests have no send time of
+        // their own to keep to
+        let scheduled = open
+            || self.rate_profile.is_some()
+            || (arrivals == ArrivalDistribution::Poisson && self.message.requests_per_second > 0);
+
+        let mut i: u32 = 0;
+        loop {
+            // Check if request cap reached
+            if request_cap.is_some_and(|cap| i >= cap) {
+                break;
+            }
+
+            if self.message.max_total_requests.is_some_and(|budget| i >= budget) {
+                warn!("💸 Request budget of {} exhausted, stopping test", i);
+                abort_reason = Some(AbortReason::BudgetExhausted);
+                break;
+            }
 
-        // Execute load test
-        for i in 0..self.message.total_requests {
             // Check if duration exceeded
-            if start_time.elapsed() >= test_duration {
+            if test_duration.is_some_and(|duration| start_time.elapsed() >= duration) {
                 info!("⏱️ Duration limit reached, stopping test");
                 break;
             }
 
-            let permit = semaphore.clone().acquire_owned().await?;
-            let client = client.clone();
-            let stats_clone = stats.clone();
-            let message = self.message.clone();
+            let plan = RequestPlan::draw(&self.message, &mut rng, i);
+            let paced = self.message.requests_per_second > 0 || self.rate_profile.is_some();
 
-            let handle = tokio::spawn(async move {
-                let request_start = Instant::now();
+            // The closed model holds the schedule until a permit frees up; the open model
+            // lets the request wait for one on its own
+            let permit = match open {
+                true => None,
+                false => tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    permit = limits.acquire(plan.group(&self.message), stats) => Some(permit?),
+                },
+            };
 
-                // Parse HTTP method
-                let method = Method::from_bytes(message.method.as_bytes())
-                    .unwrap_or(Method::GET);
+            // Time spent behind schedule, e.g. waiting for a free permit
+            if paced && !open {
+                let queue_time = Instant::now().saturating_duration_since(scheduled_at);
+                stats.lock().await.record_queue_time(queue_time.as_millis() as u64);
+            }
 
-                // Build request
-                let mut request_builder = client
-                    .request(method, &message.target_url);
+            let client = client.clone();
+            let stats_clone = stats.clone();
+            let message = match &self.data_source {
+                Some(data_source) => identity::bind(&self.message, data_source.next_row()),
+                None => self.message.clone(),
+            };
+            let auth = auth.clone();
+            let cache = cache.clone();
+            let limits = limits.clone();
+            let arrived_at = scheduled_at;
+            let think = self
+                .message
+                .think_time_ms
+                .as_ref()
+                .map(|think_time| sample_think_time(think_time, &mut rng));
 
-                // Add headers if provided
-                if let Some(headers) = &message.headers {
-                    for (key, value) in headers {
-                        request_builder = request_builder.header(key, value);
+            let handle = tokio::spawn(async move {
+                let permit = match permit {
+                    Some(permit) => permit,
+                    None => {
+                        let Ok(permit) = limits.acquire(plan.group(&message), &stats_clone).await
+                        else {
+                            return;
+                        };
+                        let queue_time = Instant::now().saturating_duration_since(arrived_at);
+                        stats_clone
+                            .lock()
+                            .await
+                            .record_queue_time(queue_time.as_millis() as u64);
+                        permit
                     }
+                };
+                if scheduled {
+                    stats_clone.lock().await.record_schedule_error(arrived_at);
                 }
-
-                // Add body if provided
-                if let Some(body) = &message.body {
-                    request_builder = request_builder.json(body);
-                }
-
-                // Execute request
-                match request_builder.send().await {
-                    Ok(response) => {
-                        let status = response.status();
-                        let response_time = request_start.elapsed().as_millis() as u64;
-
-                        let mut stats = stats_clone.lock().await;
-                        stats.record_success(response_time, status.as_u16());
-                    }
-                    Err(e) => {
-                        let mut stats = stats_clone.lock().await;
-                        stats.record_failure(e.to_string());
-                    }
+                let (auth, cache) = (auth.as_deref(), cache.as_deref());
+                send_request(&client, &message, &stats_clone, plan, &mut None, auth, cache).await;
+                // The permit is the virtual user, pausing before its next request
+                if let Some(think) = think {
+                    sleep(think).await;
                 }
-
                 drop(permit);
             });
 
+            // Drop handles of finished requests so duration-only runs don't grow unbounded
+            handles.retain(|handle| !handle.is_finished());
             handles.push(handle);
 
-            // Delay between requests to control RPS
-            if (i + 1) % self.message.requests_per_second == 0 {
-                sleep(delay_between_requests).await;
-            }
-
-            // Send metrics every second
-            if (i + 1) % self.message.requests_per_second == 0 {
-                let stats_snapshot = stats.lock().await;
-                let rps = stats_snapshot.total_requests as f64 / start_time.elapsed().as_secs_f64();
-                
-                time_series_data.push(TimeSeriesPoint {
-                    timestamp: Utc::now().timestamp(),
-                    rps,
-                    avg_response_time: stats_snapshot.get_average(),
-                    error_rate: stats_snapshot.error_rate(),
-                });
-
-                // Send metric to queue
-                let metric = Metric {
-                    test_id: self.message.test_id.clone(),
-                    timestamp: Utc::now().to_rfc3339(),
-                    request_count: stats_snapshot.total_requests,
-                    success_count: stats_snapshot.successful_requests,
-                    error_count: stats_snapshot.failed_requests,
-                    avg_response_time: stats_snapshot.get_average(),
-                    status_code: None,
-                    error_message: None,
-                    active_users: self.message.concurrent_users,
-                };
-
-                if let Ok(payload) = serde_json::to_vec(&metric) {
-                    let _ = self.channel
-                        .basic_publish(
-                            "",
-                            &self.metrics_queue,
-                            BasicPublishOptions::default(),
-                            &payload,
-                            lapin::BasicProperties::default(),
-                        )
-                        .await;
+            // Delay between requests to control RPS, cut short by a cancel
+            let pace = async {
+                match (&self.rate_profile, arrivals) {
+                    (Some(profile), _) => {
+                        scheduled_at = self
+                            .next_scheduled(profile, start_time, scheduled_at, arrivals, &mut rng)
+                            .await;
+                        sleep_until(scheduled_at.into()).await;
+                    }
+                    (None, ArrivalDistribution::Uniform) => {
+                        if let Some(interval) = uniform_interval {
+                            scheduled_at += interval;
+                        }
+                        // Open arrivals are spread evenly rather than sent in batches
+                        if open {
+                            sleep_until(scheduled_at.into()).await;
+                        } else if self.message.requests_per_second > 0
+                            && (i + 1).is_multiple_of(self.message.requests_per_second)
+                        {
+                            sleep(delay_between_requests).await;
+                        }
+                    }
+                    // Unthrottled at zero RPS, as with uniform arrivals
+                    (None, ArrivalDistribution::Poisson)
+                        if self.message.requests_per_second > 0 =>
+                    {
+                        let rate = self.message.requests_per_second as f64;
+                        let gap = exponential_interval(&mut rng, rate);
+                        scheduled_at += gap;
+                        sleep(gap).await;
+                    }
+                    (None, ArrivalDistribution::Poisson) => {}
                 }
+            };
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = pace => {}
             }
+
+            i += 1;
         }
 
-        // Wait for all requests to complete
+        // Wait for all requests to complete, dropping in-flight ones on abort
         for handle in handles {
+            if cancel.is_cancelled() {
+                handle.abort();
+            }
             let _ = handle.await;
         }
+        saturation_sampler.abort();
+        ramp_up.abort();
 
-        let total_duration = start_time.elapsed();
-        let final_stats = stats.lock().await;
+        Ok(abort_reason)
+    }
 
-        info!(
-            "✅ Test completed: {} requests in {:.2}s",
-            final_stats.total_requests,
-            total_duration.as_secs_f64()
-        );
+    // SSE: `concurrent_users` streams held open until the duration elapses
+    async fn run_sse(
+        &self,
+        client: &Client,
+        stats: &Arc<Mutex<Statistics>>,
+        auth: Option<Arc<Auth>>,
+        progress: &mut Progress,
+    ) {
+        info!("📡 Opening {} SSE streams", self.message.concurrent_users);
 
-        // Create final test result
-        let result = TestResult {
-            test_id: self.message.test_id.clone(),
-            total_requests: final_stats.total_requests,
-            successful_requests: final_stats.successful_requests,
-            failed_requests: final_stats.failed_requests,
-            average_response_time: final_stats.get_average(),
-            min_response_time: final_stats.get_min(),
-            max_response_time: final_stats.get_max(),
-            p50_response_time: final_stats.get_percentile(50.0),
-            p95_response_time: final_stats.get_percentile(95.0),
-            p99_response_time: final_stats.get_percentile(99.0),
-            requests_per_second: final_stats.total_requests as f64 / total_duration.as_secs_f64(),
-            error_rate: final_stats.error_rate(),
-            status_code_distribution: final_stats.get_status_codes(),
-            error_distribution: final_stats.get_errors(),
-            time_series_data,
-        };
+        // `validate()` requires a duration for sse
+        let deadline = progress.start_time + self.test_duration().unwrap_or_default();
+        let mut streams = vec![];
 
-        // Send result to queue
-        let payload = serde_json::to_vec(&result)?;
-        self.channel
-            .basic_publish(
-                "",
-                &self.results_queue,
-                BasicPublishOptions::default(),
-                &payload,
-                lapin::BasicProperties::default(),
-            )
-            .await?;
+        for connection in 0..self.message.concurrent_users {
+            let client = client.clone();
+            let stats = stats.clone();
+            let message = self.message.clone();
+            let cancel = progress.cancel.clone();
+            let auth = auth.clone();
 
-        info!("📤 Test result sent to queue");
+            streams.push(tokio::spawn(async move {
+                let auth = auth.as_deref();
+                tokio::select! {
+                    _ = cancel.cancelled() => {}
+                    _ = sse::consume(&client, &message, &stats, connection, deadline, auth) => {}
+                }
+            }));
+        }
 
-        Ok(())
+        // Send metrics every second until every stream has closed
+        let all_streams = join_all(streams);
+        tokio::pin!(all_streams);
+
+        let mut ticker = interval(Duration::from_secs(1));
+        ticker.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = &mut all_streams => break,
+                _ = ticker.tick() => {
+                    self.emit_metrics(stats, progress, self.message.concurrent_users)
+                        .await;
+                }
+            }
+        }
+    }
+
+    // Per-user model: `concurrent_users` long-lived virtual users, each sending
+    // `iterations` requests one after another. Without `iterations` (the userLoop model)
+    // they keep going, paced at an even share of `requestsPerSecond`, until the duration
+    // or `totalRequests` is reached.
+    async fn run_per_user(
+        &self,
+        client: &Client,
+        stats: &Arc<Mutex<Statistics>>,
+        auth: Option<Arc<Auth>>,
+        cache: Option<Arc<ResponseCache>>,
+        iterations: Option<u32>,
+        progress: &mut Progress,
+    ) {
+        let start_time = progress.start_time;
+        let user_count = self.message.concurrent_users;
+
+        match iterations {
+            Some(iterations) => {
+                info!("👥 Running {} virtual users x {} iterations", user_count, iterations)
+            }
+            None => info!(
+                "👥 Running {} looping virtual users @ {} RPS",
+                user_count, self.message.requests_per_second
+            ),
+        }
+
+        // The userLoop model's request cap, shared by all users, and the gap between one
+        // user's requests
+        let request_cap = (iterations.is_none() && self.message.total_requests > 0)
+            .then_some(self.message.total_requests);
+        let user_interval = (iterations.is_none() && self.message.requests_per_second > 0)
+            .then(|| {
+                Duration::from_secs_f64(user_count as f64 / self.message.requests_per_second as f64)
+            });
+        let iterations = iterations.unwrap_or(u32::MAX);
+
+        let test_duration = self.test_duration();
+        let mut users = vec![];
+
+        // Users share the connections/streams the protocol settings allow
+        let limits = ConcurrencyLimits::new(&self.message);
+        let saturation_sampler = limits.sample_saturation(stats.clone());
+        let ramp_up = limits.ramp_up(&self.message, start_time);
+
+        if let Some(identities) = &self.message.virtual_users {
+            if identities.len() < self.message.concurrent_users as usize {
+                warn!(
+                    "🪪 {} identities for {} virtual users, some will be shared",
+                    identities.len(),
+                    self.message.concurrent_users
+                );
+            }
+        }
+
+        // Requests started by all users together, checked against `maxTotalRequests`
+        let sent = Arc::new(AtomicU32::new(0));
+
+        // Steps are sent as the endpoints of the message
+        let steps = self.message.steps.as_ref().map(|steps| LoadTestMessage {
+            endpoints: Some(steps.clone()),
+            ..self.message.clone()
+        });
+        let flow = steps.as_ref().unwrap_or(&self.message);
+
+        for user in 0..self.message.concurrent_users {
+            let client = client.clone();
+            let stats = stats.clone();
+            let message = match &self.message.virtual_users {
+                Some(identities) => {
+                    identity::bind(flow, &identities[user as usize % identities.len()])
+                }
+                None => flow.clone(),
+            };
+            let limits = limits.clone();
+            let cancel = progress.cancel.clone();
+            let auth = auth.clone();
+            let cache = cache.clone();
+            let sent = sent.clone();
+            let data_source = self.data_source.clone();
+
+            users.push(tokio::spawn(async move {
+                let mut rng = seeded_rng(message.seed, user as u64 + 1);
+                let mut session = message.sticky_session.clone().map(StickySession::new);
+
+                // Users start staggered so together they send at an even rate
+                let mut next_at = user_interval.map(|interval| {
+                    start_time + interval.mul_f64(user as f64 / message.concurrent_users as f64)
+                });
+
+                // With `steps`, an iteration is the whole flow; they're sent as the
+                // message's endpoints, in order
+                let step_count = message.steps.as_ref().map_or(1, Vec::len);
+
+                'iterations: for iteration in 0..iterations {
+                    // Values extracted by this iteration's earlier steps
+                    let mut context = HashMap::new();
+
+                    // When this iteration's first request is due, with userLoop pacing
+                    let mut intended = None;
+                    if let (Some(at), Some(interval)) = (next_at.as_mut(), user_interval) {
+                        tokio::select! {
+                            _ = cancel.cancelled() => break 'iterations,
+                            _ = sleep_until((*at).into()) => {}
+                        }
+                        intended = Some(*at);
+                        *at += interval;
+                    }
+
+                    for step in 0..step_count {
+                        if test_duration.is_some_and(|duration| start_time.elapsed() >= duration) {
+                            break 'iterations;
+                        }
+
+                        let budget = message.max_total_requests.unwrap_or(u32::MAX);
+                        let previously_sent = sent.fetch_add(1, Ordering::Relaxed);
+                        if previously_sent >= budget
+                            || request_cap.is_some_and(|cap| previously_sent >= cap)
+                        {
+                            break 'iterations;
+                        }
+
+                        let sequence = iteration * step_count as u32 + step as u32;
+                        let mut plan = RequestPlan::draw(&message, &mut rng, sequence);
+                        if message.steps.is_some() {
+                            plan.endpoint = Some(step);
+                        }
+                        let Ok(_permit) = limits.acquire(plan.group(&message), &stats).await
+                        else {
+                            break 'iterations;
+                        };
+                        if let Some(intended) = intended.take() {
+                            stats.lock().await.record_schedule_error(intended);
+                        }
+
+                        // This request's dataSource row and extracted values, on top of the
+                        // user's identity
+                        let mut bound = data_source
+                            .as_ref()
+                            .map(|data_source| identity::bind(&message, data_source.next_row()));
+                        if !context.is_empty() {
+                            let base = bound.as_ref().unwrap_or(&message);
+                            bound = Some(identity::bind(base, &context));
+                        }
+
+                        let extracted = tokio::select! {
+                            _ = cancel.cancelled() => break 'iterations,
+                            extracted = send_request(
+                                &client,
+                                bound.as_ref().unwrap_or(&message),
+                                &stats,
+                                plan,
+                                &mut session,
+                                auth.as_deref(),
+                                cache.as_deref(),
+                            ) => extracted,
+                        };
+
+                        if let Some(think_time) = &message.think_time_ms {
+                            let think = sample_think_time(think_time, &mut rng);
+                            tokio::select! {
+                                _ = cancel.cancelled() => break 'iterations,
+                                _ = sleep(think) => {}
+                            }
+                        }
+
+                        match extracted {
+                            Some(values) => context.extend(values),
+                            // Later steps would go out without what this one provides
+                            None => break,
+                        }
+                    }
+                }
+            }));
+        }
+
+        // Send metrics every second until all virtual users are done
+        let all_users = join_all(users);
+        tokio::pin!(all_users);
+
+        let mut ticker = interval(Duration::from_secs(1));
+        ticker.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = &mut all_users => break,
+                _ = ticker.tick() => {
+                    let active_users = self.message.ramped_users(start_time.elapsed());
+                    self.emit_metrics(stats, progress, active_users).await;
+                }
+            }
+        }
+        saturation_sampler.abort();
+        ramp_up.abort();
+
+        // Users stopping at a smaller totalRequests also count past the budget
+        let budget = self
+            .message
+            .max_total_requests
+            .filter(|&budget| request_cap.is_none_or(|cap| cap > budget));
+        if let Some(budget) = budget {
+            if sent.load(Ordering::Relaxed) > budget && progress.abort_reason.is_none() {
+                warn!("💸 Request budget of {} exhausted, stopping test", budget);
+                progress.abort_reason = Some(AbortReason::BudgetExhausted);
+            }
+        }
+    }
+
+    // Publishes buffered metrics oldest first, keeping the rest when the broker refuses one
+    async fn flush_metrics(&self, buffer: &mut MetricBuffer) {
+        while let Some(payload) = buffer.pending.front() {
+            let published = self
+                .outbox
+                .publish(
+                    &self.queues.metrics,
+                    payload,
+                    self.publish_properties(self.persistent_metrics),
+                )
+                .await;
+
+            if let Err(e) = published {
+                warn!(
+                    "📦 Metrics queue unavailable, buffering {} metrics: {}",
+                    buffer.pending.len(),
+                    e
+                );
+                return;
+            }
+            buffer.pending.pop_front();
+        }
+    }
+
+    // Once the warm-up window has passed, aborts the run if the achieved rate is under
+    // `minExpectedRps` and the shortfall is the target's rather than the worker's
+    fn check_throughput(&self, stats: &Statistics, progress: &mut Progress, rps: f64) {
+        let Some(min_rps) = self.message.min_expected_rps else {
+            progress.throughput_checked = true;
+            return;
+        };
+
+        let warmup = self.message.warmup_seconds.unwrap_or(DEFAULT_WARMUP_SECONDS);
+        if progress.start_time.elapsed() < Duration::from_secs(warmup as u64) {
+            return;
+        }
+        progress.throughput_checked = true;
+
+        if rps >= min_rps {
+            return;
+        }
+
+        // Requests queued behind the schedule while the observed latency would have
+        // allowed the minimum rate: the worker, not the target, is falling behind
+        let latency_capacity = match stats.get_average() {
+            avg if avg > 0.0 => self.message.effective_concurrency() as f64 * 1000.0 / avg,
+            _ => f64::INFINITY,
+        };
+        if stats.get_queue_time_percentile(95.0) > 0.0 && latency_capacity >= min_rps {
+            warn!(
+                "⚠️ {:.1} RPS is below minExpectedRps {:.1}, but the worker is the bottleneck; continuing",
+                rps, min_rps
+            );
+            return;
+        }
+
+        warn!(
+            "🛑 {:.1} RPS after {}s warm-up is below minExpectedRps {:.1}, aborting",
+            rps, warmup, min_rps
+        );
+        progress.abort_reason = Some(AbortReason::ThroughputBelowMinimum);
+        progress.cancel.cancel();
+    }
+
+    // Trips the `abortOnErrorRate` circuit breaker once enough requests have been seen
+    fn check_error_rate(&self, stats: &Statistics, progress: &mut Progress) {
+        let Some(max_error_rate) = self.message.abort_on_error_rate else {
+            return;
+        };
+        let min_samples = self
+            .message
+            .abort_min_samples
+            .unwrap_or(DEFAULT_ABORT_MIN_SAMPLES);
+        if progress.abort_reason.is_some() || stats.total_requests < min_samples {
+            return;
+        }
+
+        let error_rate = stats.error_rate();
+        if error_rate <= max_error_rate {
+            return;
+        }
+
+        warn!(
+            "🛑 Error rate {:.1}% after {} requests is over abortOnErrorRate {:.1}%, aborting",
+            error_rate, stats.total_requests, max_error_rate
+        );
+        progress.abort_reason = Some(AbortReason::ErrorRateExceeded);
+        progress.cancel.cancel();
+    }
+
+    // When the request after the one scheduled at `scheduled_at` is due under
+    // `loadExpression`, sitting out stretches where the expression drops to zero
+    async fn next_scheduled(
+        &self,
+        profile: &RateProfile,
+        start_time: Instant,
+        mut scheduled_at: Instant,
+        arrivals: ArrivalDistribution,
+        rng: &mut StdRng,
+    ) -> Instant {
+        let test_duration = self.test_duration();
+
+        loop {
+            let t = scheduled_at.saturating_duration_since(start_time).as_secs_f64();
+            if let Some(gap) = Self::scheduled_gap(profile, t, arrivals, rng) {
+                return scheduled_at + gap;
+            }
+
+            if test_duration.is_some_and(|duration| start_time.elapsed() >= duration) {
+                return scheduled_at;
+            }
+            scheduled_at = scheduled_at.max(Instant::now()) + EXPRESSION_IDLE_POLL;
+            sleep_until(scheduled_at.into()).await;
+        }
+    }
+
+    // Gap after a request scheduled `t` seconds in, at the profile's rate then; None
+    // while the rate is zero or not a number
+    fn scheduled_gap(
+        profile: &RateProfile,
+        t: f64,
+        arrivals: ArrivalDistribution,
+        rng: &mut StdRng,
+    ) -> Option<Duration> {
+        let rate = profile.evaluate(t);
+        if !(rate.is_finite() && rate > 0.0) {
+            return None;
+        }
+
+        Some(match arrivals {
+            ArrivalDistribution::Uniform => Duration::from_secs_f64(1.0 / rate),
+            ArrivalDistribution::Poisson => exponential_interval(rng, rate),
+        })
+    }
+
+    // Warnings recorded during the run plus ones only visible from the final state
+    fn warnings(&self, stats: &Statistics, progress: &Progress) -> Vec<String> {
+        let mut warnings = stats.warnings.clone();
+
+        let effective_concurrency = self.message.effective_concurrency();
+        if effective_concurrency < self.message.concurrent_users {
+            push_warning(
+                &mut warnings,
+                format!(
+                    "concurrentUsers {} clamped to {} by protocol limits",
+                    self.message.concurrent_users, effective_concurrency
+                ),
+            );
+        }
+
+        if progress.metric_buffer.dropped > 0 {
+            push_warning(
+                &mut warnings,
+                format!(
+                    "{} metric snapshots dropped while the broker was unavailable",
+                    progress.metric_buffer.dropped
+                ),
+            );
+        }
+
+        warnings
+    }
+
+    // Warns once the pacing loop has stayed behind schedule for several points in a row
+    fn check_schedule_lag(
+        &self,
+        stats: &mut Statistics,
+        progress: &mut Progress,
+        schedule_lag_ms: Option<u64>,
+    ) {
+        let Some(lag) = schedule_lag_ms.filter(|lag| *lag >= SCHEDULE_LAG_THRESHOLD_MS) else {
+            progress.lagging_points = 0;
+            return;
+        };
+
+        progress.lagging_points += 1;
+        if progress.lagging_points == SUSTAINED_LAG_POINTS {
+            warn!(
+                "🐢 Falling behind the requested rate: requests are going out {}ms late",
+                lag
+            );
+            stats.warn("rps_target_unmet: requests fell behind the pacing schedule");
+        }
+    }
+
+    // Opening stretch of the run whose requests aren't recorded
+    fn excluded_warmup(&self) -> Duration {
+        let warmup = self.message.excluded_warmup_seconds.unwrap_or(0);
+        Duration::from_secs(warmup as u64)
+    }
+
+    fn test_duration(&self) -> Option<Duration> {
+        (self.message.duration_seconds > 0)
+            .then(|| Duration::from_secs(self.message.duration_seconds as u64))
+    }
+
+    // Requests the run stops after, if it's bounded by a count
+    fn request_target(&self) -> Option<u32> {
+        let target = match self.message.iterations_per_user {
+            Some(iterations) => iterations * self.message.concurrent_users,
+            None => self.message.total_requests,
+        };
+        (target > 0).then_some(target)
+    }
+
+    // With resetHistogramPerPhase, closes each rateSchedule segment's phase as the run
+    // passes its end. Runs until the returned task is aborted.
+    async fn phase_boundaries(
+        &self,
+        stats: Arc<Mutex<Statistics>>,
+        start_time: Instant,
+    ) -> Option<JoinHandle<()>> {
+        let segments = self.message.rate_schedule.as_ref()?;
+        if self.message.reset_histogram_per_phase != Some(true) {
+            return None;
+        }
+
+        stats.lock().await.start_phases();
+
+        // The last segment's phase is closed when the run ends
+        let mut end = Duration::ZERO;
+        let boundaries: Vec<Duration> = segments[..segments.len() - 1]
+            .iter()
+            .map(|segment| {
+                end += Duration::from_secs(segment.duration_seconds as u64);
+                end
+            })
+            .collect();
+
+        Some(tokio::spawn(async move {
+            for boundary in boundaries {
+                sleep_until((start_time + boundary).into()).await;
+                stats.lock().await.end_phase();
+            }
+        }))
+    }
+
+    // Publishes how far along the run is every `HEARTBEAT_INTERVAL`, so the dashboard
+    // sees the worker is alive between metric snapshots. Runs until the returned task
+    // is aborted.
+    fn heartbeat(&self, stats: Arc<Mutex<Statistics>>, start_time: Instant) -> JoinHandle<()> {
+        let outbox = self.outbox.clone();
+        let queue = self.queues.progress.clone();
+        let test_id = self.message.test_id.clone();
+        let request_target = self.request_target();
+        let test_duration = self.test_duration();
+
+        tokio::spawn(async move {
+            let mut ticker = interval(HEARTBEAT_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let requests_completed = {
+                    let stats = stats.lock().await;
+                    stats.total_requests + stats.warmup_requests
+                };
+                let elapsed = start_time.elapsed();
+
+                // Whichever limit is closer to being reached ends the run
+                let by_requests = request_target
+                    .map(|target| requests_completed as f64 / target as f64);
+                let by_time =
+                    test_duration.map(|duration| elapsed.as_secs_f64() / duration.as_secs_f64());
+                let complete = by_requests.into_iter().chain(by_time).fold(0.0, f64::max);
+
+                let progress = TestProgress {
+                    test_id: test_id.clone(),
+                    percent_complete: complete.min(1.0) * 100.0,
+                    elapsed_seconds: elapsed.as_secs_f64(),
+                    requests_completed,
+                };
+                let Ok(payload) = serde_json::to_vec(&progress) else {
+                    continue;
+                };
+
+                // Best effort: the next heartbeat supersedes a lost one
+                let _ = outbox
+                    .publish(
+                        &queue,
+                        &payload,
+                        BasicProperties::default().with_content_type("application/json".into()),
+                    )
+                    .await;
+            }
+        })
+    }
+
+    // Records a time-series point, publishes a metric snapshot and, when due, a checkpoint
+    async fn emit_metrics(
+        &self,
+        stats: &Mutex<Statistics>,
+        progress: &mut Progress,
+        active_users: u32,
+    ) {
+        // Excluded warm-up requests aren't recorded, so there's nothing to report yet
+        if Instant::now() < progress.measure_start {
+            return;
+        }
+
+        let mut stats_snapshot = stats.lock().await;
+        let elapsed = progress.measure_start.elapsed();
+        let rps = stats_snapshot.total_requests as f64 / elapsed.as_secs_f64();
+        let interval_latency = stats_snapshot.take_interval_latency();
+        let interval_seconds = progress.last_point.elapsed().as_secs_f64();
+        if let Some(mean) = interval_latency.mean {
+            stats_snapshot.record_interval_mean(mean, interval_seconds);
+        }
+        let interval_requests = stats_snapshot.total_requests - progress.last_point_requests;
+        let interval_failures = stats_snapshot.failed_requests - progress.last_point_failures;
+        let pool_saturated = stats_snapshot.take_interval_pool_saturated();
+        let schedule_lag_ms = stats_snapshot.schedule_lag_ms;
+        self.check_schedule_lag(&mut stats_snapshot, progress, schedule_lag_ms);
+        stats_snapshot.error_log.summarize_if_due();
+
+        if !progress.throughput_checked {
+            self.check_throughput(&stats_snapshot, progress, rps);
+        }
+        self.check_error_rate(&stats_snapshot, progress);
+
+        progress.time_series_data.push(TimeSeriesPoint {
+            timestamp: Utc::now().timestamp(),
+            interval_seconds,
+            rps,
+            goodput_rps: stats_snapshot.successful_requests as f64 / elapsed.as_secs_f64(),
+            interval_rps: interval_requests as f64 / interval_seconds,
+            target_rps: self
+                .rate_profile
+                .as_ref()
+                .map(|profile| profile.evaluate(progress.start_time.elapsed().as_secs_f64())),
+            avg_response_time: stats_snapshot.get_average(),
+            min_response_time: interval_latency.min,
+            max_response_time: interval_latency.max,
+            p95_response_time: interval_latency.p95,
+            error_rate: stats_snapshot.error_rate(),
+            interval_error_rate: match interval_requests {
+                0 => 0.0,
+                requests => interval_failures as f64 / requests as f64,
+            },
+            pool_saturated,
+            schedule_lag_ms: schedule_lag_ms.map(|lag| lag as f64),
+        });
+        progress.last_point = Instant::now();
+        progress.last_point_requests = stats_snapshot.total_requests;
+        progress.last_point_failures = stats_snapshot.failed_requests;
+
+        // Keep result messages bounded for hours-long tests
+        let max_points = self
+            .message
+            .max_time_series_points
+            .unwrap_or(DEFAULT_MAX_TIME_SERIES_POINTS);
+        let points = progress.time_series_data.len();
+        analysis::downsample(&mut progress.time_series_data, max_points);
+        if progress.time_series_data.len() < points {
+            stats_snapshot
+                .warn("timeSeriesData was downsampled to stay within maxTimeSeriesPoints");
+        }
+
+        let metric = (!self.message.disable_metrics)
+            .then(|| self.metric(&stats_snapshot, active_users));
+
+        // A partial result, so a long run isn't lost if the worker dies late
+        let checkpoint_due = self
+            .message
+            .checkpoint_interval_seconds
+            .is_some_and(|seconds| {
+                progress.last_checkpoint.elapsed() >= Duration::from_secs(seconds as u64)
+            });
+        let checkpoint = checkpoint_due.then(|| {
+            progress.last_checkpoint = Instant::now();
+            self.build_result(&stats_snapshot, progress, true)
+        });
+
+        // Requests in flight record into the statistics, so they never wait on the broker
+        drop(stats_snapshot);
+
+        // Send metric to queue
+        if let Some(metric) = metric {
+            self.publish_metric(&metric, progress).await;
+        }
+
+        if let Some(checkpoint) = checkpoint {
+            match self.publish_checkpoint(&checkpoint).await {
+                Ok(_) => info!("💾 Checkpoint published at {:.0}s", elapsed.as_secs_f64()),
+                Err(e) => warn!("Failed to publish checkpoint: {}", e),
+            }
+        }
+    }
+
+    async fn publish_metric(&self, metric: &Metric, progress: &mut Progress) {
+        if let Ok(payload) = self.encode::<_, proto::Metric>(metric) {
+            progress.metric_buffer.push(payload);
+        }
+        self.flush_metrics(&mut progress.metric_buffer).await;
+        progress.metric_buffer.trim();
+    }
+
+    fn metric(&self, stats: &Statistics, active_users: u32) -> Metric {
+        Metric {
+            test_id: self.message.test_id.clone(),
+            timestamp: Utc::now().to_rfc3339(),
+            request_count: stats.total_requests,
+            success_count: stats.successful_requests,
+            error_count: stats.failed_requests,
+            avg_response_time: stats.get_average(),
+            status_code: None,
+            error_message: None,
+            active_users,
+            region: self.region.clone(),
+        }
+    }
+}
+
+// Run bookkeeping shared by the load loops and the metrics emitter
+struct Progress {
+    start_time: Instant,
+    // Where recorded statistics begin: `start_time` unless excludedWarmupSeconds holds
+    // back the warm-up
+    measure_start: Instant,
+    started_at: i64,
+    time_series_data: Vec<TimeSeriesPoint>,
+    last_point: Instant,
+    // Requests completed as of `last_point`
+    last_point_requests: u32,
+    last_point_failures: u32,
+    last_checkpoint: Instant,
+    throughput_checked: bool,
+    // Consecutive points with schedule lag above `SCHEDULE_LAG_THRESHOLD_MS`
+    lagging_points: u32,
+    abort_reason: Option<AbortReason>,
+    // Cancelled when the run is aborted early
+    cancel: CancellationToken,
+    metric_buffer: MetricBuffer,
+}
+
+impl Progress {
+    fn new(cancel: CancellationToken, excluded_warmup: Duration) -> Self {
+        let now = Instant::now();
+        Self {
+            start_time: now,
+            measure_start: now + excluded_warmup,
+            started_at: Utc::now().timestamp(),
+            time_series_data: vec![],
+            last_point: now + excluded_warmup,
+            last_point_requests: 0,
+            last_point_failures: 0,
+            last_checkpoint: now,
+            throughput_checked: false,
+            lagging_points: 0,
+            abort_reason: None,
+            cancel,
+            metric_buffer: MetricBuffer::new(worker::metrics_buffer_size()),
+        }
+    }
+}
+
+// Permits bounding in-flight requests: one pool per endpoint group with its own
+// `groupConcurrency` limit, and a shared pool for everything else
+#[derive(Clone)]
+struct ConcurrencyLimits {
+    shared: Arc<Semaphore>,
+    // Permits the shared pool holds so far, which `rampUpSeconds` grows over time
+    shared_granted: Arc<AtomicUsize>,
+    groups: HashMap<String, (Arc<Semaphore>, u32)>,
+}
+
+impl ConcurrencyLimits {
+    fn new(message: &LoadTestMessage) -> Self {
+        let initial_permits = Self::shared_permits(message, Duration::ZERO);
+        Self {
+            shared: Arc::new(Semaphore::new(initial_permits)),
+            shared_granted: Arc::new(AtomicUsize::new(initial_permits)),
+            groups: message
+                .group_concurrency
+                .iter()
+                .flatten()
+                .map(|(group, &limit)| {
+                    let semaphore = Arc::new(Semaphore::new(limit as usize));
+                    (group.clone(), (semaphore, limit))
+                })
+                .collect(),
+        }
+    }
+
+    // Size of the shared pool `elapsed` into the run
+    fn shared_permits(message: &LoadTestMessage, elapsed: Duration) -> usize {
+        message
+            .ramped_users(elapsed)
+            .min(message.effective_concurrency()) as usize
+    }
+
+    // Grows the shared pool along `rampUpSeconds`, finishing once it's full. Does
+    // nothing without a ramp.
+    fn ramp_up(&self, message: &LoadTestMessage, start_time: Instant) -> JoinHandle<()> {
+        let shared = self.shared.clone();
+        let shared_granted = self.shared_granted.clone();
+        let message = message.clone();
+
+        tokio::spawn(async move {
+            let full = Self::shared_permits(&message, Duration::MAX);
+            let mut granted = Self::shared_permits(&message, Duration::ZERO);
+            let mut ticker = interval(RAMP_UP_STEP);
+
+            while granted < full {
+                ticker.tick().await;
+                let allowed = Self::shared_permits(&message, start_time.elapsed());
+                if allowed > granted {
+                    shared.add_permits(allowed - granted);
+                    granted = allowed;
+                    shared_granted.store(granted, Ordering::Relaxed);
+                }
+            }
+        })
+    }
+
+    // Waits for a permit from the group's pool, recording how many of the group's
+    // requests are then in flight
+    async fn acquire(
+        &self,
+        group: Option<&str>,
+        stats: &Mutex<Statistics>,
+    ) -> std::result::Result<OwnedSemaphorePermit, AcquireError> {
+        let Some((group, (semaphore, limit))) =
+            group.and_then(|group| self.groups.get_key_value(group))
+        else {
+            return self.shared.clone().acquire_owned().await;
+        };
+
+        let permit = semaphore.clone().acquire_owned().await?;
+        let in_flight = *limit - semaphore.available_permits() as u32;
+        stats
+            .lock()
+            .await
+            .record_group_in_flight(group, in_flight, *limit);
+
+        Ok(permit)
+    }
+
+    // Permits currently held across the shared pool and every group's
+    fn in_flight(&self) -> u32 {
+        // Saturating: the ramp adds permits just before it updates `shared_granted`
+        let shared = self
+            .shared_granted
+            .load(Ordering::Relaxed)
+            .saturating_sub(self.shared.available_permits());
+        let groups: usize = self
+            .groups
+            .values()
+            .map(|(semaphore, limit)| *limit as usize - semaphore.available_permits())
+            .sum();
+        (shared + groups) as u32
+    }
+
+    // Periodically records whether every shared permit is taken, i.e. new requests
+    // are queueing client-side, and how many requests are in flight. Runs until the
+    // returned task is aborted.
+    fn sample_saturation(&self, stats: Arc<Mutex<Statistics>>) -> JoinHandle<()> {
+        let limits = self.clone();
+
+        tokio::spawn(async move {
+            let mut ticker = interval(POOL_SAMPLE_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let saturated = limits.shared.available_permits() == 0;
+                let in_flight = limits.in_flight();
+                stats.lock().await.record_pool_sample(saturated, in_flight);
+            }
+        })
+    }
+}
+
+// Encoded metrics waiting to be published; bounded so a long broker outage can't
+// exhaust memory, dropping the oldest entries first
+struct MetricBuffer {
+    pending: VecDeque<Vec<u8>>,
+    capacity: usize,
+    dropped: u32,
+}
+
+impl MetricBuffer {
+    fn new(capacity: usize) -> Self {
+        Self {
+            pending: VecDeque::new(),
+            capacity,
+            dropped: 0,
+        }
+    }
+
+    fn push(&mut self, payload: Vec<u8>) {
+        self.pending.push_back(payload);
+    }
+
+    // Drops the oldest unpublished metrics beyond capacity
+    fn trim(&mut self) {
+        while self.pending.len() > self.capacity {
+            self.pending.pop_front();
+            self.dropped += 1;
+        }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression::Expression;
+    use crate::pacing::seeded_rng;
+    use crate::test_support::{message, MockH2Server, MockServer, Published, Reply, Unavailable};
+    use crate::types::ResultVerbosity;
+    use prost::Message;
+    use serde_json::{json, Value};
+
+    const CURVE: &str = "100 + 50*sin(t/5)";
+    const SECONDS: usize = 60;
+
+    type Captured = Arc<std::sync::Mutex<Vec<Published>>>;
+
+    // An executor that keeps what it publishes in memory; queues are named after the
+    // fields of `Queues`
+    fn executor(message: LoadTestMessage) -> (LoadTestExecutor, Captured) {
+        let published = Captured::default();
+        let queues = Queues {
+            results: "results".to_string(),
+            metrics: "metrics".to_string(),
+            plans: "plans".to_string(),
+            traces: "traces".to_string(),
+            progress: "progress".to_string(),
+        };
+        let executor = LoadTestExecutor::new(message, published.clone(), queues, HashMap::new());
+        (executor, published)
+    }
+
+    // Runs the test to the end and returns its final result
+    async fn run(message: LoadTestMessage) -> Value {
+        let (executor, published) = executor(message);
+        executor.execute().await.unwrap();
+        final_result(&published)
+    }
+
+    // The last result published, checkpoints aside
+    fn final_result(published: &Captured) -> Value {
+        let published = published.lock().unwrap();
+        let result = published.iter().rev().find(|p| p.queue == "results").unwrap();
+        serde_json::from_slice(&result.payload).unwrap()
+    }
+
+    // Send times over the first SECONDS seconds, following the schedule from t = 0
+    fn arrivals(profile: &RateProfile, arrivals: ArrivalDistribution) -> Vec<f64> {
+        let mut rng = seeded_rng(Some(7), 0);
+        let mut sent = vec![];
+        let mut t = 0.0;
+        while t < SECONDS as f64 {
+            sent.push(t);
+            t += LoadTestExecutor::scheduled_gap(profile, t, arrivals, &mut rng)
+                .unwrap()
+                .as_secs_f64();
+        }
+        sent
+    }
+
+    // Requests the curve asks for between `from` and `to` seconds
+    fn expected_requests(expression: &Expression, from: f64, to: f64) -> f64 {
+        let steps = 1000;
+        let width = (to - from) / steps as f64;
+        (0..steps)
+            .map(|step| expression.evaluate(from + (step as f64 + 0.5) * width) * width)
+            .sum()
+    }
+
+    #[test]
+    fn uniform_arrivals_track_a_sinusoidal_expression() {
+        let expression = Expression::parse(CURVE).unwrap();
+        let profile = RateProfile::Expression(expression.clone());
+
+        let mut per_second = [0.0; SECONDS];
+        for t in arrivals(&profile, ArrivalDistribution::Uniform) {
+            per_second[t as usize] += 1.0;
+        }
+
+        for (second, achieved) in per_second.into_iter().enumerate() {
+            let expected = expected_requests(&expression, second as f64, second as f64 + 1.0);
+            assert!(
+                (achieved - expected).abs() <= expected * 0.05,
+                "second {}: {} requests, curve asks for {:.1}",
+                second,
+                achieved,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn poisson_arrivals_track_a_sinusoidal_expression_overall() {
+        let expression = Expression::parse(CURVE).unwrap();
+        let profile = RateProfile::Expression(expression.clone());
+
+        let achieved = arrivals(&profile, ArrivalDistribution::Poisson).len() as f64;
+        let expected = expected_requests(&expression, 0.0, SECONDS as f64);
+        assert!(
+            (achieved - expected).abs() <= expected * 0.05,
+            "{} requests, curve asks for {:.1}",
+            achieved,
+            expected
+        );
+    }
+
+    #[test]
+    fn no_gap_while_the_expression_is_zero() {
+        let profile = RateProfile::Expression(Expression::parse("max(0, t - 10)").unwrap());
+        let mut rng = seeded_rng(Some(7), 0);
+        let mut gap_at = |t| {
+            LoadTestExecutor::scheduled_gap(&profile, t, ArrivalDistribution::Uniform, &mut rng)
+        };
+
+        assert_eq!(gap_at(5.0), None);
+        assert_eq!(gap_at(20.0), Some(Duration::from_millis(100)));
+    }
+
+    #[tokio::test]
+    async fn zero_rps_sends_unthrottled() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+
+        for arrivals in ["uniform", "poisson"] {
+            let result = run(message(json!({
+                "targetUrl": server.url("/"),
+                "totalRequests": 5,
+                "requestsPerSecond": 0,
+                "arrivalDistribution": arrivals,
+            })))
+            .await;
+
+            assert_eq!(result["status"], "completed", "{} arrivals", arrivals);
+            assert_eq!(result["successfulRequests"], 5, "{} arrivals", arrivals);
+        }
+    }
+
+    #[tokio::test]
+    async fn metrics_wait_out_a_broker_outage_and_go_out_in_order() {
+        let (mut executor, published) = executor(message(json!({})));
+        let captured = std::mem::replace(&mut executor.outbox, Arc::new(Unavailable));
+        let mut progress = Progress::new(CancellationToken::new(), Duration::ZERO);
+        let mut stats = Statistics::new();
+
+        for _ in 0..3 {
+            stats.record_success("GET /", 10, 5, 200);
+            let metric = executor.metric(&stats, 1);
+            executor.publish_metric(&metric, &mut progress).await;
+        }
+        assert_eq!(progress.metric_buffer.pending.len(), 3);
+
+        executor.outbox = captured;
+        executor.flush_metrics(&mut progress.metric_buffer).await;
+
+        assert!(progress.metric_buffer.pending.is_empty());
+        let request_counts: Vec<Value> = published
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|published| published.queue == "metrics")
+            .map(|published| {
+                serde_json::from_slice::<Value>(&published.payload).unwrap()["requestCount"]
+                    .clone()
+            })
+            .collect();
+        assert_eq!(request_counts, [1, 2, 3]);
+    }
+
+    #[test]
+    fn metric_buffer_drops_the_oldest_past_capacity() {
+        let mut buffer = MetricBuffer::new(2);
+        for payload in ["first", "second", "third"] {
+            buffer.push(payload.as_bytes().to_vec());
+        }
+
+        buffer.trim();
+
+        assert_eq!(buffer.pending, [b"second".to_vec(), b"third".to_vec()]);
+        assert_eq!(buffer.dropped, 1);
+    }
+
+    #[test]
+    fn protobuf_result_decodes_to_what_the_json_says() {
+        let (executor, _) = executor(message(json!({ "testId": "proto" })));
+        let progress = Progress::new(CancellationToken::new(), Duration::ZERO);
+        let mut stats = Statistics::new();
+        stats.record_success("GET /", 12, 4, 200);
+        stats.record_success("GET /", 30, 9, 200);
+        stats.record_status_code(503);
+        stats.record_failure("unexpected_status_503".to_string());
+        stats.record_retries(2);
+        let result = executor.build_result(&stats, &progress, false);
+
+        let encoded = proto::TestResult::from(&result).encode_to_vec();
+        let decoded = proto::TestResult::decode(encoded.as_slice()).unwrap();
+        assert_eq!(decoded, proto::TestResult::from(&result));
+
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["testId"], decoded.test_id);
+        assert_eq!(json["status"], decoded.status);
+        assert_eq!(json["totalRequests"], decoded.total_requests);
+        assert_eq!(json["successfulRequests"], decoded.successful_requests);
+        assert_eq!(json["failedRequests"], decoded.failed_requests);
+        assert_eq!(json["totalRetries"], decoded.total_retries);
+        assert_eq!(json["p95ResponseTime"], decoded.p95_response_time);
+        assert_eq!(json["errorRate"], decoded.error_rate);
+        assert_eq!(json["statusCodeDistribution"]["503"], decoded.status_code_distribution[&503]);
+        assert_eq!(
+            json["errorDistribution"]["unexpected_status_503"],
+            decoded.error_distribution["unexpected_status_503"]
+        );
+    }
+
+    #[tokio::test]
+    async fn zero_total_requests_runs_for_the_duration() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+        let started = Instant::now();
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "totalRequests": 0,
+            "durationSeconds": 1,
+            "requestsPerSecond": 20,
+        })))
+        .await;
+
+        assert_eq!(result["status"], "completed");
+        assert!(result["successfulRequests"].as_u64().unwrap() > 0);
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_secs(1), "stopped after {:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(3), "stopped after {:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn cancel_ends_the_run_without_waiting_out_the_pacing() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+        let (executor, published) = executor(message(json!({
+            "targetUrl": server.url("/"),
+            "totalRequests": 0,
+            "durationSeconds": 30,
+            "requestsPerSecond": 1,
+        })));
+        let cancel = executor.cancel_token();
+        let started = Instant::now();
+
+        let running = tokio::spawn(executor.execute());
+        sleep(Duration::from_millis(200)).await;
+        cancel.cancel();
+        running.await.unwrap().unwrap();
+
+        // Well before the one-second pause after the first request would be over
+        assert!(started.elapsed() < Duration::from_millis(800));
+        let result = final_result(&published);
+        assert_eq!(result["cancelled"], true);
+        assert_eq!(result["status"], "aborted");
+        assert_eq!(result["abortReason"], "cancelled");
+        assert_eq!(result["successfulRequests"], 1);
+    }
+
+    #[tokio::test]
+    async fn hanging_setup_is_cut_off_by_the_setup_timeout() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/health" | "/rows.csv" => Reply::ok().head_delay(Duration::from_secs(10)),
+            _ => Reply::ok(),
+        })
+        .await;
+
+        for setup in [
+            json!({ "preflight": { "url": server.url("/health") } }),
+            json!({ "dataSource": { "csvUrl": server.url("/rows.csv") } }),
+        ] {
+            let mut fields = json!({ "targetUrl": server.url("/"), "setupTimeoutSeconds": 1 });
+            fields.as_object_mut().unwrap().extend(setup.as_object().unwrap().clone());
+            let started = Instant::now();
+
+            let result = run(message(fields)).await;
+
+            assert!(started.elapsed() < Duration::from_secs(3), "{}", setup);
+            assert_eq!(result["status"], "aborted", "{}", setup);
+            assert_eq!(result["abortReason"], "setup_timeout", "{}", setup);
+            assert_eq!(result["totalRequests"], 0, "{}", setup);
+        }
+        assert!(server.received().iter().all(|request| request.path != "/"));
+    }
+
+    #[tokio::test]
+    async fn data_source_rows_are_used_in_turn() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/users/{{id}}"),
+            "totalRequests": 9,
+            "concurrentUsers": 3,
+            "dataSource": { "csv": "id,name\n1,ann\n2,bo\n3,cy\n" },
+        })))
+        .await;
+
+        assert_eq!(result["successfulRequests"], 9);
+        let mut uses = HashMap::new();
+        for request in server.received() {
+            *uses.entry(request.path).or_insert(0) += 1;
+        }
+        let expected: HashMap<String, i32> = ["/users/1", "/users/2", "/users/3"]
+            .into_iter()
+            .map(|path| (path.to_string(), 3))
+            .collect();
+        assert_eq!(uses, expected);
+    }
+
+    #[tokio::test]
+    async fn bootstrap_values_reach_every_load_request() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/tenants" => Reply::ok().body(r#"{"id": "t-42"}"#),
+            _ => Reply::ok(),
+        })
+        .await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/tenants/{{tenant}}/items"),
+            "headers": { "X-Tenant": "{{tenant}}" },
+            "totalRequests": 6,
+            "concurrentUsers": 3,
+            "bootstrap": [{
+                "name": "create-tenant",
+                "url": server.url("/tenants"),
+                "method": "POST",
+                "extract": { "tenant": "$.id" },
+            }],
+        })))
+        .await;
+
+        assert_eq!(result["successfulRequests"], 6);
+        let received = server.received();
+        assert_eq!(received[0].path, "/tenants");
+        let load = &received[1..];
+        assert_eq!(load.len(), 6);
+        for request in load {
+            assert_eq!(request.path, "/tenants/t-42/items");
+            assert_eq!(request.header("x-tenant"), Some("t-42"));
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_bootstrap_fails_the_test() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/tenants" => Reply::status(500),
+            _ => Reply::ok(),
+        })
+        .await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/items"),
+            "bootstrap": [{
+                "name": "create-tenant",
+                "url": server.url("/tenants"),
+                "expectedStatusCodes": [200],
+            }],
+        })))
+        .await;
+
+        assert_eq!(result["status"], "aborted");
+        assert_eq!(result["abortReason"], "setup_error");
+        assert!(result["warnings"].to_string().contains("create-tenant"));
+        assert!(server.received().iter().all(|request| request.path == "/tenants"));
+    }
+
+    #[tokio::test]
+    async fn checkpoints_are_published_before_the_final_result() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+        let (executor, published) = executor(message(json!({
+            "targetUrl": server.url("/"),
+            "totalRequests": 0,
+            "durationSeconds": 3,
+            "requestsPerSecond": 10,
+            "checkpointIntervalSeconds": 1,
+        })));
+
+        executor.execute().await.unwrap();
+
+        let results: Vec<Value> = published
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| p.queue == "results")
+            .map(|p| serde_json::from_slice(&p.payload).unwrap())
+            .collect();
+        let (last, checkpoints) = results.split_last().unwrap();
+        assert!(checkpoints.len() >= 2, "{} checkpoints", checkpoints.len());
+        let mut sent = 0;
+        for checkpoint in checkpoints {
+            assert_eq!(checkpoint["partial"], true);
+            assert_eq!(checkpoint["status"], "running");
+            let total = checkpoint["totalRequests"].as_u64().unwrap();
+            assert!(total > sent);
+            sent = total;
+        }
+        assert_eq!(last["partial"], false);
+        assert_eq!(last["status"], "completed");
+        assert!(last["totalRequests"].as_u64().unwrap() >= sent);
+    }
+
+    #[tokio::test]
+    async fn results_name_the_worker_version_and_capabilities() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+
+        for verbosity in ["full", "summary"] {
+            let result = run(message(json!({
+                "targetUrl": server.url("/"),
+                "resultVerbosity": verbosity,
+            })))
+            .await;
+
+            assert_eq!(result["workerVersion"], worker::WORKER_VERSION);
+            assert_eq!(result["capabilities"], json!(worker::CAPABILITIES));
+        }
+    }
+
+    #[tokio::test]
+    async fn each_virtual_user_sends_its_iterations_one_after_another() {
+        // When each user's requests arrived, by the user header
+        let arrivals: Arc<std::sync::Mutex<HashMap<String, Vec<Instant>>>> = Arc::default();
+        let seen = arrivals.clone();
+        let server = MockServer::start(move |request| {
+            let user = request.header("x-user").unwrap_or_default().to_string();
+            seen.lock()
+                .unwrap()
+                .entry(user)
+                .or_default()
+                .push(Instant::now());
+            Reply::ok().head_delay(Duration::from_millis(30))
+        })
+        .await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "headers": { "X-User": "{{user}}" },
+            "concurrentUsers": 3,
+            "iterationsPerUser": 4,
+            "virtualUsers": [{ "user": "a" }, { "user": "b" }, { "user": "c" }],
+        })))
+        .await;
+
+        assert_eq!(result["totalRequests"], 12);
+        assert_eq!(result["successfulRequests"], 12);
+        let arrivals = arrivals.lock().unwrap();
+        assert_eq!(arrivals.len(), 3);
+        for (user, times) in arrivals.iter() {
+            assert_eq!(times.len(), 4, "user {}", user);
+            // The next request only goes out once the previous response is in
+            for pair in times.windows(2) {
+                let gap = pair[1] - pair[0];
+                assert!(
+                    gap >= Duration::from_millis(30),
+                    "user {} after {:?}",
+                    user,
+                    gap
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn the_configured_fraction_of_requests_is_fault_injected() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "method": "POST",
+            "body": { "name": "loadmaster" },
+            "totalRequests": 400,
+            "concurrentUsers": 8,
+            "seed": 7,
+            "faultInjection": { "rate": 0.25 },
+        })))
+        .await;
+
+        let injected = result["faultInjectedRequests"].as_u64().unwrap();
+        assert!(
+            (70..=130).contains(&injected),
+            "{} of 400 injected",
+            injected
+        );
+        let by_fault: u64 = result["faultDistribution"]
+            .as_object()
+            .unwrap()
+            .values()
+            .map(|count| count.as_u64().unwrap())
+            .sum();
+        assert_eq!(by_fault, injected);
+        // The target answered everything it was sent; the faults' failures are
+        // counted apart
+        assert_eq!(result["totalRequests"].as_u64().unwrap() + injected, 400);
+        assert_eq!(result["failedRequests"], 0);
+        assert!(result["faultInjectedFailures"].as_u64().unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn requests_waiting_for_a_permit_report_queue_time() {
+        let server =
+            MockServer::start(|_| Reply::ok().head_delay(Duration::from_millis(100))).await;
+        let run_with_users = |users: u32| {
+            run(message(json!({
+                "targetUrl": server.url("/"),
+                "totalRequests": 6,
+                "concurrentUsers": users,
+                "requestsPerSecond": 50,
+            })))
+        };
+
+        // One permit for requests due every 20ms that each take 100ms
+        let constrained = run_with_users(1).await["queueTimeP95"].as_f64().unwrap();
+        let unconstrained = run_with_users(6).await["queueTimeP95"].as_f64().unwrap();
+
+        assert!(constrained >= 50.0, "queued {}ms", constrained);
+        assert!(unconstrained < 20.0, "queued {}ms", unconstrained);
+    }
+
+    #[tokio::test]
+    async fn http2_multiplexes_up_to_the_stream_limit_on_one_connection() {
+        let server = MockH2Server::start(Duration::from_millis(100)).await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "httpVersion": "http2",
+            "maxConcurrentStreams": 4,
+            "concurrentUsers": 10,
+            "totalRequests": 12,
+        })))
+        .await;
+
+        assert_eq!(result["successfulRequests"], 12);
+        assert_eq!(server.connections(), 1);
+        assert_eq!(server.max_streams(), 4);
+    }
+
+    #[tokio::test]
+    async fn a_slow_endpoint_dominates_the_latency_tail() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/slow" => Reply::ok().head_delay(Duration::from_millis(60)),
+            _ => Reply::ok(),
+        })
+        .await;
+
+        let result = run(message(json!({
+            "totalRequests": 100,
+            "concurrentUsers": 4,
+            "seed": 3,
+            "endpoints": [
+                { "name": "fast", "url": server.url("/fast"), "weight": 9 },
+                { "name": "slow", "url": server.url("/slow"), "weight": 1 },
+            ],
+        })))
+        .await;
+
+        let buckets = result["tailComposition"].as_array().unwrap();
+        assert_eq!(buckets.len(), 2);
+        for bucket in buckets {
+            let top = &bucket["entries"][0];
+            assert!(
+                top["endpoint"].as_str().unwrap().ends_with("/slow"),
+                "{}",
+                bucket
+            );
+            assert_eq!(top["statusCode"], 200);
+            assert!(top["share"].as_f64().unwrap() > 0.5, "{}", bucket);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_spike_shows_in_the_interval_max_but_not_the_average() {
+        let (executor, _) = executor(message(json!({})));
+        let mut progress = Progress::new(CancellationToken::new(), Duration::ZERO);
+        let stats = Mutex::new(Statistics::new());
+        {
+            let mut stats = stats.lock().await;
+            for _ in 0..19 {
+                stats.record_success("GET /", 10, 5, 200);
+            }
+            stats.record_success("GET /", 400, 5, 200);
+        }
+
+        executor.emit_metrics(&stats, &mut progress, 1).await;
+
+        let point = &progress.time_series_data[0];
+        assert_eq!(point.min_response_time, 10.0);
+        assert!(
+            point.max_response_time >= 399.0,
+            "max {}",
+            point.max_response_time
+        );
+        assert!(
+            point.avg_response_time < 40.0,
+            "average {}",
+            point.avg_response_time
+        );
+        assert!(point.p95_response_time < point.max_response_time);
+    }
+
+    #[tokio::test]
+    async fn a_target_too_slow_for_the_minimum_rate_aborts_early() {
+        let server =
+            MockServer::start(|_| Reply::ok().head_delay(Duration::from_millis(200))).await;
+        let started = Instant::now();
+
+        // Two users against 200ms responses manage about 10 RPS
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "totalRequests": 0,
+            "durationSeconds": 30,
+            "concurrentUsers": 2,
+            "minExpectedRps": 50,
+            "warmupSeconds": 1,
+        })))
+        .await;
+
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "ran {:?}",
+            started.elapsed()
+        );
+        assert_eq!(result["status"], "aborted");
+        assert_eq!(result["abortReason"], "throughput_below_minimum");
+    }
+
+    #[tokio::test]
+    async fn each_endpoint_is_held_to_its_own_expected_statuses() {
+        let server = MockServer::start(|request| match request.method.as_str() {
+            "POST" => Reply::status(201),
+            _ => Reply::ok(),
+        })
+        .await;
+
+        let result = run(message(json!({
+            "totalRequests": 30,
+            "seed": 5,
+            "endpoints": [
+                { "name": "list", "url": server.url("/items"), "expectedStatusCodes": [200] },
+                {
+                    "name": "create",
+                    "url": server.url("/items"),
+                    "method": "POST",
+                    "expectedStatusCodes": [201],
+                },
+                { "name": "purge", "url": server.url("/items"), "expectedStatusCodes": [204] },
+            ],
+        })))
+        .await;
+
+        let endpoints = &result["endpointResults"];
+        for (name, passing) in [("list", true), ("create", true), ("purge", false)] {
+            let endpoint = &endpoints[name];
+            let requests = endpoint["requests"].as_u64().unwrap();
+            assert!(requests > 0, "{}", name);
+            let (passed, failed) = if passing {
+                (requests, 0)
+            } else {
+                (0, requests)
+            };
+            assert_eq!(endpoint["passed"], passed, "{}", name);
+            assert_eq!(endpoint["failed"], failed, "{}", name);
+        }
+        assert_eq!(result["failedRequests"], endpoints["purge"]["requests"]);
+    }
+
+    #[tokio::test]
+    async fn failing_preflight_skips_the_load_phase() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/health" => Reply::status(503),
+            _ => Reply::ok(),
+        })
+        .await;
+
+        for preflight in [
+            json!({ "url": server.url("/health") }),
+            json!({ "url": server.url("/ready"), "expectedStatus": 204 }),
+        ] {
+            let result = run(message(json!({
+                "targetUrl": server.url("/"),
+                "totalRequests": 10,
+                "preflight": preflight,
+            })))
+            .await;
+
+            assert_eq!(result["status"], "aborted", "{}", preflight);
+            assert_eq!(result["abortReason"], "preflight_failed", "{}", preflight);
+            assert_eq!(result["totalRequests"], 0, "{}", preflight);
+        }
+        assert!(server.received().iter().all(|request| request.path != "/"));
+    }
+
+    #[tokio::test]
+    async fn results_are_routed_to_their_status_queue() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/health" => Reply::status(503),
+            _ => Reply::ok(),
+        })
+        .await;
+        let routes = HashMap::from([("aborted".to_string(), "results_failed".to_string())]);
+
+        for (preflight, queue) in [("/health", "results_failed"), ("/", "results")] {
+            let (mut executor, published) = executor(message(json!({
+                "targetUrl": server.url("/"),
+                "preflight": { "url": server.url(preflight) },
+            })));
+            executor.result_routes = routes.clone();
+
+            executor.execute().await.unwrap();
+
+            let published = published.lock().unwrap();
+            let queues: Vec<&str> = published
+                .iter()
+                .filter(|p| p.queue.starts_with("results"))
+                .map(|p| p.queue.as_str())
+                .collect();
+            assert_eq!(queues, [queue]);
+        }
+    }
+
+    #[tokio::test]
+    async fn prewarmed_connections_spare_the_measured_requests_a_handshake() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+        let target = server.url("/");
+        let run_with = |prewarm: Option<u32>| {
+            let mut message = message(json!({
+                "targetUrl": target,
+                "totalRequests": 12,
+                "concurrentUsers": 4,
+            }));
+            message.prewarm_connections = prewarm;
+            run(message)
+        };
+
+        let cold = run_with(None).await;
+        let warm = run_with(Some(4)).await;
+
+        assert!(cold["connectionsEstablished"].as_u64().unwrap() > 0);
+        assert_eq!(warm["connectionsEstablished"], 0);
+        let prewarms = server
+            .received()
+            .iter()
+            .filter(|r| r.method == "HEAD")
+            .count();
+        assert_eq!(prewarms, 4);
+    }
+
+    #[tokio::test]
+    async fn each_endpoint_group_keeps_its_own_concurrency() {
+        // Requests in flight at the server and the most seen at once, by path
+        let in_flight: Arc<std::sync::Mutex<HashMap<String, (u32, u32)>>> = Arc::default();
+        let seen = in_flight.clone();
+        let delay = Duration::from_millis(50);
+        let server = MockServer::start(move |request| {
+            let path = request.path.clone();
+            {
+                let mut in_flight = seen.lock().unwrap();
+                let (current, peak) = in_flight.entry(path.clone()).or_default();
+                *current += 1;
+                *peak = (*peak).max(*current);
+            }
+            // Done just before the reply goes out, so the freed permit's next request
+            // can't be counted alongside it
+            let seen = seen.clone();
+            tokio::spawn(async move {
+                sleep(delay - Duration::from_millis(5)).await;
+                seen.lock().unwrap().get_mut(&path).unwrap().0 -= 1;
+            });
+            Reply::ok().head_delay(delay)
+        })
+        .await;
+
+        let result = run(message(json!({
+            "totalRequests": 80,
+            "concurrentUsers": 20,
+            "seed": 11,
+            "groupConcurrency": { "read": 6, "write": 2 },
+            "endpoints": [
+                { "name": "list", "url": server.url("/read"), "group": "read", "weight": 1 },
+                { "name": "save", "url": server.url("/write"), "group": "write", "weight": 1 },
+            ],
+        })))
+        .await;
+
+        let peaks = in_flight.lock().unwrap();
+        assert_eq!(peaks["/read"].1, 6);
+        assert_eq!(peaks["/write"].1, 2);
+        let groups = &result["groupResults"];
+        assert_eq!(groups["read"]["concurrency"], 6);
+        assert_eq!(groups["read"]["peakInFlight"], 6);
+        assert_eq!(groups["write"]["peakInFlight"], 2);
+        let requests = groups["read"]["requests"].as_u64().unwrap()
+            + groups["write"]["requests"].as_u64().unwrap();
+        assert_eq!(requests, 80);
+    }
+
+    #[tokio::test]
+    async fn p99_of_a_ten_request_run_is_flagged_unreliable() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "totalRequests": 10,
+        })))
+        .await;
+
+        let adequacy = &result["sampleAdequacy"];
+        assert_eq!(adequacy["samples"], 10);
+        assert_eq!(adequacy["suggestedMinimumSamples"], 100);
+        let reliable = |percentile: f64| {
+            adequacy["percentiles"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|confidence| confidence["percentile"] == percentile)
+                .unwrap()["reliable"]
+                .clone()
+        };
+        assert_eq!(reliable(50.0), true);
+        assert_eq!(reliable(99.0), false);
+    }
+
+    #[tokio::test]
+    async fn goodput_leaves_out_the_failed_fraction() {
+        // Every fourth request fails
+        let sent = Arc::new(AtomicU32::new(0));
+        let counted = sent.clone();
+        let server = MockServer::start(move |_| {
+            if counted.fetch_add(1, Ordering::SeqCst) % 4 == 3 {
+                Reply::status(500)
+            } else {
+                Reply::ok()
+            }
+        })
+        .await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "totalRequests": 40,
+            "expectedStatusCodes": [200],
+        })))
+        .await;
+
+        assert_eq!(result["failedRequests"], 10);
+        let rps = result["requestsPerSecond"].as_f64().unwrap();
+        let goodput = result["goodputRps"].as_f64().unwrap();
+        assert!(
+            (goodput / rps - 0.75).abs() < 1e-9,
+            "{} of {}",
+            goodput,
+            rps
+        );
+
+        // The same holds for each metrics point
+        let (executor, _) = executor(message(json!({})));
+        let mut progress = Progress::new(CancellationToken::new(), Duration::ZERO);
+        let stats = Mutex::new(Statistics::new());
+        {
+            let mut stats = stats.lock().await;
+            for _ in 0..3 {
+                stats.record_success("GET /", 10, 5, 200);
+            }
+            stats.record_failure("HTTP 500".to_string());
+        }
+        executor.emit_metrics(&stats, &mut progress, 1).await;
+        let point = &progress.time_series_data[0];
+        assert!((point.goodput_rps / point.rps - 0.75).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn connections_are_cycled_every_n_requests() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "totalRequests": 20,
+            "maxRequestsPerConnection": 5,
+        })))
+        .await;
+
+        let closing = server
+            .received()
+            .iter()
+            .filter(|r| r.header("connection") == Some("close"))
+            .count();
+        assert_eq!(closing, 4);
+        // The first connection, and one after each close but the last
+        assert_eq!(result["connectionsEstablished"], 4);
+    }
+
+    #[tokio::test]
+    async fn resolved_plan_shows_the_clamped_concurrency() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+        let (executor, published) = executor(message(json!({
+            "targetUrl": server.url("/"),
+            "concurrentUsers": 10,
+            "maxConnectionsPerOrigin": 2,
+        })));
+
+        executor.execute().await.unwrap();
+
+        let published = published.lock().unwrap();
+        // Published ahead of everything else
+        assert_eq!(published[0].queue, "plans");
+        let plan: Value = serde_json::from_slice(&published[0].payload).unwrap();
+        assert_eq!(plan["concurrentUsers"], 10);
+        assert_eq!(plan["effectiveConcurrency"], 2);
+        assert_eq!(plan["warmupSeconds"], DEFAULT_WARMUP_SECONDS);
+        assert_eq!(plan["maxTimeSeriesPoints"], DEFAULT_MAX_TIME_SERIES_POINTS);
+    }
+
+    #[tokio::test]
+    async fn looping_users_stop_at_the_request_budget() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+        let started = Instant::now();
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "totalRequests": 0,
+            "durationSeconds": 30,
+            "concurrentUsers": 3,
+            "concurrencyModel": "userLoop",
+            "maxTotalRequests": 15,
+        })))
+        .await;
+
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(result["abortReason"], "budget_exhausted");
+        assert_eq!(result["totalRequests"], 15);
+        assert_eq!(server.received().len(), 15);
+    }
+
+    #[tokio::test]
+    async fn clamping_and_histogram_overflow_are_result_warnings() {
+        let (executor, _) = executor(message(json!({
+            "concurrentUsers": 10,
+            "maxConnectionsPerOrigin": 2,
+        })));
+        let progress = Progress::new(CancellationToken::new(), Duration::ZERO);
+        let mut stats = Statistics::new();
+        // Above the histogram's 60s range, twice
+        stats.record_success("GET /", 90_000, 5, 200);
+        stats.record_success("GET /", 120_000, 5, 200);
+
+        let result = executor.build_result(&stats, &progress, false);
+
+        assert_eq!(
+            result.warnings,
+            [
+                "latency above the 60s histogram range was left out of percentiles",
+                "concurrentUsers 10 clamped to 2 by protocol limits",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn sse_streams_report_their_event_count_and_rate() {
+        // Five events 100ms apart, then the stream ends
+        let events: String = (0..5).map(|i| format!("data: {}\n\n", i)).collect();
+        let server = MockServer::start(move |_| {
+            Reply::ok()
+                .header("content-type", "text/event-stream")
+                .body(events.clone())
+                .streamed(9, Duration::from_millis(100))
+        })
+        .await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/events"),
+            "protocol": "sse",
+            "totalRequests": 0,
+            "durationSeconds": 1,
+            "concurrentUsers": 2,
+        })))
+        .await;
+
+        let sse = &result["sse"];
+        assert_eq!(sse["connections"], 2);
+        assert_eq!(sse["eventsReceived"], 10);
+        for connection in sse["perConnection"].as_array().unwrap() {
+            assert_eq!(connection["events"], 5);
+        }
+        // Events and stream opens are counted over the same time
+        let rate = sse["eventRate"].as_f64().unwrap();
+        let opens = result["requestsPerSecond"].as_f64().unwrap();
+        assert!((rate / opens - 5.0).abs() < 1e-9, "{} vs {}", rate, opens);
+        let gap = sse["interEventP50"].as_f64().unwrap();
+        assert!((90.0..200.0).contains(&gap), "gap {}", gap);
+    }
+
+    #[tokio::test]
+    async fn a_tiny_pool_under_load_reports_saturation() {
+        let server = MockServer::start(|_| Reply::ok().head_delay(Duration::from_millis(50))).await;
+
+        // Two permits for an unpaced backlog of requests, lasting past the first
+        // metrics interval
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "totalRequests": 50,
+            "concurrentUsers": 2,
+        })))
+        .await;
+
+        let saturation = result["poolSaturationPercent"].as_f64().unwrap();
+        assert!(saturation > 50.0, "saturated {}%", saturation);
+        let points = result["timeSeriesData"].as_array().unwrap();
+        assert!(
+            points.iter().any(|point| point["poolSaturated"] == true),
+            "{:?}",
+            points
+        );
+    }
+
+    #[tokio::test]
+    async fn a_fast_target_leaves_achieved_concurrency_below_the_configured_users() {
+        let server = MockServer::start(|_| Reply::ok().head_delay(Duration::from_millis(20))).await;
+
+        // 50 evenly spread RPS of 20ms responses keep about one request in flight
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "mode": "openArrivalRate",
+            "totalRequests": 25,
+            "requestsPerSecond": 50,
+            "concurrentUsers": 20,
+        })))
+        .await;
+
+        let achieved = result["achievedConcurrency"].as_f64().unwrap();
+        assert!(achieved > 0.3 && achieved < 3.0, "achieved {}", achieved);
+        let rps = result["requestsPerSecond"].as_f64().unwrap();
+        let latency = result["averageResponseTime"].as_f64().unwrap();
+        assert!((achieved - rps * latency / 1000.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn latency_is_broken_down_per_dimension_value() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/us" => Reply::ok().head_delay(Duration::from_millis(100)),
+            _ => Reply::ok(),
+        })
+        .await;
+
+        let result = run(message(json!({
+            "totalRequests": 20,
+            "concurrentUsers": 4,
+            "seed": 5,
+            "endpoints": [
+                { "name": "eu", "url": server.url("/eu"), "weight": 1, "tags": { "region": "eu" } },
+                { "name": "us", "url": server.url("/us"), "weight": 1, "tags": { "region": "us" } },
+            ],
+        })))
+        .await;
+
+        let regions = &result["breakdownByDimension"]["region"];
+        let (eu, us) = (&regions["eu"], &regions["us"]);
+        assert_eq!(
+            eu["requests"].as_u64().unwrap() + us["requests"].as_u64().unwrap(),
+            20
+        );
+        assert!(eu["p50ResponseTime"].as_f64().unwrap() < 50.0, "{}", eu);
+        assert!(us["p50ResponseTime"].as_f64().unwrap() >= 100.0, "{}", us);
+    }
+
+    #[tokio::test]
+    async fn lag_behind_an_unreachable_rate_is_warned_about() {
+        let (executor, _) = executor(message(json!({ "requestsPerSecond": 1000 })));
+        let mut progress = Progress::new(CancellationToken::new(), Duration::ZERO);
+        let stats = Mutex::new(Statistics::new());
+
+        // Requests going out later and later, as when the rate can't be kept up
+        for lag in [1500, 2500, 3500] {
+            stats.lock().await.record_queue_time(lag);
+            executor.emit_metrics(&stats, &mut progress, 1).await;
+        }
+
+        let lags: Vec<_> = progress
+            .time_series_data
+            .iter()
+            .map(|point| point.schedule_lag_ms)
+            .collect();
+        assert_eq!(lags, [Some(1500.0), Some(2500.0), Some(3500.0)]);
+        let result = executor.build_result(&*stats.lock().await, &progress, false);
+        assert!(
+            result
+                .warnings
+                .iter()
+                .any(|warning| warning.starts_with("rps_target_unmet")),
+            "{:?}",
+            result.warnings
+        );
+    }
+
+    #[tokio::test]
+    async fn results_and_metrics_carry_the_worker_region() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+        let (mut executor, published) = executor(message(json!({ "targetUrl": server.url("/") })));
+        // As read from WORKER_REGION
+        executor.region = Some("eu-west-1".to_string());
+        let mut progress = Progress::new(CancellationToken::new(), Duration::ZERO);
+
+        executor
+            .emit_metrics(&Mutex::new(Statistics::new()), &mut progress, 1)
+            .await;
+        executor.execute().await.unwrap();
+
+        assert_eq!(final_result(&published)["region"], "eu-west-1");
+        let published = published.lock().unwrap();
+        let metric = published.iter().find(|p| p.queue == "metrics").unwrap();
+        let metric: Value = serde_json::from_slice(&metric.payload).unwrap();
+        assert_eq!(metric["region"], "eu-west-1");
+    }
+
+    #[tokio::test]
+    async fn traced_requests_become_chrome_trace_events() {
+        let server = MockServer::start(|_| Reply::ok().head_delay(Duration::from_millis(10))).await;
+        let (executor, published) = executor(message(json!({
+            "targetUrl": server.url("/"),
+            "totalRequests": 8,
+            "concurrentUsers": 2,
+            "trace": { "maxRequests": 5 },
+        })));
+
+        executor.execute().await.unwrap();
+
+        let published = published.lock().unwrap();
+        let trace = published.iter().find(|p| p.queue == "traces").unwrap();
+        let trace: Value = serde_json::from_slice(&trace.payload).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        // Only the first five requests fit
+        assert_eq!(events.len(), 5);
+        for event in events {
+            assert_eq!(event["ph"], "X");
+            assert_eq!(event["pid"], 1);
+            assert!(event["name"].as_str().unwrap().contains("GET"), "{}", event);
+            assert!(event["ts"].is_u64() && event["tid"].is_u64(), "{}", event);
+            assert!(event["dur"].as_u64().unwrap() >= 10_000, "{}", event);
+            assert_eq!(event["args"]["status"], 200);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_three_second_test_has_a_point_per_second() {
+        // Slow enough that request counts alone would hardly ever emit a point
+        let server =
+            MockServer::start(|_| Reply::ok().head_delay(Duration::from_millis(300))).await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "totalRequests": 0,
+            "durationSeconds": 3,
+            "requestsPerSecond": 1,
+        })))
+        .await;
+
+        let points = result["timeSeriesData"].as_array().unwrap().len();
+        assert!((2..=4).contains(&points), "{} points", points);
+    }
+
+    #[tokio::test]
+    async fn ramp_up_grows_the_permits_over_time() {
+        let message = message(json!({ "concurrentUsers": 11, "rampUpSeconds": 1 }));
+        let limits = ConcurrencyLimits::new(&message);
+        let ramp_up = limits.ramp_up(&message, Instant::now());
+
+        let mut permits = vec![limits.shared.available_permits()];
+        for _ in 0..4 {
+            sleep(Duration::from_millis(300)).await;
+            permits.push(limits.shared.available_permits());
+        }
+        ramp_up.abort();
+
+        assert_eq!(permits[0], 1);
+        assert!(
+            permits.windows(2).all(|pair| pair[0] < pair[1]),
+            "{:?}",
+            permits
+        );
+        assert_eq!(permits[4], 11);
+    }
+
+    #[tokio::test]
+    async fn byte_averages_match_the_known_sizes() {
+        // Every other request fails, with the same 100-byte body
+        let sent = Arc::new(AtomicU32::new(0));
+        let counted = sent.clone();
+        let server = MockServer::start(move |_| {
+            let status = match counted.fetch_add(1, Ordering::SeqCst) % 2 {
+                0 => 200,
+                _ => 500,
+            };
+            Reply::status(status).body(vec![b'r'; 100])
+        })
+        .await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "method": "POST",
+            // 17 bytes serialized
+            "body": { "name": "abcdef" },
+            "totalRequests": 10,
+            "expectedStatusCodes": [200],
+        })))
+        .await;
+
+        assert_eq!(server.received()[0].body.len(), 17);
+        assert_eq!(result["avgRequestBytes"], 17.0);
+        assert_eq!(result["avgResponseBytes"], 100.0);
+        // Failed requests' bytes are spread over the successful ones
+        assert_eq!(result["bytesPerSuccessfulRequest"], 234.0);
+    }
+
+    #[tokio::test]
+    async fn requests_past_the_timeout_are_bucketed_as_timeouts() {
+        let server =
+            MockServer::start(|_| Reply::ok().head_delay(Duration::from_millis(500))).await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "totalRequests": 3,
+            "requestTimeoutMs": 50,
+        })))
+        .await;
+
+        assert_eq!(result["failedRequests"], 3);
+        assert_eq!(result["errorDistribution"], json!({ "timeout": 3 }));
+    }
+
+    #[tokio::test]
+    async fn summary_verbosity_drops_the_heavy_fields_and_full_keeps_them() {
+        let (executor, _) = executor(message(json!({})));
+        let mut progress = Progress::new(CancellationToken::new(), Duration::ZERO);
+        let stats = Mutex::new(Statistics::new());
+        {
+            let mut stats = stats.lock().await;
+            stats.record_success("GET /", 10, 5, 200);
+            stats.record_failure("timeout".to_string());
+        }
+        executor.emit_metrics(&stats, &mut progress, 1).await;
+        let stats = stats.into_inner();
+
+        let mut full = executor.build_result(&stats, &progress, false);
+        full.apply_verbosity(ResultVerbosity::Full);
+        let full = serde_json::to_value(&full).unwrap();
+        assert_eq!(full["timeSeriesData"].as_array().unwrap().len(), 1);
+        assert!(full["latencyHistogram"].is_string());
+        assert_eq!(full["statusCodeDistribution"]["200"], 1);
+        assert_eq!(full["errorDistribution"]["timeout"], 1);
+
+        let mut summary = executor.build_result(&stats, &progress, false);
+        summary.apply_verbosity(ResultVerbosity::Summary);
+        let summary = serde_json::to_value(&summary).unwrap();
+        assert_eq!(summary["timeSeriesData"], json!([]));
+        assert!(summary["latencyHistogram"].is_null());
+        assert_eq!(summary["statusCodeDistribution"], json!({}));
+        assert_eq!(summary["errorDistribution"], json!({}));
+        // The scalar summary stays
+        assert_eq!(summary["totalRequests"], 2);
+        assert_eq!(summary["failedRequests"], 1);
+    }
+
+    #[tokio::test]
+    async fn each_virtual_user_keeps_its_identity_across_iterations() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+
+        run(message(json!({
+            "targetUrl": server.url("/accounts/{{account}}"),
+            "headers": { "Authorization": "Bearer {{token}}" },
+            "concurrentUsers": 3,
+            "iterationsPerUser": 5,
+            "virtualUsers": [
+                { "account": "a", "token": "ta" },
+                { "account": "b", "token": "tb" },
+                { "account": "c", "token": "tc" },
+            ],
+        })))
+        .await;
+
+        let mut requests: HashMap<String, u32> = HashMap::new();
+        for received in server.received() {
+            let account = received.path.trim_start_matches("/accounts/").to_string();
+            // The account and token always come from the same identity
+            let token = format!("Bearer t{}", account);
+            assert_eq!(received.header("authorization"), Some(token.as_str()));
+            *requests.entry(account).or_default() += 1;
+        }
+        assert_eq!(
+            requests,
+            HashMap::from([("a".into(), 5), ("b".into(), 5), ("c".into(), 5)])
+        );
+    }
+
+    #[tokio::test]
+    async fn a_target_that_stays_slow_fails_recovery() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/healthy" => Reply::ok(),
+            _ => Reply::ok().head_delay(Duration::from_millis(150)),
+        })
+        .await;
+        let run_with = |probe_path: &str| {
+            run(message(json!({
+                "targetUrl": server.url("/"),
+                "totalRequests": 5,
+                "verifyRecovery": {
+                    "url": server.url(probe_path),
+                    "windowSeconds": 1,
+                    "baselineP95Ms": 50.0,
+                    "probeIntervalMs": 50,
+                },
+            })))
+        };
+
+        let degraded = run_with("/").await;
+        let recovered = run_with("/healthy").await;
+
+        assert_eq!(degraded["recoveryFailed"], true);
+        assert!(degraded["recoveredWithinSeconds"].is_null());
+        assert_eq!(recovered["recoveryFailed"], false);
+        assert!(recovered["recoveredWithinSeconds"].as_f64().unwrap() < 1.0);
+    }
+
+    #[tokio::test]
+    async fn an_invalid_body_regex_fails_setup() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "bodyAssertions": { "regex": "(unclosed" },
+        })))
+        .await;
+
+        assert_eq!(result["abortReason"], AbortReason::SetupError.as_str());
+        assert_eq!(result["totalRequests"], 0);
+        assert!(server.received().is_empty());
+        let warnings = result["warnings"].as_array().unwrap();
+        assert!(
+            warnings[0]
+                .as_str()
+                .unwrap()
+                .starts_with("bodyAssertions.regex is invalid"),
+            "{:?}",
+            warnings
+        );
+    }
+
+    #[tokio::test]
+    async fn bursty_load_spreads_the_in_flight_distribution() {
+        let server = MockServer::start(|_| Reply::ok().head_delay(Duration::from_millis(30))).await;
+
+        // Batches of ten at once, then nothing until the next batch 100ms later
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "totalRequests": 50,
+            "requestsPerSecond": 10,
+            "concurrentUsers": 10,
+        })))
+        .await;
+
+        let in_flight = &result["inFlightDistribution"];
+        assert!(in_flight["samples"].as_u64().unwrap() > 20, "{}", in_flight);
+        assert!(in_flight["max"].as_u64().unwrap() >= 8, "{}", in_flight);
+        assert!(in_flight["p50"].as_u64().unwrap() <= 2, "{}", in_flight);
+    }
+
+    #[tokio::test]
+    async fn open_arrivals_keep_their_rate_despite_slow_responses() {
+        let server =
+            MockServer::start(|_| Reply::ok().head_delay(Duration::from_millis(200))).await;
+
+        // Arrivals don't wait for the 200ms responses, keeping about ten in flight
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "mode": "openArrivalRate",
+            "requestsPerSecond": 50,
+            "totalRequests": 0,
+            "durationSeconds": 1,
+            "concurrentUsers": 20,
+        })))
+        .await;
+
+        let sent = server.received().len();
+        assert!((45..=52).contains(&sent), "{} requests", sent);
+        assert_eq!(result["totalRequests"].as_u64().unwrap() as usize, sent);
+    }
+
+    #[tokio::test]
+    async fn a_slow_first_response_is_reported_as_the_cold_start() {
+        // Only the first request to each server is slow
+        async fn cold_server() -> MockServer {
+            let served = AtomicU32::new(0);
+            MockServer::start(move |_| match served.fetch_add(1, Ordering::SeqCst) {
+                0 => Reply::ok().head_delay(Duration::from_millis(300)),
+                _ => Reply::ok(),
+            })
+            .await
+        }
+        let run_against = |server: &MockServer, exclude: bool| {
+            let mut message = message(json!({
+                "targetUrl": server.url("/"),
+                "totalRequests": 10,
+            }));
+            message.exclude_cold_start = exclude;
+            run(message)
+        };
+
+        let (first, second) = (cold_server().await, cold_server().await);
+        let included = run_against(&first, false).await;
+        let excluded = run_against(&second, true).await;
+
+        for result in [&included, &excluded] {
+            assert!(result["coldStartMs"].as_u64().unwrap() >= 300, "{}", result);
+            assert_eq!(result["successfulRequests"], 10);
+        }
+        assert!(included["p99ResponseTime"].as_f64().unwrap() >= 300.0);
+        assert!(excluded["p99ResponseTime"].as_f64().unwrap() < 100.0);
+    }
+
+    #[tokio::test]
+    async fn warmup_requests_stay_out_of_the_percentiles() {
+        // Slow while cold, for the first second
+        let started = Instant::now();
+        let server = MockServer::start(move |_| match started.elapsed() {
+            elapsed if elapsed < Duration::from_secs(1) => {
+                Reply::ok().head_delay(Duration::from_millis(300))
+            }
+            _ => Reply::ok(),
+        })
+        .await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "mode": "openArrivalRate",
+            "requestsPerSecond": 20,
+            "totalRequests": 0,
+            "durationSeconds": 2,
+            "concurrentUsers": 10,
+            "excludedWarmupSeconds": 1,
+        })))
+        .await;
+
+        let warmup = result["warmupRequests"].as_u64().unwrap();
+        let measured = result["totalRequests"].as_u64().unwrap();
+        assert!(warmup >= 15, "{} warm-up requests", warmup);
+        assert_eq!(warmup + measured, server.received().len() as u64);
+        let p99 = result["p99ResponseTime"].as_f64().unwrap();
+        assert!(p99 < 100.0, "p99 {}", p99);
+    }
+
+    #[tokio::test]
+    async fn excluded_warmup_is_separate_from_the_throughput_warmup() {
+        // Far below minExpectedRps, but the run ends before that check's warm-up does
+        let server =
+            MockServer::start(|_| Reply::ok().head_delay(Duration::from_millis(100))).await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "totalRequests": 0,
+            "durationSeconds": 2,
+            "minExpectedRps": 1000.0,
+            "warmupSeconds": 5,
+            "excludedWarmupSeconds": 1,
+        })))
+        .await;
+
+        assert_eq!(result["status"], "completed");
+        let warmup = result["warmupRequests"].as_u64().unwrap();
+        assert!((1..=10).contains(&warmup), "{} warm-up requests", warmup);
+        assert!(result["totalRequests"].as_u64().unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn progress_heartbeats_only_move_forward() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+        let (executor, published) = executor(message(json!({
+            "targetUrl": server.url("/"),
+            "mode": "openArrivalRate",
+            "requestsPerSecond": 20,
+            "totalRequests": 0,
+            "durationSeconds": 2,
+        })));
+
+        executor.execute().await.unwrap();
+
+        let published = published.lock().unwrap();
+        let heartbeats: Vec<Value> = published
+            .iter()
+            .filter(|p| p.queue == "progress")
+            .map(|p| serde_json::from_slice(&p.payload).unwrap())
+            .collect();
+        assert!(heartbeats.len() >= 2, "{:?}", heartbeats);
+        for pair in heartbeats.windows(2) {
+            let field = |i: usize, name: &str| pair[i][name].as_f64().unwrap();
+            assert!(field(1, "percentComplete") > field(0, "percentComplete"));
+            assert!(field(1, "elapsedSeconds") > field(0, "elapsedSeconds"));
+            assert!(field(1, "requestsCompleted") >= field(0, "requestsCompleted"));
+        }
+        let last = heartbeats.last().unwrap();
+        assert!(last["percentComplete"].as_f64().unwrap() <= 100.0);
+        assert!(last["requestsCompleted"].as_u64().unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn results_are_published_persistent_and_metrics_transient() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+        let (mut executor, published) = executor(message(json!({ "targetUrl": server.url("/") })));
+        // The defaults, as PERSISTENT_RESULTS and PERSISTENT_METRICS would set them
+        executor.persistent_results = true;
+        executor.persistent_metrics = false;
+        let mut progress = Progress::new(CancellationToken::new(), Duration::ZERO);
+
+        executor
+            .emit_metrics(&Mutex::new(Statistics::new()), &mut progress, 1)
+            .await;
+        executor.execute().await.unwrap();
+
+        let published = published.lock().unwrap();
+        let delivery_mode = |queue: &str| {
+            let message = published.iter().rev().find(|p| p.queue == queue).unwrap();
+            *message.properties.delivery_mode()
+        };
+        assert_eq!(delivery_mode("results"), Some(PERSISTENT_DELIVERY_MODE));
+        assert_eq!(delivery_mode("metrics"), None);
+    }
+
+    #[tokio::test]
+    async fn time_weighted_latency_counts_quiet_seconds_as_much_as_busy_ones() {
+        let (executor, _) = executor(message(json!({})));
+        let mut progress = Progress::new(CancellationToken::new(), Duration::ZERO);
+        let stats = Mutex::new(Statistics::new());
+
+        // A busy second of fast responses, then a quiet second of slow ones
+        for (requests, latency) in [(90, 10), (10, 100)] {
+            {
+                let mut stats = stats.lock().await;
+                for _ in 0..requests {
+                    stats.record_success("GET /", latency, 5, 200);
+                }
+            }
+            progress.last_point = Instant::now() - Duration::from_secs(1);
+            executor.emit_metrics(&stats, &mut progress, 1).await;
+        }
+
+        let result = executor.build_result(&*stats.lock().await, &progress, false);
+        // (90 x 10 + 10 x 100) / 100 requests, against (10 + 100) / 2 seconds
+        assert!((result.average_response_time - 19.0).abs() < 0.5);
+        let time_weighted = result.time_weighted_avg_latency.unwrap();
+        assert!((time_weighted - 55.0).abs() < 1.0, "{}", time_weighted);
+    }
+
+    #[tokio::test]
+    async fn each_endpoint_is_held_to_its_own_timeout() {
+        let server =
+            MockServer::start(|_| Reply::ok().head_delay(Duration::from_millis(200))).await;
+
+        // The report gets longer than the test's 100ms, the health check less
+        let result = run(message(json!({
+            "totalRequests": 10,
+            "concurrentUsers": 5,
+            "seed": 3,
+            "requestTimeoutMs": 100,
+            "endpoints": [
+                { "name": "report", "url": server.url("/report"), "weight": 1, "requestTimeoutMs": 1000 },
+                { "name": "health", "url": server.url("/health"), "weight": 1, "requestTimeoutMs": 50 },
+            ],
+        })))
+        .await;
+
+        let (report, health) = (
+            &result["endpointResults"]["report"],
+            &result["endpointResults"]["health"],
+        );
+        assert!(report["requests"].as_u64().unwrap() > 0);
+        assert_eq!(report["passed"], report["requests"]);
+        assert_eq!(report["timeouts"], 0);
+        assert!(health["requests"].as_u64().unwrap() > 0);
+        assert_eq!(health["timeouts"], health["requests"]);
+    }
+
+    #[tokio::test]
+    async fn requests_follow_the_endpoint_weights() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+
+        let result = run(message(json!({
+            "totalRequests": 400,
+            "concurrentUsers": 10,
+            "seed": 21,
+            "endpoints": [
+                {
+                    "name": "browse",
+                    "url": server.url("/browse"),
+                    "weight": 3,
+                    "headers": { "X-Endpoint": "browse" },
+                },
+                {
+                    "name": "buy",
+                    "url": server.url("/buy"),
+                    "method": "POST",
+                    "weight": 1,
+                    "body": { "sku": 7 },
+                },
+            ],
+        })))
+        .await;
+
+        let received = server.received();
+        let browse = received.iter().filter(|r| r.path == "/browse").count();
+        let share = browse as f64 / received.len() as f64;
+        assert!((share - 0.75).abs() < 0.07, "browse share {}", share);
+        for request in &received {
+            match request.path.as_str() {
+                "/browse" => assert_eq!(request.header("x-endpoint"), Some("browse")),
+                _ => {
+                    assert_eq!(request.method, "POST");
+                    assert_eq!(request.body_text(), r#"{"sku":7}"#);
+                }
+            }
+        }
+        let endpoints = &result["endpointResults"];
+        assert_eq!(endpoints["browse"]["requests"], browse as u64);
+        assert!(endpoints["buy"]["p50ResponseTime"].is_number());
+    }
+
+    #[tokio::test]
+    async fn alternating_failures_flip_often_and_an_outage_flips_twice() {
+        // The nth request fails when `fails(n)`
+        async fn failing_server(fails: fn(u32) -> bool) -> MockServer {
+            let served = AtomicU32::new(0);
+            MockServer::start(
+                move |_| match fails(served.fetch_add(1, Ordering::SeqCst)) {
+                    true => Reply::status(503),
+                    false => Reply::ok(),
+                },
+            )
+            .await
+        }
+        let run_against = |server: &MockServer| {
+            run(message(json!({
+                "targetUrl": server.url("/"),
+                "totalRequests": 40,
+                "expectedStatusCodes": [200],
+            })))
+        };
+
+        let flaky = failing_server(|n| n % 2 == 1).await;
+        let outage = failing_server(|n| (10..30).contains(&n)).await;
+        let flaky = run_against(&flaky).await;
+        let outage = run_against(&outage).await;
+
+        // The same 50% error rate either way
+        assert_eq!(flaky["failedRequests"], 20);
+        assert_eq!(outage["failedRequests"], 20);
+        assert_eq!(flaky["outcomeTransitions"], 39);
+        assert_eq!(outage["outcomeTransitions"], 2);
+        assert!(flaky["flakinessScore"].as_f64().unwrap() > 1.5);
+        assert!(outage["flakinessScore"].as_f64().unwrap() < 0.2);
+    }
+
+    #[tokio::test]
+    async fn a_step_uses_the_token_the_step_before_it_extracted() {
+        // Every login hands out a fresh token
+        let issued = AtomicU32::new(0);
+        let server = MockServer::start(move |request| match request.path.as_str() {
+            "/login" => {
+                let token = issued.fetch_add(1, Ordering::SeqCst);
+                Reply::ok()
+                    .body(json!({ "auth": { "token": format!("tok-{}", token) } }).to_string())
+            }
+            _ => Reply::ok(),
+        })
+        .await;
+
+        let result = run(message(json!({
+            "concurrentUsers": 2,
+            "iterationsPerUser": 3,
+            "steps": [
+                {
+                    "name": "login",
+                    "url": server.url("/login"),
+                    "method": "POST",
+                    "extract": { "token": "$.auth.token" },
+                },
+                {
+                    "name": "orders",
+                    "url": server.url("/orders"),
+                    "headers": { "Authorization": "Bearer {{token}}" },
+                },
+            ],
+        })))
+        .await;
+
+        let mut tokens: Vec<String> = server
+            .received()
+            .iter()
+            .filter(|r| r.path == "/orders")
+            .map(|r| r.header("authorization").unwrap().to_string())
+            .collect();
+        tokens.sort();
+        let expected: Vec<String> = (0..6).map(|n| format!("Bearer tok-{}", n)).collect();
+        assert_eq!(tokens, expected);
+        assert_eq!(result["endpointResults"]["login"]["passed"], 6);
+        assert_eq!(result["endpointResults"]["orders"]["passed"], 6);
+    }
+
+    #[tokio::test]
+    async fn a_user_pauses_within_the_think_time_between_requests() {
+        for model in ["taskPerRequest", "userLoop"] {
+            let arrivals = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let recorded = arrivals.clone();
+            let server = MockServer::start(move |_| {
+                recorded.lock().unwrap().push(Instant::now());
+                Reply::ok()
+            })
+            .await;
+
+            let result = run(message(json!({
+                "targetUrl": server.url("/"),
+                "totalRequests": 4,
+                "concurrencyModel": model,
+                "thinkTimeMs": { "min": 100, "max": 200 },
+            })))
+            .await;
+
+            assert_eq!(result["successfulRequests"], 4, "{}", model);
+            let arrivals = arrivals.lock().unwrap();
+            for gap in arrivals.windows(2).map(|pair| pair[1] - pair[0]) {
+                // The think time plus a local round trip
+                assert!(gap >= Duration::from_millis(100), "{}: {:?}", model, gap);
+                assert!(gap < Duration::from_millis(300), "{}: {:?}", model, gap);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn the_capacity_probe_estimates_a_rate_limited_target() {
+        // A token bucket serving 50 requests a second, turning away the rest
+        const LIMIT: f64 = 50.0;
+        let bucket = std::sync::Mutex::new((Instant::now(), 5.0));
+        let server = MockServer::start(move |_| {
+            let mut bucket = bucket.lock().unwrap();
+            let (refilled, tokens) = &mut *bucket;
+            *tokens = (*tokens + refilled.elapsed().as_secs_f64() * LIMIT).min(5.0);
+            *refilled = Instant::now();
+            if *tokens < 1.0 {
+                return Reply::status(429);
+            }
+            *tokens -= 1.0;
+            Reply::ok()
+        })
+        .await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "concurrentUsers": 20,
+            "expectedStatusCodes": [200],
+            "capacityProbe": { "startRps": 12, "maxRps": 200, "stepSeconds": 1 },
+        })))
+        .await;
+
+        // 12, 24 and 48 RPS are sustained; 96 isn't
+        let estimate = result["capacityEstimateRps"].as_f64().unwrap();
+        assert!((40.0..=LIMIT * 1.1).contains(&estimate), "{}", estimate);
+    }
+
+    #[tokio::test]
+    async fn a_failing_target_trips_the_error_rate_breaker() {
+        let server = MockServer::start(|_| Reply::status(500)).await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "totalRequests": 500,
+            "requestsPerSecond": 50,
+            "mode": "openArrivalRate",
+            "expectedStatusCodes": [200],
+            "abortOnErrorRate": 50.0,
+            "abortMinSamples": 20,
+        })))
+        .await;
+
+        assert_eq!(result["status"], "aborted");
+        assert_eq!(result["abortReason"], "error_rate_exceeded");
+        let sent = result["totalRequests"].as_u64().unwrap();
+        assert!((20..500).contains(&sent), "{}", sent);
+        assert!(server.received().len() < 500);
+    }
+
+    #[tokio::test]
+    async fn user_loop_runs_one_persistent_task_per_user() {
+        // A request without a session is a task's first; the session it's handed stays
+        // with that task, so sessions count the tasks
+        let sessions = AtomicUsize::new(0);
+        let server = MockServer::start(move |request| match request.header("x-session") {
+            Some(_) => Reply::ok(),
+            None => {
+                let session = sessions.fetch_add(1, Ordering::Relaxed);
+                Reply::ok().header("x-session", &session.to_string())
+            }
+        })
+        .await;
+
+        // One identity more than there are users: a task per user binds one each
+        let message = message(json!({
+            "targetUrl": server.url("/users/{{user}}"),
+            "concurrentUsers": 4,
+            "totalRequests": 20,
+            "requestsPerSecond": 40,
+            "concurrencyModel": "userLoop",
+            "stickySession": { "headerName": "x-session" },
+            "virtualUsers": [
+                { "user": "0" },
+                { "user": "1" },
+                { "user": "2" },
+                { "user": "3" },
+                { "user": "4" },
+            ],
+        }));
+        message.validate().unwrap();
+        let result = run(message).await;
+
+        assert_eq!(result["totalRequests"], 20);
+        let received = server.received();
+        let first_requests = received.iter().filter(|r| r.header("x-session").is_none());
+        assert_eq!(first_requests.count(), 4);
+
+        // Every later request came from one of those 4 tasks, each keeping its identity
+        let mut tasks: HashMap<&str, Vec<&str>> = HashMap::new();
+        for received in &received {
+            if let Some(session) = received.header("x-session") {
+                tasks.entry(session).or_default().push(&received.path);
+            }
+        }
+        assert_eq!(tasks.len(), 4, "{:?}", tasks);
+        let mut users: Vec<_> = tasks.values().map(|paths| paths[0]).collect();
+        users.sort();
+        assert_eq!(users, ["/users/0", "/users/1", "/users/2", "/users/3"]);
+        for paths in tasks.values() {
+            assert!(paths.iter().all(|path| *path == paths[0]), "{:?}", paths);
+        }
+    }
+
+    #[tokio::test]
+    async fn each_phase_has_percentiles_of_its_own_samples() {
+        // Fast for the first second, slow after
+        let first = std::sync::OnceLock::new();
+        let server = MockServer::start(move |_| {
+            let first = first.get_or_init(Instant::now);
+            match first.elapsed() < Duration::from_secs(1) {
+                true => Reply::ok(),
+                false => Reply::ok().head_delay(Duration::from_millis(150)),
+            }
+        })
+        .await;
+
+        let result = run(message(json!({
+            "targetUrl": server.url("/"),
+            "totalRequests": 0,
+            "durationSeconds": 2,
+            "concurrentUsers": 10,
+            "rateSchedule": [
+                { "durationSeconds": 1, "rps": 20 },
+                { "durationSeconds": 1, "rps": 20 },
+            ],
+            "resetHistogramPerPhase": true,
+        })))
+        .await;
+
+        let segments = result["segmentResults"].as_array().unwrap();
+        let (fast, slow) = (&segments[0]["phase"], &segments[1]["phase"]);
+        assert!(fast["requests"].as_u64().unwrap() > 0);
+        assert!(slow["requests"].as_u64().unwrap() > 0);
+        assert!(
+            fast["p99ResponseTime"].as_f64().unwrap() < 100.0,
+            "{}",
+            fast
+        );
+        assert!(
+            slow["p50ResponseTime"].as_f64().unwrap() >= 150.0,
+            "{}",
+            slow
+        );
+        // Every request of the run falls in exactly one phase
+        let requests = result["totalRequests"].as_u64().unwrap();
+        let phases = fast["requests"].as_u64().unwrap() + slow["requests"].as_u64().unwrap();
+        assert_eq!(requests, phases);
+    }
+
+    #[tokio::test]
+    async fn schedule_error_is_low_when_kept_and_high_when_the_rate_is_out_of_reach() {
+        let fast = MockServer::start(|_| Reply::ok()).await;
+        let slow = MockServer::start(|_| Reply::ok().head_delay(Duration::from_millis(50))).await;
+        let open = |server: &MockServer, rps: u32, users: u32| {
+            message(json!({
+                "targetUrl": server.url("/"),
+                "totalRequests": 20,
+                "requestsPerSecond": rps,
+                "concurrentUsers": users,
+                "mode": "openArrivalRate",
+            }))
+        };
+
+        let kept = run(open(&fast, 20, 10)).await;
+        // One user at 50ms a request can't send 200 a second
+        let missed = run(open(&slow, 200, 1)).await;
+
+        let (kept, missed) = (
+            kept["scheduleErrorP95"].as_f64().unwrap(),
+            missed["scheduleErrorP95"].as_f64().unwrap(),
+        );
+        assert!(kept < 20.0, "{}", kept);
+        assert!(missed > 200.0, "{}", missed);
+    }
+}
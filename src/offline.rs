@@ -0,0 +1,174 @@
+// `loadmaster-worker run <message.json|-> [result.json]`: runs one load test without a
+// broker and writes its final result as JSON to the file, or stdout. The exit code is 0
+// when the test completed and 2 when it was aborted (failed preflight or setup, error
+// rate or throughput thresholds, ...), so CI can gate on it; 1, like any error, when it
+// couldn't run at all.
+use crate::load_test::{LoadTestExecutor, Outbox, Queues};
+use crate::types::{LoadTestMessage, ResultEncoding};
+use anyhow::{anyhow, bail, Context, Result};
+use futures::future::BoxFuture;
+use lapin::BasicProperties;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex};
+
+pub const EXIT_COMPLETED: i32 = 0;
+pub const EXIT_ABORTED: i32 = 2;
+
+// `args` are what follows `run`: the message file ("-" for stdin) and an optional
+// result file
+pub async fn run(args: &[String]) -> Result<i32> {
+    let Some(source) = args.first() else {
+        bail!("usage: loadmaster-worker run <message.json|-> [result.json]");
+    };
+
+    let raw = if source == "-" {
+        let mut raw = String::new();
+        io::stdin().read_to_string(&mut raw)?;
+        raw
+    } else {
+        fs::read_to_string(source).with_context(|| format!("reading {}", source))?
+    };
+
+    let mut message: LoadTestMessage = serde_json::from_str(&raw)?;
+    message.validate()?;
+    // Written out for people and scripts, whatever a queue consumer would want
+    message.result_encoding = Some(ResultEncoding::Json);
+
+    let result = execute(message).await?;
+    let code = match result["status"].as_str() {
+        Some("completed") => EXIT_COMPLETED,
+        _ => EXIT_ABORTED,
+    };
+
+    let written = serde_json::to_string_pretty(&result)?;
+    match args.get(1) {
+        Some(path) => fs::write(path, written).with_context(|| format!("writing {}", path))?,
+        None => println!("{}", written),
+    }
+
+    Ok(code)
+}
+
+// Keeps the last message published to `queue`, dropping everything else
+struct LastResult {
+    queue: String,
+    payload: Mutex<Option<Vec<u8>>>,
+}
+
+impl Outbox for LastResult {
+    fn publish<'a>(
+        &'a self,
+        queue: &'a str,
+        payload: &'a [u8],
+        _properties: BasicProperties,
+    ) -> BoxFuture<'a, lapin::Result<()>> {
+        if queue == self.queue {
+            *self.payload.lock().unwrap() = Some(payload.to_vec());
+        }
+        Box::pin(async { Ok(()) })
+    }
+}
+
+// Runs the test and returns its final result
+async fn execute(message: LoadTestMessage) -> Result<Value> {
+    let queues = Queues {
+        results: "test_results".to_string(),
+        metrics: "test_metrics".to_string(),
+        plans: "test_plans".to_string(),
+        traces: "test_traces".to_string(),
+        progress: "test_progress".to_string(),
+    };
+    // Checkpoints go to the same queue, ahead of the final result
+    let outbox = Arc::new(LastResult {
+        queue: queues.results.clone(),
+        payload: Mutex::new(None),
+    });
+
+    LoadTestExecutor::new(message, outbox.clone(), queues, HashMap::new())
+        .execute()
+        .await?;
+
+    let result = outbox.payload.lock().unwrap().take();
+    let result = result.ok_or_else(|| anyhow!("the test published no result"))?;
+    Ok(serde_json::from_slice(&result)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockServer, Reply};
+    use crate::types::TestStatus;
+    use serde_json::json;
+    use std::env;
+
+    // Runs `message` offline and returns the exit code and the result written
+    async fn run_offline(name: &str, message: Value) -> (i32, Value) {
+        let directory = env::temp_dir();
+        let source = directory.join(format!("loadmaster-{}-{}.json", name, std::process::id()));
+        let output = source.with_extension("result.json");
+        fs::write(&source, message.to_string()).unwrap();
+
+        let args = [source.display().to_string(), output.display().to_string()];
+        let code = run(&args).await.unwrap();
+        let result = serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+
+        fs::remove_file(source).ok();
+        fs::remove_file(output).ok();
+        (code, result)
+    }
+
+    fn message(target: &str, fields: Value) -> Value {
+        let mut message = json!({
+            "testId": "offline",
+            "targetUrl": target,
+            "method": "GET",
+            "concurrentUsers": 1,
+            "totalRequests": 3,
+            "durationSeconds": 0,
+            "requestsPerSecond": 0,
+        });
+        if let (Some(message), Value::Object(fields)) = (message.as_object_mut(), fields) {
+            message.extend(fields);
+        }
+        message
+    }
+
+    #[tokio::test]
+    async fn completed_test_exits_zero() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+
+        let (code, result) = run_offline("completed", message(&server.url("/"), json!({}))).await;
+
+        assert_eq!(code, EXIT_COMPLETED);
+        assert_eq!(result["status"], TestStatus::Completed.as_str());
+        assert_eq!(result["successfulRequests"], 3);
+    }
+
+    #[tokio::test]
+    async fn failing_test_exits_non_zero() {
+        let server = MockServer::start(|_| Reply::status(503)).await;
+        let failing = message(
+            &server.url("/"),
+            json!({ "preflight": { "url": server.url("/health") } }),
+        );
+
+        let (code, result) = run_offline("failing", failing).await;
+
+        assert_eq!(code, EXIT_ABORTED);
+        assert_eq!(result["status"], TestStatus::Aborted.as_str());
+        assert_eq!(result["abortReason"], "preflight_failed");
+    }
+
+    #[tokio::test]
+    async fn invalid_message_is_an_error() {
+        let path = env::temp_dir().join(format!("loadmaster-invalid-{}.json", std::process::id()));
+        let unbounded = message("http://127.0.0.1:9/", json!({ "totalRequests": 0 }));
+        fs::write(&path, unbounded.to_string()).unwrap();
+
+        assert!(run(&[path.display().to_string()]).await.is_err());
+        fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,125 @@
+use crate::types::{BodySizeDistribution, BodySizeKind, ThinkTimeConfig};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::Duration;
+
+// RNG for one independent stream of decisions (the request loop, a virtual user, ...).
+// With a seed, every stream is reproducible across runs; without one it's drawn from entropy.
+pub fn seeded_rng(seed: Option<u64>, stream: u64) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed ^ stream.wrapping_mul(0x9E37_79B9_7F4A_7C15)),
+        None => StdRng::from_entropy(),
+    }
+}
+
+// Draws a Poisson-process inter-arrival gap: exponentially distributed with mean 1/rate seconds
+pub fn exponential_interval(rng: &mut StdRng, rate: f64) -> Duration {
+    let uniform: f64 = rng.gen();
+    Duration::from_secs_f64(-(1.0 - uniform).ln() / rate)
+}
+
+// Draws a request body size in bytes, clamped to the configured range
+pub fn sample_body_size(config: &BodySizeDistribution, rng: &mut StdRng) -> usize {
+    let size = match config.distribution {
+        BodySizeKind::Uniform => rng.gen_range(config.min..=config.max) as f64,
+        BodySizeKind::Lognormal => {
+            let sigma = config.sigma.unwrap_or(1.0);
+            // Defaults to the geometric midpoint of the range
+            let mean = config
+                .mean
+                .map(|mean| mean as f64)
+                .unwrap_or_else(|| ((config.min.max(1) * config.max.max(1)) as f64).sqrt());
+            let mu = mean.max(1.0).ln() - sigma * sigma / 2.0;
+            (mu + sigma * standard_normal(rng)).exp()
+        }
+    };
+
+    (size.round() as usize).clamp(config.min, config.max)
+}
+
+// Draws a pause between a virtual user's requests, uniformly within the configured range
+pub fn sample_think_time(config: &ThinkTimeConfig, rng: &mut StdRng) -> Duration {
+    Duration::from_millis(rng.gen_range(config.min..=config.max))
+}
+
+// Box-Muller transform
+fn standard_normal(rng: &mut StdRng) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exponential_intervals_average_one_over_the_rate() {
+        let mut rng = seeded_rng(Some(42), 0);
+        let rate = 50.0;
+        let samples = 20_000;
+
+        let gaps: Vec<f64> = (0..samples)
+            .map(|_| exponential_interval(&mut rng, rate).as_secs_f64())
+            .collect();
+        let mean = gaps.iter().sum::<f64>() / samples as f64;
+        assert!((mean - 1.0 / rate).abs() < 0.02 / rate, "mean gap {}", mean);
+
+        // Exponential: about 1/e of the gaps are longer than the mean
+        let long = gaps.iter().filter(|&&gap| gap > 1.0 / rate).count() as f64;
+        let share = long / samples as f64;
+        assert!(
+            (share - (-1.0f64).exp()).abs() < 0.02,
+            "{} over the mean",
+            share
+        );
+    }
+
+    fn sizes(config: serde_json::Value) -> Vec<usize> {
+        let config: BodySizeDistribution = serde_json::from_value(config).unwrap();
+        let mut rng = seeded_rng(Some(42), 0);
+        let mut sizes: Vec<usize> = (0..20_000)
+            .map(|_| sample_body_size(&config, &mut rng))
+            .collect();
+        sizes.sort();
+        sizes
+    }
+
+    fn mean(sizes: &[usize]) -> f64 {
+        sizes.iter().sum::<usize>() as f64 / sizes.len() as f64
+    }
+
+    #[test]
+    fn lognormal_body_sizes_center_on_the_mean_with_a_long_tail() {
+        let sizes = sizes(serde_json::json!({
+            "distribution": "lognormal",
+            "min": 100,
+            "max": 100_000,
+            "mean": 2000,
+            "sigma": 0.5,
+        }));
+
+        assert!(sizes[0] >= 100 && sizes[sizes.len() - 1] <= 100_000);
+        let mean = mean(&sizes);
+        assert!((mean - 2000.0).abs() < 50.0, "mean {}", mean);
+        // Skewed right: the median sits at exp(mu), below the mean
+        let median = sizes[sizes.len() / 2] as f64;
+        let expected_median = 2000.0 * (-0.125f64).exp();
+        assert!((median - expected_median).abs() < 50.0, "median {}", median);
+    }
+
+    #[test]
+    fn uniform_body_sizes_spread_evenly_over_the_range() {
+        let sizes = sizes(serde_json::json!({ "min": 1000, "max": 3000 }));
+
+        assert_eq!((sizes[0], sizes[sizes.len() - 1]), (1000, 3000));
+        let mean = mean(&sizes);
+        assert!((mean - 2000.0).abs() < 20.0, "mean {}", mean);
+        let quartile = sizes[sizes.len() / 4] as f64;
+        assert!(
+            (quartile - 1500.0).abs() < 30.0,
+            "first quartile {}",
+            quartile
+        );
+    }
+}
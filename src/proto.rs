@@ -0,0 +1,815 @@
+// Protobuf mirrors of the published payloads, matching proto/loadmaster.proto
+use crate::types;
+use std::collections::HashMap;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TestResult {
+    #[prost(string, tag = "1")]
+    pub test_id: String,
+    #[prost(bool, tag = "2")]
+    pub partial: bool,
+    #[prost(string, tag = "3")]
+    pub status: String,
+    #[prost(string, optional, tag = "4")]
+    pub abort_reason: Option<String>,
+    #[prost(uint32, tag = "5")]
+    pub total_requests: u32,
+    #[prost(uint32, tag = "6")]
+    pub successful_requests: u32,
+    #[prost(uint32, tag = "7")]
+    pub failed_requests: u32,
+    #[prost(double, tag = "8")]
+    pub average_response_time: f64,
+    #[prost(double, tag = "9")]
+    pub min_response_time: f64,
+    #[prost(double, tag = "10")]
+    pub max_response_time: f64,
+    #[prost(double, tag = "11")]
+    pub p50_response_time: f64,
+    #[prost(double, tag = "12")]
+    pub p95_response_time: f64,
+    #[prost(double, tag = "13")]
+    pub p99_response_time: f64,
+    #[prost(double, tag = "14")]
+    pub queue_time_p95: f64,
+    #[prost(uint32, tag = "15")]
+    pub effective_concurrency: u32,
+    #[prost(string, tag = "16")]
+    pub concurrency_model: String,
+    #[prost(double, tag = "17")]
+    pub requests_per_second: f64,
+    #[prost(double, tag = "18")]
+    pub error_rate: f64,
+    #[prost(map = "uint32, uint32", tag = "19")]
+    pub status_code_distribution: HashMap<u32, u32>,
+    #[prost(map = "string, message", tag = "84")]
+    pub latency_by_tls_version: HashMap<String, TlsLatency>,
+    #[prost(map = "string, uint32", tag = "20")]
+    pub error_distribution: HashMap<String, u32>,
+    #[prost(uint32, tag = "21")]
+    pub fault_injected_requests: u32,
+    #[prost(uint32, tag = "22")]
+    pub fault_injected_failures: u32,
+    #[prost(map = "string, uint32", tag = "23")]
+    pub fault_distribution: HashMap<String, u32>,
+    #[prost(map = "string, uint32", tag = "24")]
+    pub backend_distribution: HashMap<String, u32>,
+    #[prost(uint32, tag = "25")]
+    pub sticky_violations: u32,
+    #[prost(uint32, tag = "26")]
+    pub validation_failures: u32,
+    #[prost(uint32, tag = "27")]
+    pub validation_webhook_errors: u32,
+    #[prost(uint32, tag = "28")]
+    pub total_retries: u32,
+    #[prost(message, repeated, tag = "29")]
+    pub tail_composition: Vec<TailBucket>,
+    #[prost(double, optional, tag = "30")]
+    pub time_to_steady_state_seconds: Option<f64>,
+    #[prost(double, optional, tag = "31")]
+    pub ramp_stability_score: Option<f64>,
+    #[prost(message, repeated, tag = "32")]
+    pub time_series_data: Vec<TimeSeriesPoint>,
+    #[prost(string, tag = "33")]
+    pub worker_version: String,
+    #[prost(string, repeated, tag = "34")]
+    pub capabilities: Vec<String>,
+    #[prost(uint32, tag = "35")]
+    pub dropped_metrics: u32,
+    #[prost(map = "string, message", tag = "36")]
+    pub endpoint_results: HashMap<String, EndpointResult>,
+    #[prost(message, optional, tag = "37")]
+    pub worst_interval: Option<WorstInterval>,
+    #[prost(message, optional, tag = "38")]
+    pub latency_digest: Option<LatencyDigest>,
+    #[prost(double, tag = "39")]
+    pub dns_time_p95: f64,
+    #[prost(double, optional, tag = "40")]
+    pub error_recovery_seconds: Option<f64>,
+    #[prost(message, optional, tag = "41")]
+    pub body_size_summary: Option<BodySizeSummary>,
+    #[prost(string, optional, tag = "42")]
+    pub latency_histogram: Option<String>,
+    #[prost(map = "string, message", tag = "43")]
+    pub group_results: HashMap<String, GroupResult>,
+    #[prost(message, optional, tag = "44")]
+    pub sample_adequacy: Option<SampleAdequacy>,
+    #[prost(double, tag = "45")]
+    pub goodput_rps: f64,
+    #[prost(uint64, tag = "46")]
+    pub connections_established: u64,
+    #[prost(string, repeated, tag = "47")]
+    pub warnings: Vec<String>,
+    #[prost(message, optional, tag = "48")]
+    pub sse: Option<SseSummary>,
+    #[prost(double, tag = "49")]
+    pub pool_saturation_percent: f64,
+    #[prost(double, tag = "50")]
+    pub achieved_concurrency: f64,
+    #[prost(map = "string, message", tag = "51")]
+    pub breakdown_by_dimension: HashMap<String, DimensionValues>,
+    #[prost(string, optional, tag = "52")]
+    pub region: Option<String>,
+    #[prost(bool, tag = "53")]
+    pub cancelled: bool,
+    #[prost(uint32, tag = "54")]
+    pub cache_hits: u32,
+    #[prost(double, optional, tag = "55")]
+    pub cache_hit_rate: Option<f64>,
+    #[prost(double, optional, tag = "56")]
+    pub avg_request_bytes: Option<f64>,
+    #[prost(double, optional, tag = "57")]
+    pub avg_response_bytes: Option<f64>,
+    #[prost(double, optional, tag = "58")]
+    pub bytes_per_successful_request: Option<f64>,
+    #[prost(uint64, tag = "59")]
+    pub total_bytes: u64,
+    #[prost(double, tag = "60")]
+    pub throughput_bytes_per_second: f64,
+    #[prost(double, tag = "61")]
+    pub ttfb_p50: f64,
+    #[prost(double, tag = "62")]
+    pub ttfb_p95: f64,
+    #[prost(double, tag = "63")]
+    pub ttfb_p99: f64,
+    #[prost(double, optional, tag = "64")]
+    pub recovered_within_seconds: Option<f64>,
+    #[prost(bool, tag = "65")]
+    pub recovery_failed: bool,
+    #[prost(message, optional, tag = "66")]
+    pub in_flight_distribution: Option<InFlightDistribution>,
+    #[prost(bool, tag = "67")]
+    pub cache_busted: bool,
+    #[prost(uint64, optional, tag = "68")]
+    pub cold_start_ms: Option<u64>,
+    #[prost(uint32, tag = "69")]
+    pub warmup_requests: u32,
+    #[prost(message, repeated, tag = "70")]
+    pub segment_results: Vec<SegmentResult>,
+    #[prost(double, optional, tag = "71")]
+    pub time_weighted_avg_latency: Option<f64>,
+    #[prost(double, optional, tag = "72")]
+    pub global_target_rps: Option<f64>,
+    #[prost(double, optional, tag = "73")]
+    pub aggregate_rps: Option<f64>,
+    #[prost(uint32, tag = "74")]
+    pub outcome_transitions: u32,
+    #[prost(double, optional, tag = "75")]
+    pub flakiness_score: Option<f64>,
+    #[prost(double, optional, tag = "76")]
+    pub compressed_response_fraction: Option<f64>,
+    #[prost(double, optional, tag = "77")]
+    pub capacity_estimate_rps: Option<f64>,
+    #[prost(double, optional, tag = "78")]
+    pub network_error_fraction: Option<f64>,
+    #[prost(map = "uint32, message", tag = "79")]
+    pub status_code_latencies: HashMap<u32, StatusCodeLatency>,
+    #[prost(uint64, tag = "80")]
+    pub decompressed_bytes: u64,
+    #[prost(double, optional, tag = "81")]
+    pub schedule_error_p50: Option<f64>,
+    #[prost(double, optional, tag = "82")]
+    pub schedule_error_p95: Option<f64>,
+    #[prost(double, optional, tag = "83")]
+    pub schedule_error_p99: Option<f64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DimensionValues {
+    #[prost(map = "string, message", tag = "1")]
+    pub values: HashMap<String, DimensionBreakdown>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DimensionBreakdown {
+    #[prost(uint32, tag = "1")]
+    pub requests: u32,
+    #[prost(uint32, tag = "2")]
+    pub successful: u32,
+    #[prost(uint32, tag = "3")]
+    pub failed: u32,
+    #[prost(double, tag = "4")]
+    pub average_response_time: f64,
+    #[prost(double, tag = "5")]
+    pub p50_response_time: f64,
+    #[prost(double, tag = "6")]
+    pub p95_response_time: f64,
+    #[prost(double, tag = "7")]
+    pub p99_response_time: f64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StatusCodeLatency {
+    #[prost(uint64, tag = "1")]
+    pub count: u64,
+    #[prost(double, tag = "2")]
+    pub average_response_time: f64,
+    #[prost(double, tag = "3")]
+    pub p50_response_time: f64,
+    #[prost(double, tag = "4")]
+    pub p95_response_time: f64,
+    #[prost(double, tag = "5")]
+    pub p99_response_time: f64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SseSummary {
+    #[prost(uint32, tag = "1")]
+    pub connections: u32,
+    #[prost(uint64, tag = "2")]
+    pub events_received: u64,
+    #[prost(double, tag = "3")]
+    pub event_rate: f64,
+    #[prost(double, tag = "4")]
+    pub inter_event_p50: f64,
+    #[prost(double, tag = "5")]
+    pub inter_event_p95: f64,
+    #[prost(double, tag = "6")]
+    pub inter_event_p99: f64,
+    #[prost(message, repeated, tag = "7")]
+    pub per_connection: Vec<SseConnectionSummary>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SseConnectionSummary {
+    #[prost(uint32, tag = "1")]
+    pub connection: u32,
+    #[prost(uint64, tag = "2")]
+    pub events: u64,
+    #[prost(double, tag = "3")]
+    pub event_rate: f64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SampleAdequacy {
+    #[prost(uint64, tag = "1")]
+    pub samples: u64,
+    #[prost(uint64, tag = "2")]
+    pub suggested_minimum_samples: u64,
+    #[prost(message, repeated, tag = "3")]
+    pub percentiles: Vec<PercentileConfidence>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PercentileConfidence {
+    #[prost(double, tag = "1")]
+    pub percentile: f64,
+    #[prost(uint64, tag = "2")]
+    pub minimum_samples: u64,
+    #[prost(bool, tag = "3")]
+    pub reliable: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TlsLatency {
+    #[prost(uint64, tag = "1")]
+    pub count: u64,
+    #[prost(double, tag = "2")]
+    pub average_response_time: f64,
+    #[prost(double, tag = "3")]
+    pub p50_response_time: f64,
+    #[prost(double, tag = "4")]
+    pub p95_response_time: f64,
+    #[prost(double, tag = "5")]
+    pub p99_response_time: f64,
+    #[prost(string, repeated, tag = "6")]
+    pub cipher_suites: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GroupResult {
+    #[prost(uint32, tag = "1")]
+    pub concurrency: u32,
+    #[prost(uint32, tag = "2")]
+    pub peak_in_flight: u32,
+    #[prost(uint32, tag = "3")]
+    pub requests: u32,
+    #[prost(uint32, tag = "4")]
+    pub successful: u32,
+    #[prost(uint32, tag = "5")]
+    pub failed: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct InFlightDistribution {
+    #[prost(uint64, tag = "1")]
+    pub samples: u64,
+    #[prost(double, tag = "2")]
+    pub mean: f64,
+    #[prost(uint64, tag = "3")]
+    pub p50: u64,
+    #[prost(uint64, tag = "4")]
+    pub p90: u64,
+    #[prost(uint64, tag = "5")]
+    pub p99: u64,
+    #[prost(uint64, tag = "6")]
+    pub max: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BodySizeSummary {
+    #[prost(uint64, tag = "1")]
+    pub count: u64,
+    #[prost(uint64, tag = "2")]
+    pub min: u64,
+    #[prost(uint64, tag = "3")]
+    pub max: u64,
+    #[prost(double, tag = "4")]
+    pub mean: f64,
+    #[prost(uint64, tag = "5")]
+    pub p50: u64,
+    #[prost(uint64, tag = "6")]
+    pub p95: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct LatencyDigest {
+    #[prost(double, tag = "1")]
+    pub compression: f64,
+    #[prost(double, tag = "2")]
+    pub count: f64,
+    #[prost(double, tag = "3")]
+    pub min: f64,
+    #[prost(double, tag = "4")]
+    pub max: f64,
+    #[prost(message, repeated, tag = "5")]
+    pub centroids: Vec<Centroid>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Centroid {
+    #[prost(double, tag = "1")]
+    pub mean: f64,
+    #[prost(double, tag = "2")]
+    pub weight: f64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WorstInterval {
+    #[prost(int64, tag = "1")]
+    pub timestamp: i64,
+    #[prost(double, tag = "2")]
+    pub p95: f64,
+    #[prost(double, tag = "3")]
+    pub error_rate: f64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EndpointResult {
+    #[prost(uint32, tag = "1")]
+    pub requests: u32,
+    #[prost(uint32, tag = "2")]
+    pub passed: u32,
+    #[prost(uint32, tag = "3")]
+    pub failed: u32,
+    #[prost(map = "uint32, uint32", tag = "4")]
+    pub status_code_distribution: HashMap<u32, u32>,
+    #[prost(uint32, tag = "5")]
+    pub timeouts: u32,
+    #[prost(double, tag = "6")]
+    pub average_response_time: f64,
+    #[prost(double, tag = "7")]
+    pub p50_response_time: f64,
+    #[prost(double, tag = "8")]
+    pub p95_response_time: f64,
+    #[prost(double, tag = "9")]
+    pub p99_response_time: f64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SegmentResult {
+    #[prost(double, tag = "1")]
+    pub start_seconds: f64,
+    #[prost(uint32, tag = "2")]
+    pub duration_seconds: u32,
+    #[prost(double, tag = "3")]
+    pub target_rps: f64,
+    #[prost(double, tag = "4")]
+    pub achieved_rps: f64,
+    #[prost(message, optional, tag = "5")]
+    pub phase: Option<PhaseSummary>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PhaseSummary {
+    #[prost(uint32, tag = "1")]
+    pub requests: u32,
+    #[prost(uint32, tag = "2")]
+    pub failed: u32,
+    #[prost(double, tag = "3")]
+    pub average_response_time: f64,
+    #[prost(double, tag = "4")]
+    pub p50_response_time: f64,
+    #[prost(double, tag = "5")]
+    pub p95_response_time: f64,
+    #[prost(double, tag = "6")]
+    pub p99_response_time: f64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TailBucket {
+    #[prost(double, tag = "1")]
+    pub percentile: f64,
+    #[prost(double, tag = "2")]
+    pub threshold_ms: f64,
+    #[prost(message, repeated, tag = "3")]
+    pub entries: Vec<TailEntry>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TailEntry {
+    #[prost(string, tag = "1")]
+    pub endpoint: String,
+    #[prost(uint32, tag = "2")]
+    pub status_code: u32,
+    #[prost(uint64, tag = "3")]
+    pub count: u64,
+    #[prost(double, tag = "4")]
+    pub share: f64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TimeSeriesPoint {
+    #[prost(int64, tag = "1")]
+    pub timestamp: i64,
+    #[prost(double, tag = "2")]
+    pub interval_seconds: f64,
+    #[prost(double, tag = "3")]
+    pub rps: f64,
+    #[prost(double, tag = "4")]
+    pub avg_response_time: f64,
+    #[prost(double, tag = "5")]
+    pub min_response_time: f64,
+    #[prost(double, tag = "6")]
+    pub max_response_time: f64,
+    #[prost(double, tag = "7")]
+    pub p95_response_time: f64,
+    #[prost(double, tag = "8")]
+    pub error_rate: f64,
+    #[prost(double, tag = "9")]
+    pub interval_rps: f64,
+    #[prost(double, optional, tag = "10")]
+    pub target_rps: Option<f64>,
+    #[prost(double, tag = "11")]
+    pub interval_error_rate: f64,
+    #[prost(double, tag = "12")]
+    pub goodput_rps: f64,
+    #[prost(bool, tag = "13")]
+    pub pool_saturated: bool,
+    #[prost(double, optional, tag = "14")]
+    pub schedule_lag_ms: Option<f64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Metric {
+    #[prost(string, tag = "1")]
+    pub test_id: String,
+    #[prost(string, tag = "2")]
+    pub timestamp: String,
+    #[prost(uint32, tag = "3")]
+    pub request_count: u32,
+    #[prost(uint32, tag = "4")]
+    pub success_count: u32,
+    #[prost(uint32, tag = "5")]
+    pub error_count: u32,
+    #[prost(double, tag = "6")]
+    pub avg_response_time: f64,
+    #[prost(uint32, optional, tag = "7")]
+    pub status_code: Option<u32>,
+    #[prost(string, optional, tag = "8")]
+    pub error_message: Option<String>,
+    #[prost(uint32, tag = "9")]
+    pub active_users: u32,
+    #[prost(string, optional, tag = "10")]
+    pub region: Option<String>,
+}
+
+impl From<&types::TestResult> for TestResult {
+    fn from(result: &types::TestResult) -> Self {
+        Self {
+            test_id: result.test_id.clone(),
+            partial: result.partial,
+            status: result.status.as_str().to_string(),
+            abort_reason: result
+                .abort_reason
+                .map(|reason| reason.as_str().to_string()),
+            total_requests: result.total_requests,
+            successful_requests: result.successful_requests,
+            failed_requests: result.failed_requests,
+            average_response_time: result.average_response_time,
+            min_response_time: result.min_response_time,
+            max_response_time: result.max_response_time,
+            p50_response_time: result.p50_response_time,
+            p95_response_time: result.p95_response_time,
+            p99_response_time: result.p99_response_time,
+            queue_time_p95: result.queue_time_p95,
+            dns_time_p95: result.dns_time_p95,
+            error_recovery_seconds: result.error_recovery_seconds,
+            body_size_summary: result
+                .body_size_summary
+                .as_ref()
+                .map(|sizes| BodySizeSummary {
+                    count: sizes.count,
+                    min: sizes.min,
+                    max: sizes.max,
+                    mean: sizes.mean,
+                    p50: sizes.p50,
+                    p95: sizes.p95,
+                }),
+            effective_concurrency: result.effective_concurrency,
+            concurrency_model: result.concurrency_model.clone(),
+            requests_per_second: result.requests_per_second,
+            error_rate: result.error_rate,
+            status_code_distribution: result
+                .status_code_distribution
+                .iter()
+                .map(|(code, count)| (*code as u32, *count))
+                .collect(),
+            latency_by_tls_version: result
+                .latency_by_tls_version
+                .iter()
+                .map(|(version, latency)| (version.clone(), latency.into()))
+                .collect(),
+            error_distribution: result.error_distribution.clone(),
+            fault_injected_requests: result.fault_injected_requests,
+            fault_injected_failures: result.fault_injected_failures,
+            fault_distribution: result.fault_distribution.clone(),
+            backend_distribution: result.backend_distribution.clone(),
+            sticky_violations: result.sticky_violations,
+            validation_failures: result.validation_failures,
+            validation_webhook_errors: result.validation_webhook_errors,
+            total_retries: result.total_retries,
+            tail_composition: result.tail_composition.iter().map(Into::into).collect(),
+            time_to_steady_state_seconds: result.time_to_steady_state_seconds,
+            ramp_stability_score: result.ramp_stability_score,
+            time_series_data: result.time_series_data.iter().map(Into::into).collect(),
+            worker_version: result.worker_version.clone(),
+            capabilities: result.capabilities.clone(),
+            dropped_metrics: result.dropped_metrics,
+            endpoint_results: result
+                .endpoint_results
+                .iter()
+                .map(|(name, endpoint)| (name.clone(), endpoint.into()))
+                .collect(),
+            group_results: result
+                .group_results
+                .iter()
+                .map(|(name, group)| {
+                    let group = GroupResult {
+                        concurrency: group.concurrency,
+                        peak_in_flight: group.peak_in_flight,
+                        requests: group.requests,
+                        successful: group.successful,
+                        failed: group.failed,
+                    };
+                    (name.clone(), group)
+                })
+                .collect(),
+            sample_adequacy: Some(SampleAdequacy {
+                samples: result.sample_adequacy.samples,
+                suggested_minimum_samples: result.sample_adequacy.suggested_minimum_samples,
+                percentiles: result
+                    .sample_adequacy
+                    .percentiles
+                    .iter()
+                    .map(|confidence| PercentileConfidence {
+                        percentile: confidence.percentile,
+                        minimum_samples: confidence.minimum_samples,
+                        reliable: confidence.reliable,
+                    })
+                    .collect(),
+            }),
+            goodput_rps: result.goodput_rps,
+            connections_established: result.connections_established,
+            warnings: result.warnings.clone(),
+            sse: result.sse.as_ref().map(|sse| SseSummary {
+                connections: sse.connections,
+                events_received: sse.events_received,
+                event_rate: sse.event_rate,
+                inter_event_p50: sse.inter_event_p50,
+                inter_event_p95: sse.inter_event_p95,
+                inter_event_p99: sse.inter_event_p99,
+                per_connection: sse
+                    .per_connection
+                    .iter()
+                    .map(|connection| SseConnectionSummary {
+                        connection: connection.connection,
+                        events: connection.events,
+                        event_rate: connection.event_rate,
+                    })
+                    .collect(),
+            }),
+            pool_saturation_percent: result.pool_saturation_percent,
+            achieved_concurrency: result.achieved_concurrency,
+            breakdown_by_dimension: result
+                .breakdown_by_dimension
+                .iter()
+                .map(|(dimension, values)| {
+                    let values = values
+                        .iter()
+                        .map(|(value, breakdown)| (value.clone(), breakdown.into()))
+                        .collect();
+                    (dimension.clone(), DimensionValues { values })
+                })
+                .collect(),
+            region: result.region.clone(),
+            cancelled: result.cancelled,
+            cache_hits: result.cache_hits,
+            cache_hit_rate: result.cache_hit_rate,
+            avg_request_bytes: result.avg_request_bytes,
+            avg_response_bytes: result.avg_response_bytes,
+            bytes_per_successful_request: result.bytes_per_successful_request,
+            total_bytes: result.total_bytes,
+            throughput_bytes_per_second: result.throughput_bytes_per_second,
+            ttfb_p50: result.ttfb_p50,
+            ttfb_p95: result.ttfb_p95,
+            ttfb_p99: result.ttfb_p99,
+            recovered_within_seconds: result.recovered_within_seconds,
+            recovery_failed: result.recovery_failed,
+            in_flight_distribution: result.in_flight_distribution.as_ref().map(|in_flight| {
+                InFlightDistribution {
+                    samples: in_flight.samples,
+                    mean: in_flight.mean,
+                    p50: in_flight.p50,
+                    p90: in_flight.p90,
+                    p99: in_flight.p99,
+                    max: in_flight.max,
+                }
+            }),
+            cache_busted: result.cache_busted,
+            cold_start_ms: result.cold_start_ms,
+            warmup_requests: result.warmup_requests,
+            segment_results: result
+                .segment_results
+                .iter()
+                .map(|segment| SegmentResult {
+                    start_seconds: segment.start_seconds,
+                    duration_seconds: segment.duration_seconds,
+                    target_rps: segment.target_rps,
+                    achieved_rps: segment.achieved_rps,
+                    phase: segment.phase.as_ref().map(|phase| PhaseSummary {
+                        requests: phase.requests,
+                        failed: phase.failed,
+                        average_response_time: phase.average_response_time,
+                        p50_response_time: phase.p50_response_time,
+                        p95_response_time: phase.p95_response_time,
+                        p99_response_time: phase.p99_response_time,
+                    }),
+                })
+                .collect(),
+            time_weighted_avg_latency: result.time_weighted_avg_latency,
+            global_target_rps: result.global_target_rps,
+            aggregate_rps: result.aggregate_rps,
+            outcome_transitions: result.outcome_transitions,
+            flakiness_score: result.flakiness_score,
+            compressed_response_fraction: result.compressed_response_fraction,
+            capacity_estimate_rps: result.capacity_estimate_rps,
+            network_error_fraction: result.network_error_fraction,
+            status_code_latencies: result
+                .status_code_latencies
+                .iter()
+                .map(|(code, latency)| (*code as u32, latency.into()))
+                .collect(),
+            decompressed_bytes: result.decompressed_bytes,
+            schedule_error_p50: result.schedule_error_p50,
+            schedule_error_p95: result.schedule_error_p95,
+            schedule_error_p99: result.schedule_error_p99,
+            worst_interval: result.worst_interval.as_ref().map(|worst| WorstInterval {
+                timestamp: worst.timestamp,
+                p95: worst.p95,
+                error_rate: worst.error_rate,
+            }),
+            latency_histogram: result.latency_histogram.clone(),
+            latency_digest: result.latency_digest.as_ref().map(|digest| LatencyDigest {
+                compression: digest.compression,
+                count: digest.count,
+                min: digest.min,
+                max: digest.max,
+                centroids: digest
+                    .centroids
+                    .iter()
+                    .map(|centroid| Centroid {
+                        mean: centroid.mean,
+                        weight: centroid.weight,
+                    })
+                    .collect(),
+            }),
+        }
+    }
+}
+
+impl From<&types::TlsLatency> for TlsLatency {
+    fn from(latency: &types::TlsLatency) -> Self {
+        Self {
+            count: latency.count,
+            average_response_time: latency.average_response_time,
+            p50_response_time: latency.p50_response_time,
+            p95_response_time: latency.p95_response_time,
+            p99_response_time: latency.p99_response_time,
+            cipher_suites: latency.cipher_suites.clone(),
+        }
+    }
+}
+
+impl From<&types::EndpointResult> for EndpointResult {
+    fn from(endpoint: &types::EndpointResult) -> Self {
+        Self {
+            requests: endpoint.requests,
+            passed: endpoint.passed,
+            failed: endpoint.failed,
+            status_code_distribution: endpoint
+                .status_code_distribution
+                .iter()
+                .map(|(code, count)| (*code as u32, *count))
+                .collect(),
+            timeouts: endpoint.timeouts,
+            average_response_time: endpoint.average_response_time,
+            p50_response_time: endpoint.p50_response_time,
+            p95_response_time: endpoint.p95_response_time,
+            p99_response_time: endpoint.p99_response_time,
+        }
+    }
+}
+
+impl From<&types::DimensionBreakdown> for DimensionBreakdown {
+    fn from(breakdown: &types::DimensionBreakdown) -> Self {
+        Self {
+            requests: breakdown.requests,
+            successful: breakdown.successful,
+            failed: breakdown.failed,
+            average_response_time: breakdown.average_response_time,
+            p50_response_time: breakdown.p50_response_time,
+            p95_response_time: breakdown.p95_response_time,
+            p99_response_time: breakdown.p99_response_time,
+        }
+    }
+}
+
+impl From<&types::StatusCodeLatency> for StatusCodeLatency {
+    fn from(latency: &types::StatusCodeLatency) -> Self {
+        Self {
+            count: latency.count,
+            average_response_time: latency.average_response_time,
+            p50_response_time: latency.p50_response_time,
+            p95_response_time: latency.p95_response_time,
+            p99_response_time: latency.p99_response_time,
+        }
+    }
+}
+
+impl From<&types::TailBucket> for TailBucket {
+    fn from(bucket: &types::TailBucket) -> Self {
+        Self {
+            percentile: bucket.percentile,
+            threshold_ms: bucket.threshold_ms,
+            entries: bucket
+                .entries
+                .iter()
+                .map(|entry| TailEntry {
+                    endpoint: entry.endpoint.clone(),
+                    status_code: entry.status_code as u32,
+                    count: entry.count,
+                    share: entry.share,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&types::TimeSeriesPoint> for TimeSeriesPoint {
+    fn from(point: &types::TimeSeriesPoint) -> Self {
+        Self {
+            timestamp: point.timestamp,
+            interval_seconds: point.interval_seconds,
+            rps: point.rps,
+            avg_response_time: point.avg_response_time,
+            min_response_time: point.min_response_time,
+            max_response_time: point.max_response_time,
+            p95_response_time: point.p95_response_time,
+            error_rate: point.error_rate,
+            interval_rps: point.interval_rps,
+            target_rps: point.target_rps,
+            interval_error_rate: point.interval_error_rate,
+            goodput_rps: point.goodput_rps,
+            pool_saturated: point.pool_saturated,
+            schedule_lag_ms: point.schedule_lag_ms,
+        }
+    }
+}
+
+impl From<&types::Metric> for Metric {
+    fn from(metric: &types::Metric) -> Self {
+        Self {
+            test_id: metric.test_id.clone(),
+            timestamp: metric.timestamp.clone(),
+            request_count: metric.request_count,
+            success_count: metric.success_count,
+            error_count: metric.error_count,
+            avg_response_time: metric.avg_response_time,
+            status_code: metric.status_code.map(u32::from),
+            error_message: metric.error_message.clone(),
+            active_users: metric.active_users,
+            region: metric.region.clone(),
+        }
+    }
+}
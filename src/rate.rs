@@ -0,0 +1,117 @@
+// Target request rate as a function of time into the test, from either a
+// `loadExpression` or a `rateSchedule` of fixed-length segments, or as rebalanced
+// between shards under `rateCoordination`
+use crate::expression::Expression;
+use crate::types::{LoadTestMessage, RateSegment, RateSmoothing};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+pub enum RateProfile {
+    Expression(Expression),
+    Schedule {
+        segments: Vec<RateSegment>,
+        smoothing: RateSmoothing,
+    },
+    // f64 bits, updated as shards report in
+    Coordinated(Arc<AtomicU64>),
+}
+
+impl RateProfile {
+    // None when the rate is fixed by `requestsPerSecond`
+    pub fn from_message(message: &LoadTestMessage) -> Option<Self> {
+        if let Some(segments) = &message.rate_schedule {
+            return Some(RateProfile::Schedule {
+                segments: segments.clone(),
+                smoothing: message.rate_smoothing.unwrap_or_default(),
+            });
+        }
+
+        // Already checked by `validate()`
+        let expression = Expression::parse(message.load_expression.as_deref()?).ok()?;
+        Some(RateProfile::Expression(expression))
+    }
+
+    // Requests per second wanted at `t` seconds; zero once the schedule has run out
+    pub fn evaluate(&self, t: f64) -> f64 {
+        match self {
+            RateProfile::Expression(expression) => expression.evaluate(t),
+            RateProfile::Coordinated(rate) => f64::from_bits(rate.load(Ordering::Relaxed)),
+            RateProfile::Schedule {
+                segments,
+                smoothing,
+            } => {
+                let mut segment_start = 0.0;
+                let mut previous_rps = 0.0;
+
+                for segment in segments {
+                    let length = segment.duration_seconds as f64;
+                    if t < segment_start + length {
+                        return match smoothing {
+                            RateSmoothing::Step => segment.rps,
+                            // Ramps from the previous segment's target (zero for the
+                            // first) to this one's over the segment
+                            RateSmoothing::Linear => {
+                                let progress = (t - segment_start) / length;
+                                previous_rps + (segment.rps - previous_rps) * progress
+                            }
+                        };
+                    }
+                    segment_start += length;
+                    previous_rps = segment.rps;
+                }
+
+                0.0
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(smoothing: RateSmoothing) -> RateProfile {
+        RateProfile::Schedule {
+            segments: vec![
+                RateSegment {
+                    duration_seconds: 10,
+                    rps: 10.0,
+                },
+                RateSegment {
+                    duration_seconds: 10,
+                    rps: 50.0,
+                },
+            ],
+            smoothing,
+        }
+    }
+
+    #[test]
+    fn step_schedule_switches_at_segment_boundaries() {
+        let profile = schedule(RateSmoothing::Step);
+
+        assert_eq!(profile.evaluate(0.0), 10.0);
+        assert_eq!(profile.evaluate(9.99), 10.0);
+        assert_eq!(profile.evaluate(10.0), 50.0);
+        assert_eq!(profile.evaluate(19.99), 50.0);
+    }
+
+    #[test]
+    fn linear_schedule_ramps_from_the_previous_segment() {
+        let profile = schedule(RateSmoothing::Linear);
+
+        assert_eq!(profile.evaluate(0.0), 0.0);
+        assert_eq!(profile.evaluate(5.0), 5.0);
+        assert_eq!(profile.evaluate(10.0), 10.0);
+        assert_eq!(profile.evaluate(15.0), 30.0);
+    }
+
+    #[test]
+    fn schedule_drops_to_zero_after_the_last_segment() {
+        for smoothing in [RateSmoothing::Step, RateSmoothing::Linear] {
+            let profile = schedule(smoothing);
+            assert_eq!(profile.evaluate(20.0), 0.0);
+            assert_eq!(profile.evaluate(3600.0), 0.0);
+        }
+    }
+}
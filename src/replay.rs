@@ -0,0 +1,88 @@
+// `loadmaster-worker replay <result.json> [percentiles]`: recomputes percentiles from the
+// latency histogram embedded in a stored result, without sending any load
+use crate::stats;
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{Map, Value};
+use std::fs;
+use std::io::{self, Read};
+
+const DEFAULT_PERCENTILES: &[f64] = &[50.0, 90.0, 95.0, 99.0, 99.9];
+
+// `args` are what follows `replay`: the result file ("-" for stdin) and an optional
+// comma-separated percentile list
+pub fn run(args: &[String]) -> Result<()> {
+    let Some(source) = args.first() else {
+        bail!("usage: loadmaster-worker replay <result.json|-> [p1,p2,...]");
+    };
+
+    let percentiles = match args.get(1) {
+        Some(list) => parse_percentiles(list)?,
+        None => DEFAULT_PERCENTILES.to_vec(),
+    };
+
+    let raw = if source == "-" {
+        let mut raw = String::new();
+        io::stdin().read_to_string(&mut raw)?;
+        raw
+    } else {
+        fs::read_to_string(source).with_context(|| format!("reading {}", source))?
+    };
+
+    let mut result: Value = serde_json::from_str(&raw)?;
+    let encoded = result
+        .get("latencyHistogram")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("result has no latencyHistogram to replay"))?;
+    let histogram = stats::decode_histogram(encoded)?;
+
+    let recomputed: Map<String, Value> = percentiles
+        .iter()
+        .map(|percentile| {
+            let value = histogram.value_at_percentile(*percentile) as f64;
+            (format!("p{}", percentile), Value::from(value))
+        })
+        .collect();
+
+    if let Some(fields) = result.as_object_mut() {
+        fields.insert("percentiles".to_string(), Value::Object(recomputed));
+    }
+
+    println!("{}", serde_json::to_string_pretty(&result)?);
+    Ok(())
+}
+
+fn parse_percentiles(list: &str) -> Result<Vec<f64>> {
+    list.split(',')
+        .map(|percentile| {
+            let value: f64 = percentile
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid percentile {:?}", percentile))?;
+            if !(0.0..=100.0).contains(&value) {
+                bail!("percentile {} must be between 0 and 100", value);
+            }
+            Ok(value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_comma_separated_list() {
+        assert_eq!(
+            parse_percentiles("50, 99.9,100").unwrap(),
+            [50.0, 99.9, 100.0]
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_and_malformed_entries() {
+        assert!(parse_percentiles("101").is_err());
+        assert!(parse_percentiles("-1").is_err());
+        assert!(parse_percentiles("50,p99").is_err());
+        assert!(parse_percentiles("50,,99").is_err());
+    }
+}
@@ -0,0 +1,1338 @@
+use crate::auth::Auth;
+use crate::body;
+use crate::cache::ResponseCache;
+use crate::pacing::sample_body_size;
+use crate::session::StickySession;
+use crate::stats::{note_connection, Statistics};
+use crate::template;
+use crate::trace::TraceEntry;
+use crate::types::{
+    BodyType, EndpointConfig, FaultKind, LoadTestMessage, ValidationRequest, ValidationVerdict,
+    ValidationWebhookConfig, DEFAULT_CACHE_BUST_PARAM, DEFAULT_MAX_DECOMPRESSED_BYTES,
+};
+use anyhow::Result;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use futures::stream::{self, StreamExt};
+use log::warn;
+use rand::rngs::StdRng;
+use rand::Rng;
+use reqwest::header::{HeaderMap, ACCEPT_ENCODING, CONNECTION, CONTENT_ENCODING, CONTENT_TYPE};
+use reqwest::{Body, Client, Method, Request, RequestBuilder, Response, StatusCode};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+// Sent as Accept-Encoding with `acceptCompression`, unless the test sets its own
+const ACCEPTED_ENCODINGS: &str = "gzip, deflate, br";
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+// How long an early_close fault keeps the request half-sent before dropping it
+const EARLY_CLOSE_AFTER: Duration = Duration::from_millis(10);
+
+// Per-request decisions drawn up front from the caller's RNG
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestPlan {
+    pub fault: Option<FaultKind>,
+    pub validate: bool,
+    // Index into `endpoints` when the test defines several
+    pub endpoint: Option<usize>,
+    // Size of the generated body, with bodySizeDistribution
+    pub body_size: Option<usize>,
+    // Sends `Connection: close`, with maxRequestsPerConnection
+    pub close_connection: bool,
+}
+
+impl RequestPlan {
+    // `sequence` is the request's position within its stream of requests (the whole
+    // test, or one virtual user), used for connection cycling
+    pub fn draw(message: &LoadTestMessage, rng: &mut StdRng, sequence: u32) -> Self {
+        Self {
+            fault: pick_fault(message, rng),
+            validate: message
+                .validation_webhook
+                .as_ref()
+                .is_some_and(|webhook| rng.gen_bool(webhook.sample_rate.unwrap_or(1.0))),
+            endpoint: pick_endpoint(message, rng),
+            body_size: message
+                .body_size_distribution
+                .as_ref()
+                .map(|sizes| sample_body_size(sizes, rng)),
+            close_connection: message
+                .max_requests_per_connection
+                .is_some_and(|max| (sequence + 1).is_multiple_of(max)),
+        }
+    }
+
+    // Endpoint group the request counts against, if any
+    pub fn group<'a>(&self, message: &'a LoadTestMessage) -> Option<&'a str> {
+        let endpoint = message.endpoints.as_ref()?.get(self.endpoint?)?;
+        endpoint.group.as_deref()
+    }
+}
+
+// Weighted pick of the endpoint the next request goes to
+fn pick_endpoint(message: &LoadTestMessage, rng: &mut StdRng) -> Option<usize> {
+    let endpoints = message.endpoints.as_ref()?;
+    let total: u32 = endpoints.iter().map(|e| e.weight.unwrap_or(1)).sum();
+
+    let mut roll = rng.gen_range(0..total);
+    endpoints.iter().position(|endpoint| {
+        let weight = endpoint.weight.unwrap_or(1);
+        if roll < weight {
+            return true;
+        }
+        roll -= weight;
+        false
+    })
+}
+
+// Method and URL a request goes to: the drawn endpoint's, or the test's own target
+fn target<'a>(
+    message: &'a LoadTestMessage,
+    endpoint: Option<&'a EndpointConfig>,
+) -> (&'a str, &'a str) {
+    match endpoint {
+        Some(endpoint) => (
+            endpoint.method.as_deref().unwrap_or(&message.method),
+            &endpoint.url,
+        ),
+        None => (&message.method, &message.target_url),
+    }
+}
+
+// Decides whether the next request gets a fault injected, and which one
+fn pick_fault(message: &LoadTestMessage, rng: &mut StdRng) -> Option<FaultKind> {
+    let config = message.fault_injection.as_ref()?;
+    if !rng.gen_bool(config.rate) {
+        return None;
+    }
+
+    let faults = config.faults.as_deref().unwrap_or(&FaultKind::ALL);
+    Some(faults[rng.gen_range(0..faults.len())])
+}
+
+// Sends a single request, retrying when configured, and records its outcome. Returns
+// the values the endpoint extracts from the response, or None when the request failed.
+pub async fn send_request(
+    client: &Client,
+    message: &LoadTestMessage,
+    stats: &Mutex<Statistics>,
+    plan: RequestPlan,
+    session: &mut Option<StickySession>,
+    auth: Option<&Auth>,
+    cache: Option<&ResponseCache>,
+) -> Option<HashMap<String, String>> {
+    // Current credentials, refreshed first when they've expired
+    let mut auth_headers = match auth {
+        Some(auth) => match auth.headers().await {
+            Ok(headers) => Some(headers),
+            Err(e) => {
+                warn!("🔑 Failed to obtain credentials: {}", e);
+                stats.lock().await.record_failure("auth_failed".to_string());
+                return None;
+            }
+        },
+        None => None,
+    };
+
+    let endpoint = plan
+        .endpoint
+        .and_then(|index| message.endpoints.as_ref()?.get(index));
+    let (method, url) = target(message, endpoint);
+    let extract = endpoint.and_then(|e| e.extract.as_ref());
+
+    // Identical GETs within the cache TTL never reach the network. Requests whose
+    // response gets inspected (faults, validation, assertions, sessions) always do.
+    let cacheable = method.eq_ignore_ascii_case("GET")
+        && plan.fault.is_none()
+        && !plan.validate
+        && session.is_none()
+        && endpoint.and_then(|e| e.body_contains.as_ref()).is_none()
+        && extract.is_none()
+        && message.body_assertions.is_none();
+
+    // The request as it goes out, with the credentials on top of the test's and the
+    // endpoint's headers
+    let prepare = |auth_headers: Option<&HeaderMap>| {
+        let request_builder = build_request(client, message, plan, endpoint, session.as_ref());
+        match auth_headers {
+            Some(headers) => request_builder.headers(headers.clone()),
+            None => request_builder,
+        }
+    };
+
+    let cache_key = cache.filter(|_| cacheable).and_then(|cache| {
+        let request = prepare(auth_headers.as_ref()).build().ok()?;
+        Some((cache, ResponseCache::key(url, request.headers())))
+    });
+    if let Some((cache, key)) = &cache_key {
+        let hit = cache.is_fresh(key);
+        stats.lock().await.record_cache_lookup(hit);
+        if hit {
+            return Some(HashMap::new());
+        }
+    }
+
+    // Injected faults are deliberate, so they only ever get one attempt
+    let max_attempts = match plan.fault {
+        Some(_) => 1,
+        None => message.retry.retries.saturating_add(1),
+    };
+
+    let mut retries = 0;
+    let mut reauthenticated = false;
+    let mut request_bytes = 0;
+    let (request_start, sent) = loop {
+        let request_start = Instant::now();
+
+        // Rebuilt on every attempt so a retry never reuses a consumed body
+        let sent = match prepare(auth_headers.as_ref()).build() {
+            Ok(mut request) => {
+                if let Some(fault) = plan.fault {
+                    corrupt_body(&mut request, fault);
+                }
+                request_bytes = request
+                    .body()
+                    .and_then(|body| body.as_bytes())
+                    .map_or(0, |body| body.len() as u64);
+                client.execute(request).await
+            }
+            Err(e) => Err(e),
+        };
+        if let Ok(response) = &sent {
+            note_connection(&stats.lock().await.connections, response);
+        }
+
+        let retryable = match &sent {
+            Ok(response) => message.retry.is_retryable(response.status().as_u16()),
+            // Only a request that never got through; a timed out one may well have
+            Err(e) => is_network_error(e),
+        };
+        // A 401 gets one resend with refreshed credentials, whatever the retry settings
+        let unauthorized = auth.is_some()
+            && plan.fault.is_none()
+            && sent
+                .as_ref()
+                .is_ok_and(|response| response.status() == StatusCode::UNAUTHORIZED);
+        let reauthenticate = unauthorized && !reauthenticated;
+        if !reauthenticate && (!retryable || retries + 1 >= max_attempts) {
+            break (request_start, sent);
+        }
+
+        if let (true, Some(auth)) = (unauthorized, auth) {
+            reauthenticated = true;
+            auth.invalidate().await;
+            match auth.headers().await {
+                Ok(headers) => auth_headers = Some(headers),
+                Err(e) => {
+                    warn!("🔑 Failed to refresh credentials: {}", e);
+                    break (request_start, sent);
+                }
+            }
+        }
+
+        retries += 1;
+        if let Some(backoff) = message.retry.backoff(retries) {
+            tokio::time::sleep(backoff).await;
+        }
+    };
+
+    // Execute request
+    let mut backend = None;
+    let mut verdict = None;
+    let mut response_status = None;
+    let mut unexpected_status = None;
+    let mut response_bytes = 0;
+    let mut decompressed_bytes = 0;
+    let mut extracted = HashMap::new();
+    let mut compressed = None;
+    let mut network_error = false;
+    let mut response_latency = None;
+    let outcome = match sent {
+        Ok(mut response) => {
+            let status = response.status();
+            // Headers are in: time to first byte
+            let ttfb = request_start.elapsed().as_millis() as u64;
+            response_status = Some(status.as_u16());
+            compressed = Some(
+                response
+                    .headers()
+                    .get(CONTENT_ENCODING)
+                    .is_some_and(|encoding| encoding != "identity"),
+            );
+
+            // Rejected credentials get refreshed before the next request
+            if let (StatusCode::UNAUTHORIZED, Some(auth)) = (status, auth) {
+                auth.invalidate().await;
+            }
+
+            if let Some(session) = session.as_mut() {
+                backend = session.observe(response.headers());
+            }
+
+            // Hand sampled responses to the external validator
+            if let (true, Some(webhook)) = (plan.validate, &message.validation_webhook) {
+                verdict = Some(
+                    call_validation_webhook(
+                        client,
+                        webhook,
+                        message,
+                        (method, url),
+                        status.as_u16(),
+                        ttfb,
+                        response.headers(),
+                    )
+                    .await,
+                );
+            }
+
+            // Abort reading bodies that expand past the configured limit
+            let body_contains = endpoint.and_then(|e| e.body_contains.as_deref());
+            let body_start = Instant::now();
+            let inspected =
+                body_contains.is_some() || message.body_assertions.is_some() || extract.is_some();
+            // Compressed bodies are always read, so both their sizes are known
+            let encoding = content_coding(response.headers());
+            let limit = message.max_decompressed_bytes;
+            let mut wire_bytes = None;
+            let mut decode_failed = false;
+            let body_check = match (limit, inspected || encoding.is_some()) {
+                // Read through all the same, so the total time covers the download
+                (None, false) => drain_body(&mut response).await.map(|received| {
+                    wire_bytes = Some(received);
+                    Some(Vec::new())
+                }),
+                (limit, _) => {
+                    let limit = limit.unwrap_or(DEFAULT_MAX_DECOMPRESSED_BYTES);
+                    match read_body_within_limit(&mut response, limit).await {
+                        Ok(Some(raw)) => {
+                            wire_bytes = Some(raw.len() as u64);
+                            match encoding {
+                                // Decoding is CPU-bound, so it stays off the runtime's threads
+                                Some(encoding) => {
+                                    let decoded = tokio::task::spawn_blocking(move || {
+                                        decode_body(encoding, &raw, limit)
+                                    })
+                                    .await;
+                                    match decoded {
+                                        Ok(Ok(body)) => Ok(body),
+                                        _ => {
+                                            decode_failed = true;
+                                            Ok(Some(Vec::new()))
+                                        }
+                                    }
+                                }
+                                None => Ok(Some(raw)),
+                            }
+                        }
+                        other => other,
+                    }
+                }
+            };
+            let response_time = ttfb + body_start.elapsed().as_millis() as u64;
+            response_latency = Some(response_time);
+
+            // Every body is read through, so its size is what actually came in, with or
+            // without a Content-Length; bodies abandoned past the limit count as empty
+            response_bytes = wire_bytes.unwrap_or(0);
+            decompressed_bytes = match (&body_check, encoding) {
+                (Ok(Some(body)), Some(_)) => body.len() as u64,
+                _ => response_bytes,
+            };
+
+            if message
+                .expected_status_codes
+                .as_ref()
+                .is_some_and(|codes| !codes.contains(&status.as_u16()))
+            {
+                unexpected_status = Some(status.as_u16());
+            }
+
+            match body_check {
+                Ok(Some(_)) if decode_failed => Err("decompression_failed".to_string()),
+                Ok(Some(_)) if unexpected_status.is_some() => {
+                    Err(format!("unexpected_status_{}", status.as_u16()))
+                }
+                Ok(Some(_)) if matches!(verdict, Some(Ok(false))) => {
+                    Err("validation_failed".to_string())
+                }
+                Ok(Some(body)) if !assertions_pass(endpoint, status.as_u16(), &body) => {
+                    Err("assertion_failed".to_string())
+                }
+                Ok(Some(body)) => match (body_assertion_failure(message, &body), extract) {
+                    (Some(error), _) => Err(error),
+                    (None, Some(extract)) => match extract_values(&body, extract) {
+                        Some(values) => {
+                            extracted = values;
+                            Ok((response_time, ttfb, status.as_u16()))
+                        }
+                        None => Err("extraction_failed".to_string()),
+                    },
+                    (None, None) => Ok((response_time, ttfb, status.as_u16())),
+                },
+                Ok(None) => Err("decompression_bomb".to_string()),
+                Err(e) => {
+                    network_error = is_network_error(&e);
+                    Err(error_bucket(&e))
+                }
+            }
+        }
+        Err(e) => {
+            network_error = is_network_error(&e);
+            Err(error_bucket(&e))
+        }
+    };
+
+    if let (Some((cache, key)), Ok(_)) = (cache_key, &outcome) {
+        cache.store(key);
+    }
+
+    let mut stats = stats.lock().await;
+    let passed = outcome.is_ok().then_some(extracted);
+    if stats.record_warmup(request_start) {
+        return passed;
+    }
+    stats.record_retries(retries);
+
+    if let Some(size) = plan.body_size {
+        stats.record_body_size(size);
+    }
+
+    if plan.fault.is_none() {
+        stats.record_bytes(request_bytes, response_bytes, decompressed_bytes);
+    }
+
+    if let (None, Some(compressed)) = (plan.fault, compressed) {
+        stats.record_compression(compressed);
+    }
+
+    if let Some(observation) = backend {
+        stats.record_backend(&observation.backend, observation.violation);
+    }
+
+    if let Some(verdict) = &verdict {
+        stats.record_validation(verdict.as_ref().ok().copied());
+    }
+
+    if let (None, Some(endpoint)) = (plan.fault, endpoint) {
+        let response_time = outcome
+            .as_ref()
+            .ok()
+            .map(|(response_time, _, _)| *response_time);
+        let timed_out = outcome.as_ref().is_err_and(|error| error == "timeout");
+        stats.record_endpoint(&endpoint.name, response_status, response_time, timed_out);
+    }
+
+    if let (None, Some(status), Some(response_time)) =
+        (plan.fault, response_status, response_latency)
+    {
+        stats.record_status_latency(status, response_time);
+    }
+
+    if let (None, Some(response_time)) = (plan.fault, response_latency) {
+        stats.record_tls_latency(url, response_time);
+    }
+
+    if let (None, Some(group)) = (plan.fault, plan.group(message)) {
+        stats.record_group(group, outcome.is_ok());
+    }
+
+    if let (None, Some(tags)) = (plan.fault, endpoint.and_then(|e| e.tags.as_ref())) {
+        let response_time = outcome
+            .as_ref()
+            .ok()
+            .map(|(response_time, _, _)| *response_time);
+        stats.record_dimensions(tags, response_time);
+    }
+
+    if stats.wants_trace() {
+        stats.record_trace(TraceEntry {
+            name: format!("{} {}", method, url),
+            start: request_start,
+            duration: request_start.elapsed(),
+            status: response_status,
+            error: outcome.as_ref().err().cloned(),
+        });
+    }
+
+    match (plan.fault, outcome) {
+        (Some(fault), outcome) => stats.record_fault(fault.as_str(), outcome.is_err()),
+        (None, Ok((response_time, ttfb, status))) => {
+            if stats.record_cold_start(response_time) && message.exclude_cold_start {
+                stats.record_success_without_latency(status);
+            } else {
+                let endpoint = format!("{} {}", method, url);
+                stats.record_success(&endpoint, response_time, ttfb, status);
+            }
+        }
+        (None, Err(error)) => {
+            if let Some(status) = unexpected_status {
+                stats.record_status_code(status);
+            }
+            if network_error {
+                stats.record_network_error();
+            }
+            stats.record_failure(error)
+        }
+    }
+
+    passed
+}
+
+// Values picked out of a JSON body by paths such as "$.auth.token" or "items[0].id";
+// None when the body isn't JSON or a path doesn't resolve
+fn extract_values(
+    body: &[u8],
+    extract: &HashMap<String, String>,
+) -> Option<HashMap<String, String>> {
+    let json: Value = serde_json::from_slice(body).ok()?;
+
+    extract
+        .iter()
+        .map(|(name, path)| {
+            let path = path
+                .trim_start_matches('$')
+                .replace('[', ".")
+                .replace(']', "");
+            let value =
+                path.split('.')
+                    .filter(|key| !key.is_empty())
+                    .try_fold(&json, |value, key| match value {
+                        Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+                        _ => value.get(key),
+                    })?;
+            let text = match value {
+                Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            Some((name.clone(), text))
+        })
+        .collect()
+}
+
+// Connection failures and transport errors while sending, and connections reset or
+// dropped while the response was read. Timeouts are the target being slow, not the path.
+fn is_network_error(error: &reqwest::Error) -> bool {
+    if error.is_timeout() {
+        return false;
+    }
+    if error.is_connect() || error.is_request() {
+        return true;
+    }
+
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+            return matches!(
+                io.kind(),
+                ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::ConnectionRefused
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::UnexpectedEof
+            );
+        }
+        source = cause.source();
+    }
+    false
+}
+
+// Timeouts share one bucket; other errors are reported as reqwest describes them
+fn error_bucket(error: &reqwest::Error) -> String {
+    if error.is_timeout() {
+        "timeout".to_string()
+    } else {
+        error.to_string()
+    }
+}
+
+// Builds one attempt of the request: method, headers, body, session state and any fault
+fn build_request(
+    client: &Client,
+    message: &LoadTestMessage,
+    plan: RequestPlan,
+    endpoint: Option<&EndpointConfig>,
+    session: Option<&StickySession>,
+) -> RequestBuilder {
+    let (method, url) = target(message, endpoint);
+
+    // Parse HTTP method
+    let method = Method::from_bytes(method.as_bytes()).unwrap_or(Method::GET);
+
+    // Build request
+    let mut request_builder = client.request(method, url);
+
+    // Add headers if provided; the endpoint's own replace the test's of the same name
+    let endpoint_headers = endpoint.and_then(|endpoint| endpoint.headers.as_ref());
+
+    // Ask for compressed responses when the test opts in and doesn't name its own encodings
+    let chooses_encoding = message
+        .headers
+        .iter()
+        .chain(endpoint_headers)
+        .flat_map(HashMap::keys)
+        .any(|name| name.eq_ignore_ascii_case(ACCEPT_ENCODING.as_str()));
+    if message.accept_compression && !chooses_encoding {
+        request_builder = request_builder.header(ACCEPT_ENCODING, ACCEPTED_ENCODINGS);
+    }
+    if let Some(headers) = &message.headers {
+        for (key, value) in headers {
+            let overridden = endpoint_headers
+                .is_some_and(|own| own.keys().any(|name| name.eq_ignore_ascii_case(key)));
+            if !overridden {
+                request_builder = request_builder.header(key, template::render(value).as_ref());
+            }
+        }
+    }
+    for (key, value) in endpoint_headers.into_iter().flatten() {
+        request_builder = request_builder.header(key, template::render(value).as_ref());
+    }
+
+    // Add body if provided, with its placeholders filled in for this request
+    let body = endpoint
+        .and_then(|endpoint| endpoint.body.as_ref())
+        .or(message.body.as_ref());
+    if let Some(body) = body {
+        let body = template::render_json(body);
+        request_builder = match message.body_type.unwrap_or_default() {
+            BodyType::Json => request_builder.json(&body),
+            BodyType::Form => request_builder.form(&body::form_fields(&body)),
+            BodyType::Multipart => {
+                let (content_type, encoded) = body::multipart(&body);
+                request_builder
+                    .header(CONTENT_TYPE, content_type)
+                    .body(encoded)
+            }
+            BodyType::Raw => {
+                let (content_type, encoded) =
+                    body::raw(&body, message.body_content_type.as_deref());
+                request_builder
+                    .header(CONTENT_TYPE, content_type)
+                    .body(encoded)
+            }
+        };
+    }
+
+    // Binary bodies go out as-is with their own content type
+    if let Some(bytes) = &message.body_base64 {
+        let content_type = message
+            .body_content_type
+            .as_deref()
+            .unwrap_or("application/octet-stream");
+        request_builder = request_builder
+            .header(CONTENT_TYPE, content_type)
+            .body(bytes.clone());
+    }
+
+    // Filler body of the size drawn for this request
+    if let Some(size) = plan.body_size {
+        let content_type = message
+            .body_content_type
+            .as_deref()
+            .unwrap_or("application/octet-stream");
+        request_builder = request_builder
+            .header(CONTENT_TYPE, content_type)
+            .body(vec![b'x'; size]);
+    }
+
+    // Fresh on every attempt, so retries miss the cache too
+    if let Some(cache_bust) = &message.cache_bust {
+        let value = Uuid::new_v4().to_string();
+        if let Some(header) = &cache_bust.header {
+            request_builder = request_builder.header(header.as_str(), value.as_str());
+        }
+        if cache_bust.query_param.is_some() || cache_bust.header.is_none() {
+            let param = cache_bust
+                .query_param
+                .as_deref()
+                .unwrap_or(DEFAULT_CACHE_BUST_PARAM);
+            request_builder = request_builder.query(&[(param, value)]);
+        }
+    }
+
+    if plan.close_connection {
+        request_builder = request_builder.header(CONNECTION, "close");
+    }
+
+    // Replay the virtual user's sticky cookie/header
+    if let Some(session) = session {
+        request_builder = session.apply(request_builder);
+    }
+
+    if let Some(timeout_ms) = endpoint.and_then(|endpoint| endpoint.request_timeout_ms) {
+        request_builder = request_builder.timeout(Duration::from_millis(timeout_ms));
+    }
+
+    // Body faults are applied to the built request, see `corrupt_body`
+    if plan.fault == Some(FaultKind::InvalidHeader) {
+        request_builder = request_builder.header(CONTENT_TYPE, ";;invalid/;;");
+    }
+
+    request_builder
+}
+
+// Corrupts the body exactly as it was about to go out, whatever its bodyType: a
+// truncated body sends its first half; an early close starts sending that half, then
+// fails the body stream so the connection is dropped mid-request
+fn corrupt_body(request: &mut Request, fault: FaultKind) {
+    let body = request.body().and_then(Body::as_bytes).unwrap_or_default();
+    let half = body[..body.len() / 2].to_vec();
+
+    match fault {
+        FaultKind::TruncateBody => *request.body_mut() = Some(half.into()),
+        FaultKind::EarlyClose => {
+            // The pause lets the headers and first half reach the wire before the failure
+            let close = async {
+                tokio::time::sleep(EARLY_CLOSE_AFTER).await;
+                Err(std::io::Error::new(
+                    ErrorKind::ConnectionAborted,
+                    "early_close fault",
+                ))
+            };
+            let chunks = stream::once(async { Ok(half) }).chain(stream::once(close));
+            *request.body_mut() = Some(Body::wrap_stream(chunks));
+        }
+        FaultKind::InvalidHeader => {}
+    }
+}
+
+// Checks a response against the endpoint's own success criteria
+fn assertions_pass(endpoint: Option<&EndpointConfig>, status: u16, body: &[u8]) -> bool {
+    let Some(endpoint) = endpoint else {
+        return true;
+    };
+
+    let status_ok = endpoint
+        .expected_status_codes
+        .as_ref()
+        .is_none_or(|codes| codes.contains(&status));
+    let body_ok = endpoint
+        .body_contains
+        .as_ref()
+        .is_none_or(|needle| String::from_utf8_lossy(body).contains(needle.as_str()));
+
+    status_ok && body_ok
+}
+
+// Error key for a body failing the test's `bodyAssertions`, if it does
+fn body_assertion_failure(message: &LoadTestMessage, body: &[u8]) -> Option<String> {
+    let assertions = message.body_assertions.as_ref()?;
+    let body = String::from_utf8_lossy(body);
+
+    if let Some(needle) = &assertions.contains {
+        if !body.contains(needle.as_str()) {
+            return Some("body_missing_substring".to_string());
+        }
+    }
+
+    // An invalid pattern never gets this far; `execute` refuses to start the test
+    if let Some(Ok(regex)) = assertions.regex.as_ref().map(|regex| &regex.compiled) {
+        if !regex.is_match(&body) {
+            return Some("body_regex_mismatch".to_string());
+        }
+    }
+
+    None
+}
+
+// Posts response metadata to the validation webhook and returns whether it passed
+async fn call_validation_webhook(
+    client: &Client,
+    webhook: &ValidationWebhookConfig,
+    message: &LoadTestMessage,
+    (method, url): (&str, &str),
+    status_code: u16,
+    response_time_ms: u64,
+    headers: &HeaderMap,
+) -> Result<bool> {
+    let request = ValidationRequest {
+        test_id: message.test_id.clone(),
+        url: url.to_string(),
+        method: method.to_string(),
+        status_code,
+        response_time_ms,
+        headers: headers
+            .iter()
+            .filter_map(|(name, value)| {
+                value
+                    .to_str()
+                    .ok()
+                    .map(|value| (name.to_string(), value.to_string()))
+            })
+            .collect(),
+    };
+
+    let verdict: ValidationVerdict = client
+        .post(&webhook.url)
+        .json(&request)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    Ok(verdict.pass)
+}
+
+// The response's Content-Encoding when it's one the worker decodes
+fn content_coding(headers: &HeaderMap) -> Option<&'static str> {
+    let encoding = headers.get(CONTENT_ENCODING)?.to_str().ok()?.trim();
+    ["gzip", "x-gzip", "deflate", "br"]
+        .into_iter()
+        .find(|coding| encoding.eq_ignore_ascii_case(coding))
+}
+
+// Decodes a compressed body, giving up with None once it expands past `limit`
+fn decode_body(encoding: &str, raw: &[u8], limit: u64) -> std::io::Result<Option<Vec<u8>>> {
+    let decoder: Box<dyn Read + '_> = match encoding {
+        "br" => Box::new(brotli_decompressor::Decompressor::new(
+            raw,
+            BROTLI_BUFFER_SIZE,
+        )),
+        "deflate" => Box::new(ZlibDecoder::new(raw)),
+        _ => Box::new(GzDecoder::new(raw)),
+    };
+
+    let mut decoded = Vec::new();
+    decoder
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut decoded)?;
+    Ok((decoded.len() as u64 <= limit).then_some(decoded))
+}
+
+// Reads the response body chunk by chunk, returning None as soon as more than
+// `limit` bytes have been received so oversized bodies are never fully buffered
+async fn read_body_within_limit(
+    response: &mut Response,
+    limit: u64,
+) -> reqwest::Result<Option<Vec<u8>>> {
+    let mut body = Vec::new();
+
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() as u64 + chunk.len() as u64 > limit {
+            return Ok(None);
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(Some(body))
+}
+
+// Streams the response body without keeping it, returning how many bytes came in
+async fn drain_body(response: &mut Response) -> reqwest::Result<u64> {
+    let mut received = 0;
+
+    while let Some(chunk) = response.chunk().await? {
+        received += chunk.len() as u64;
+    }
+
+    Ok(received)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{message, MockServer, Reply};
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use base64::Engine;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use serde_json::json;
+    use std::io::Write;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    // Sends the message's request once, returning what was recorded
+    async fn send(message: &LoadTestMessage) -> Statistics {
+        let stats = Mutex::new(Statistics::new());
+        let plan = RequestPlan::default();
+        send_request(&Client::new(), message, &stats, plan, &mut None, None, None).await;
+        stats.into_inner()
+    }
+
+    #[test]
+    fn truncation_halves_the_encoded_body() {
+        let client = Client::new();
+        let mut request = client
+            .post("http://localhost/")
+            .body("0123456789")
+            .build()
+            .unwrap();
+
+        corrupt_body(&mut request, FaultKind::TruncateBody);
+
+        assert_eq!(request.body().and_then(Body::as_bytes), Some(&b"01234"[..]));
+    }
+
+    #[tokio::test]
+    async fn early_close_drops_the_connection_mid_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut received = vec![];
+            socket.read_to_end(&mut received).await.ok();
+            String::from_utf8_lossy(&received).into_owned()
+        });
+
+        let client = Client::new();
+        let mut request = client
+            .post(format!("http://{}/", address))
+            .json(&json!({ "name": "loadmaster" }))
+            .build()
+            .unwrap();
+        corrupt_body(&mut request, FaultKind::EarlyClose);
+
+        assert!(client.execute(request).await.is_err());
+        let received = server.await.unwrap();
+        assert!(received.starts_with("POST / HTTP/1.1"));
+        assert!(received.contains("{\"name\""));
+        assert!(!received.contains("loadmaster\"}"));
+    }
+
+    #[tokio::test]
+    async fn total_time_includes_a_delayed_body() {
+        let server = MockServer::start(|_| {
+            Reply::ok()
+                .body("done")
+                .body_delay(Duration::from_millis(200))
+        })
+        .await;
+        let message = message(json!({ "targetUrl": server.url("/") }));
+
+        let stats = send(&message).await;
+        assert_eq!(stats.successful_requests, 1);
+        let (ttfb, total) = (stats.get_ttfb_percentile(50.0), stats.get_percentile(50.0));
+        assert!(ttfb < total, "ttfb {} >= total {}", ttfb, total);
+        assert!(total >= 200.0);
+    }
+
+    #[tokio::test]
+    async fn response_bytes_are_counted_with_and_without_content_length() {
+        let payload = vec![b'x'; 5000];
+
+        for chunked in [false, true] {
+            let body = payload.clone();
+            let server = MockServer::start(move |_| match chunked {
+                true => Reply::ok().body(body.clone()).chunked(1024),
+                false => Reply::ok().body(body.clone()),
+            })
+            .await;
+            let message = message(json!({ "targetUrl": server.url("/") }));
+
+            let stats = send(&message).await;
+
+            assert_eq!(stats.successful_requests, 1, "chunked: {}", chunked);
+            assert_eq!(stats.bytes_received, 5000, "chunked: {}", chunked);
+            assert_eq!(stats.bytes_decompressed, 5000, "chunked: {}", chunked);
+        }
+    }
+
+    #[tokio::test]
+    async fn failures_are_retried_until_one_succeeds() {
+        let server = MockServer::start(|request| match request.index {
+            0 | 1 => Reply::status(503),
+            _ => Reply::ok(),
+        })
+        .await;
+        let message = message(json!({
+            "targetUrl": server.url("/"),
+            "retries": 3,
+            "retryBackoffMs": 1,
+        }));
+
+        let stats = send(&message).await;
+
+        assert_eq!(stats.successful_requests, 1);
+        assert_eq!(stats.failed_requests, 0);
+        assert_eq!(stats.retries, 2);
+        assert_eq!(server.received().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn timeouts_are_not_retried() {
+        let server =
+            MockServer::start(|_| Reply::ok().head_delay(Duration::from_millis(200))).await;
+        let message = message(json!({ "targetUrl": server.url("/"), "retries": 3 }));
+        let client = Client::builder()
+            .timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        let stats = Mutex::new(Statistics::new());
+
+        send_request(&client, &message, &stats, RequestPlan::default(), &mut None, None, None)
+            .await;
+
+        let stats = stats.into_inner();
+        assert_eq!(stats.failed_requests, 1);
+        assert_eq!(stats.retries, 0);
+        assert_eq!(server.received().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn connection_errors_are_retried() {
+        let message = message(json!({ "retries": 2 }));
+
+        let stats = send(&message).await;
+
+        assert_eq!(stats.failed_requests, 1);
+        assert_eq!(stats.retries, 2);
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn brotli(data: &[u8]) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        let mut encoder = brotli::CompressorWriter::new(&mut encoded, 4096, 5, 22);
+        encoder.write_all(data).unwrap();
+        drop(encoder);
+        encoded
+    }
+
+    #[test]
+    fn bodies_within_the_limit_decode_intact() {
+        let body = b"{\"items\": [1, 2, 3]}".repeat(20);
+
+        for (encoding, raw) in [("gzip", gzip(&body)), ("br", brotli(&body))] {
+            let decoded = decode_body(encoding, &raw, body.len() as u64).unwrap();
+            assert_eq!(decoded.as_deref(), Some(&body[..]), "{}", encoding);
+        }
+    }
+
+    #[test]
+    fn bodies_expanding_past_the_limit_are_rejected() {
+        let body = vec![b'0'; 100_000];
+
+        for (encoding, raw) in [("gzip", gzip(&body)), ("br", brotli(&body))] {
+            assert!(
+                raw.len() < 1000,
+                "{} compressed to {} bytes",
+                encoding,
+                raw.len()
+            );
+            let decoded = decode_body(encoding, &raw, 1000).unwrap();
+            assert_eq!(decoded, None, "{}", encoding);
+        }
+    }
+
+    #[tokio::test]
+    async fn reading_stops_at_the_limit() {
+        let server = MockServer::start(|_| Reply::ok().body(vec![b'x'; 100])).await;
+
+        let mut response = reqwest::get(server.url("/")).await.unwrap();
+        assert_eq!(
+            read_body_within_limit(&mut response, 99).await.unwrap(),
+            None
+        );
+
+        let mut response = reqwest::get(server.url("/")).await.unwrap();
+        let body = read_body_within_limit(&mut response, 100).await.unwrap();
+        assert_eq!(body.map(|body| body.len()), Some(100));
+    }
+
+    #[tokio::test]
+    async fn compressed_response_over_the_limit_fails_the_request() {
+        let server = MockServer::start(|_| {
+            Reply::ok()
+                .header("content-encoding", "gzip")
+                .body(gzip(&[b'0'; 100_000]))
+        })
+        .await;
+        let message = message(json!({
+            "targetUrl": server.url("/"),
+            "maxDecompressedBytes": 1000,
+        }));
+
+        let stats = send(&message).await;
+
+        assert_eq!(stats.failed_requests, 1);
+        assert_eq!(stats.get_errors().get("decompression_bomb"), Some(&1));
+    }
+
+    #[tokio::test]
+    async fn retried_request_renders_its_templates_again() {
+        let server = MockServer::start(|request| match request.index {
+            0 => Reply::status(503),
+            _ => Reply::ok(),
+        })
+        .await;
+        let message = message(json!({
+            "targetUrl": server.url("/"),
+            "method": "POST",
+            "body": { "id": "{{uuid}}" },
+            "retries": 1,
+        }));
+
+        let stats = send(&message).await;
+
+        assert_eq!(stats.successful_requests, 1);
+        let bodies: Vec<String> = server.received().iter().map(|r| r.body_text()).collect();
+        assert_eq!(bodies.len(), 2);
+        assert!(!bodies[0].contains("{{uuid}}"), "{}", bodies[0]);
+        assert_ne!(bodies[0], bodies[1]);
+    }
+
+    #[tokio::test]
+    async fn responses_the_webhook_fails_are_validation_failures() {
+        let server = MockServer::start(|request| match request.path.as_str() {
+            "/validate" => {
+                let metadata: Value = serde_json::from_slice(&request.body).unwrap();
+                let pass = !metadata["url"].as_str().unwrap().ends_with("/bad");
+                Reply::ok().body(json!({ "pass": pass }).to_string())
+            }
+            _ => Reply::ok(),
+        })
+        .await;
+
+        let stats = Mutex::new(Statistics::new());
+        let plan = RequestPlan {
+            validate: true,
+            ..RequestPlan::default()
+        };
+        for path in ["/good", "/bad", "/good", "/bad", "/bad"] {
+            let message = message(json!({
+                "targetUrl": server.url(path),
+                "validationWebhook": { "url": server.url("/validate") },
+            }));
+            send_request(
+                &Client::new(),
+                &message,
+                &stats,
+                plan,
+                &mut None,
+                None,
+                None,
+            )
+            .await;
+        }
+
+        let stats = stats.into_inner();
+        assert_eq!(stats.validation_failures, 3);
+        assert_eq!(stats.validation_webhook_errors, 0);
+        assert_eq!(stats.successful_requests, 2);
+        assert_eq!(stats.get_errors()["validation_failed"], 3);
+        let validated = server
+            .received()
+            .iter()
+            .filter(|r| r.path == "/validate")
+            .count();
+        assert_eq!(validated, 5);
+    }
+
+    #[tokio::test]
+    async fn base64_body_arrives_as_the_original_bytes() {
+        let server = MockServer::start(|_| Reply::ok()).await;
+        let blob: Vec<u8> = (0..=255).rev().collect();
+
+        let stats = send(&message(json!({
+            "targetUrl": server.url("/upload"),
+            "method": "POST",
+            "bodyBase64": BASE64.encode(&blob),
+            "bodyContentType": "application/x-protobuf",
+        })))
+        .await;
+
+        assert_eq!(stats.successful_requests, 1);
+        let received = &server.received()[0];
+        assert_eq!(received.body, blob);
+        assert_eq!(
+            received.header("content-type"),
+            Some("application/x-protobuf")
+        );
+    }
+
+    #[tokio::test]
+    async fn a_500_passes_without_expected_status_codes() {
+        let server = MockServer::start(|_| Reply::status(500)).await;
+
+        let stats = send(&message(json!({ "targetUrl": server.url("/") }))).await;
+
+        assert_eq!(stats.successful_requests, 1);
+        assert_eq!(stats.get_status_codes(), HashMap::from([(500, 1)]));
+    }
+
+    #[tokio::test]
+    async fn a_500_outside_expected_status_codes_fails() {
+        let server = MockServer::start(|_| Reply::status(500)).await;
+
+        let stats = send(&message(json!({
+            "targetUrl": server.url("/"),
+            "expectedStatusCodes": [200, 204],
+        })))
+        .await;
+
+        assert_eq!(stats.failed_requests, 1);
+        assert_eq!(
+            stats.get_errors(),
+            HashMap::from([("unexpected_status_500".to_string(), 1)])
+        );
+        // Still counted under the code that came back
+        assert_eq!(stats.get_status_codes(), HashMap::from([(500, 1)]));
+    }
+
+    #[tokio::test]
+    async fn bodies_matching_the_assertions_pass() {
+        let server = MockServer::start(|_| Reply::ok().body(r#"{"order":"A-1042"}"#)).await;
+
+        let stats = send(&message(json!({
+            "targetUrl": server.url("/"),
+            "bodyAssertions": { "contains": "order", "regex": "A-\\d{4}" },
+        })))
+        .await;
+
+        assert_eq!(stats.successful_requests, 1);
+    }
+
+    #[tokio::test]
+    async fn bodies_failing_an_assertion_say_which() {
+        let server = MockServer::start(|_| Reply::ok().body(r#"{"order":"B-7"}"#)).await;
+        let check = |assertions| {
+            message(json!({
+                "targetUrl": server.url("/"),
+                "bodyAssertions": assertions,
+            }))
+        };
+
+        let missing = send(&check(json!({ "contains": "invoice" }))).await;
+        let mismatched = send(&check(json!({ "regex": "A-\\d{4}" }))).await;
+
+        assert_eq!(
+            missing.get_errors(),
+            HashMap::from([("body_missing_substring".to_string(), 1)])
+        );
+        assert_eq!(
+            mismatched.get_errors(),
+            HashMap::from([("body_regex_mismatch".to_string(), 1)])
+        );
+    }
+
+    #[tokio::test]
+    async fn compressed_fraction_counts_the_encoded_responses() {
+        let body = b"{\"items\": [1, 2, 3]}".repeat(20);
+        let server = MockServer::start(move |request| match request.index % 2 {
+            0 => Reply::ok()
+                .header("content-encoding", "gzip")
+                .body(gzip(&body)),
+            _ => Reply::ok().body(body.clone()),
+        })
+        .await;
+        let message = message(json!({ "targetUrl": server.url("/") }));
+
+        let stats = Mutex::new(Statistics::new());
+        for _ in 0..4 {
+            let plan = RequestPlan::default();
+            send_request(
+                &Client::new(),
+                &message,
+                &stats,
+                plan,
+                &mut None,
+                None,
+                None,
+            )
+            .await;
+        }
+
+        let stats = stats.into_inner();
+        assert_eq!(stats.successful_requests, 4);
+        assert_eq!(stats.get_compressed_response_fraction(), Some(0.5));
+    }
+
+    #[tokio::test]
+    async fn reset_connections_are_network_errors() {
+        let server = MockServer::start(|request| match request.index % 2 {
+            0 => Reply::reset(),
+            _ => Reply::status(500),
+        })
+        .await;
+        let message = message(json!({
+            "targetUrl": server.url("/"),
+            "expectedStatusCodes": [200],
+        }));
+
+        let stats = Mutex::new(Statistics::new());
+        for _ in 0..4 {
+            let plan = RequestPlan::default();
+            send_request(
+                &Client::new(),
+                &message,
+                &stats,
+                plan,
+                &mut None,
+                None,
+                None,
+            )
+            .await;
+        }
+
+        // All four fail, but only the resets on the network path
+        let stats = stats.into_inner();
+        assert_eq!(stats.failed_requests, 4);
+        assert_eq!(stats.network_errors, 2);
+        assert_eq!(stats.get_network_error_fraction(), Some(0.5));
+    }
+
+    #[tokio::test]
+    async fn each_status_code_gets_its_own_latency() {
+        let server = MockServer::start(|request| match request.index % 2 {
+            0 => Reply::ok(),
+            _ => Reply::status(503).head_delay(Duration::from_millis(100)),
+        })
+        .await;
+        let message = message(json!({ "targetUrl": server.url("/") }));
+
+        let stats = Mutex::new(Statistics::new());
+        for _ in 0..4 {
+            let plan = RequestPlan::default();
+            send_request(
+                &Client::new(),
+                &message,
+                &stats,
+                plan,
+                &mut None,
+                None,
+                None,
+            )
+            .await;
+        }
+
+        let latencies = stats.into_inner().get_status_code_latencies();
+        assert_eq!(latencies.len(), 2);
+        let (ok, unavailable) = (&latencies[&200], &latencies[&503]);
+        assert_eq!((ok.count, unavailable.count), (2, 2));
+        assert!(ok.p50_response_time < 100.0, "{}", ok.p50_response_time);
+        assert!(unavailable.p50_response_time >= 100.0);
+    }
+
+    #[tokio::test]
+    async fn gzip_responses_report_wire_and_decompressed_bytes() {
+        let payload = b"{\"items\": [1, 2, 3]}".repeat(250);
+        let encoded = gzip(&payload);
+        let wire = encoded.len() as u64;
+        let server = MockServer::start(move |request| {
+            assert_eq!(request.header("accept-encoding"), Some(ACCEPTED_ENCODINGS));
+            Reply::ok()
+                .header("content-encoding", "gzip")
+                .body(encoded.clone())
+        })
+        .await;
+        let message = message(json!({
+            "targetUrl": server.url("/"),
+            "acceptCompression": true,
+        }));
+
+        let stats = send(&message).await;
+
+        assert_eq!(stats.successful_requests, 1);
+        assert_eq!(stats.bytes_received, wire);
+        assert_eq!(stats.bytes_decompressed, payload.len() as u64);
+        assert!(stats.bytes_received < stats.bytes_decompressed);
+    }
+}
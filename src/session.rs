@@ -0,0 +1,105 @@
+use crate::types::StickySessionConfig;
+use reqwest::header::{HeaderMap, COOKIE, SET_COOKIE};
+use reqwest::RequestBuilder;
+
+// Per-virtual-user sticky session state, replayed on every request the user sends
+pub struct StickySession {
+    config: StickySessionConfig,
+    cookie: Option<String>,
+    header: Option<String>,
+    backend: Option<String>,
+}
+
+// Backend that served a request and whether it broke the user's affinity
+pub struct BackendObservation {
+    pub backend: String,
+    pub violation: bool,
+}
+
+impl StickySession {
+    pub fn new(config: StickySessionConfig) -> Self {
+        Self {
+            config,
+            cookie: None,
+            header: None,
+            backend: None,
+        }
+    }
+
+    pub fn apply(&self, mut request_builder: RequestBuilder) -> RequestBuilder {
+        if let (Some(name), Some(value)) = (&self.config.cookie_name, &self.cookie) {
+            request_builder = request_builder.header(COOKIE, format!("{}={}", name, value));
+        }
+
+        if let (Some(name), Some(value)) = (&self.config.header_name, &self.header) {
+            request_builder = request_builder.header(name.as_str(), value.as_str());
+        }
+
+        request_builder
+    }
+
+    // Captures the sticky value from a response and reports which backend served it
+    pub fn observe(&mut self, headers: &HeaderMap) -> Option<BackendObservation> {
+        if let Some(name) = &self.config.cookie_name {
+            let cookie = headers
+                .get_all(SET_COOKIE)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .find_map(|value| parse_set_cookie(value, name));
+
+            if cookie.is_some() {
+                self.cookie = cookie;
+            }
+        }
+
+        if let Some(name) = &self.config.header_name {
+            if let Some(value) = headers.get(name.as_str()).and_then(|v| v.to_str().ok()) {
+                self.header = Some(value.to_string());
+            }
+        }
+
+        let backend_header = self.config.backend_header.as_deref().unwrap_or("x-backend");
+        let backend = headers.get(backend_header)?.to_str().ok()?.to_string();
+
+        let violation = match &self.backend {
+            Some(first) => *first != backend,
+            None => {
+                self.backend = Some(backend.clone());
+                false
+            }
+        };
+
+        Some(BackendObservation { backend, violation })
+    }
+}
+
+// Extracts the value of cookie `name` from a `Set-Cookie` header
+fn parse_set_cookie(header: &str, name: &str) -> Option<String> {
+    let pair = header.split(';').next()?;
+    let (key, value) = pair.split_once('=')?;
+    (key.trim() == name).then(|| value.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_named_cookie_before_its_attributes() {
+        assert_eq!(
+            parse_set_cookie("SERVERID=web-2; Path=/; HttpOnly", "SERVERID"),
+            Some("web-2".to_string())
+        );
+        assert_eq!(
+            parse_set_cookie(" lb = a=b ;Secure", "lb"),
+            Some("a=b".to_string())
+        );
+    }
+
+    #[test]
+    fn ignores_other_and_malformed_cookies() {
+        assert_eq!(parse_set_cookie("session=abc; Path=/", "SERVERID"), None);
+        assert_eq!(parse_set_cookie("Path=/; SERVERID=web-2", "SERVERID"), None);
+        assert_eq!(parse_set_cookie("SERVERID", "SERVERID"), None);
+    }
+}
@@ -0,0 +1,55 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+// AMQP headers carrying the signature and how it was computed
+pub const SIGNATURE_HEADER: &str = "x-signature";
+pub const SIGNATURE_ALGORITHM_HEADER: &str = "x-signature-algorithm";
+pub const SIGNATURE_ALGORITHM: &str = "hmac-sha256";
+
+// Hex-encoded HMAC-SHA256 of the payload exactly as published
+pub fn sign(secret: &[u8], payload: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(payload);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// Checks a hex signature from `sign` in constant time. The worker only signs; this is
+// the check consumers make, kept next to `sign` so the two can't drift apart.
+#[allow(dead_code)]
+pub fn verify(secret: &[u8], payload: &[u8], signature: &str) -> bool {
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(payload);
+    mac.verify_slice(&signature).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"shared-secret";
+    const RESULT: &[u8] = br#"{"testId":"t1","totalRequests":100,"successfulRequests":100}"#;
+
+    #[test]
+    fn unmodified_result_verifies() {
+        let signature = sign(SECRET, RESULT);
+        assert!(verify(SECRET, RESULT, &signature));
+    }
+
+    #[test]
+    fn tampered_result_fails() {
+        let signature = sign(SECRET, RESULT);
+        let tampered = br#"{"testId":"t1","totalRequests":100,"successfulRequests":99}"#;
+        assert!(!verify(SECRET, tampered, &signature));
+    }
+
+    #[test]
+    fn wrong_secret_or_malformed_signature_fails() {
+        let signature = sign(SECRET, RESULT);
+        assert!(!verify(b"other-secret", RESULT, &signature));
+        assert!(!verify(SECRET, RESULT, "not hex"));
+        assert!(!verify(SECRET, RESULT, &signature[..32]));
+    }
+}
@@ -0,0 +1,204 @@
+// Server-Sent Events: each virtual user keeps one stream open until the deadline,
+// reconnecting when it drops, and counts the events it receives
+use crate::auth::Auth;
+use crate::stats::{note_connection, Statistics};
+use crate::types::LoadTestMessage;
+use log::warn;
+use reqwest::header::ACCEPT;
+use reqwest::{Client, Response};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+
+// Wait before reopening a stream that failed or ended early
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+// Longest event held while waiting for the blank line that ends it; a stream going past
+// it isn't sending events worth counting
+const MAX_EVENT_BYTES: usize = 1024 * 1024;
+
+pub async fn consume(
+    client: &Client,
+    message: &LoadTestMessage,
+    stats: &Mutex<Statistics>,
+    connection: u32,
+    deadline: Instant,
+    auth: Option<&Auth>,
+) {
+    while Instant::now() < deadline {
+        let request_start = Instant::now();
+
+        match open(client, message, deadline, auth).await {
+            Ok(response) => {
+                let connect_time = request_start.elapsed().as_millis() as u64;
+                let endpoint = format!("SSE {}", message.target_url);
+                {
+                    let mut stats = stats.lock().await;
+                    note_connection(&stats.connections, &response);
+                    stats.record_success(
+                        &endpoint,
+                        connect_time,
+                        connect_time,
+                        response.status().as_u16(),
+                    );
+                    stats.record_sse_open(connection);
+                }
+
+                if let Err(e) = read_events(response, stats, connection).await {
+                    // The client timeout ending the stream at the deadline is expected
+                    if Instant::now() < deadline {
+                        warn!("📡 SSE stream {} dropped: {}", connection, e);
+                        stats
+                            .lock()
+                            .await
+                            .record_failure("sse_stream_dropped".to_string());
+                    }
+                }
+            }
+            Err(error) => stats.lock().await.record_failure(error),
+        }
+
+        if Instant::now() + RECONNECT_DELAY < deadline {
+            sleep(RECONNECT_DELAY).await;
+        } else {
+            break;
+        }
+    }
+}
+
+async fn open(
+    client: &Client,
+    message: &LoadTestMessage,
+    deadline: Instant,
+    auth: Option<&Auth>,
+) -> Result<Response, String> {
+    // The stream outlives the client's per-request timeout by design
+    let mut request_builder = client
+        .get(&message.target_url)
+        .header(ACCEPT, "text/event-stream")
+        .timeout(deadline.saturating_duration_since(Instant::now()));
+
+    if let Some(headers) = &message.headers {
+        for (key, value) in headers {
+            request_builder = request_builder.header(key, value);
+        }
+    }
+
+    if let Some(auth) = auth {
+        let headers = auth
+            .headers()
+            .await
+            .map_err(|_| "auth_failed".to_string())?;
+        request_builder = request_builder.headers(headers);
+    }
+
+    let response = request_builder.send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("sse_rejected_{}", response.status().as_u16()));
+    }
+
+    Ok(response)
+}
+
+// Events end at a blank line; blocks without a `data` field (comments, keep-alives)
+// aren't dispatched by browsers either, so they aren't counted
+async fn read_events(
+    mut response: Response,
+    stats: &Mutex<Statistics>,
+    connection: u32,
+) -> Result<(), String> {
+    let mut buffer = EventBuffer::default();
+    let mut last_event: Option<Instant> = None;
+
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        for block in buffer.push(&chunk)? {
+            if !block.lines().any(|line| line.starts_with("data")) {
+                continue;
+            }
+
+            let now = Instant::now();
+            let gap = last_event.map(|last| now.duration_since(last).as_millis() as u64);
+            last_event = Some(now);
+
+            stats.lock().await.record_sse_event(connection, gap);
+        }
+    }
+
+    Ok(())
+}
+
+// Stream bytes not yet ended by a blank line. Kept as bytes so a character split
+// across chunks is only decoded once the event holding it is complete.
+#[derive(Default)]
+struct EventBuffer {
+    pending: Vec<u8>,
+    // Bytes already searched for the end of an event
+    scanned: usize,
+}
+
+impl EventBuffer {
+    // Takes the next chunk and returns the events it completed
+    fn push(&mut self, chunk: &[u8]) -> Result<Vec<String>, String> {
+        // CRLF line endings become LF; dropping every CR leaves the LFs to split on
+        self.pending
+            .extend(chunk.iter().filter(|&&byte| byte != b'\r'));
+
+        let mut events = vec![];
+        while let Some(end) = self.pending[self.scanned..]
+            .windows(2)
+            .position(|pair| pair == b"\n\n")
+        {
+            let block: Vec<u8> = self.pending.drain(..self.scanned + end + 2).collect();
+            events.push(String::from_utf8_lossy(&block).into_owned());
+            self.scanned = 0;
+        }
+        // The last byte may start the next blank line
+        self.scanned = self.pending.len().saturating_sub(1);
+
+        if self.pending.len() > MAX_EVENT_BYTES {
+            return Err(format!("event longer than {} bytes", MAX_EVENT_BYTES));
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_events_at_blank_lines() {
+        let mut buffer = EventBuffer::default();
+
+        assert_eq!(
+            buffer.push(b"data: a\n\ndata: b\n").unwrap(),
+            ["data: a\n\n"]
+        );
+        assert_eq!(
+            buffer.push(b"\n: keep-alive\r\n\r\n").unwrap(),
+            ["data: b\n\n", ": keep-alive\n\n"]
+        );
+    }
+
+    #[test]
+    fn keeps_characters_split_across_chunks_intact() {
+        let event = "data: caf\u{e9} \u{1F600}\n\n".as_bytes();
+        let mut buffer = EventBuffer::default();
+
+        let mut events = vec![];
+        for chunk in event.chunks(3) {
+            events.extend(buffer.push(chunk).unwrap());
+        }
+
+        assert_eq!(events, ["data: caf\u{e9} \u{1F600}\n\n"]);
+    }
+
+    #[test]
+    fn rejects_events_past_the_cap() {
+        let mut buffer = EventBuffer::default();
+        let chunk = vec![b'x'; MAX_EVENT_BYTES / 2 + 1];
+
+        assert!(buffer.push(&chunk).is_ok());
+        assert!(buffer.push(&chunk).is_err());
+    }
+}
@@ -1,14 +1,198 @@
+use crate::error_log::ErrorLog;
+use crate::tdigest::{TDigest, DEFAULT_COMPRESSION};
+use crate::tls::{self, Negotiated};
+use crate::trace::{Trace, TraceEntry};
+use crate::types::{
+    BodySizeSummary, DimensionBreakdown, EndpointResult, GroupResult, InFlightDistribution,
+    PercentileBackend, PhaseSummary, SseConnectionSummary, SseSummary, StatusCodeLatency,
+    TailBucket, TailEntry, TlsLatency,
+};
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hdrhistogram::serialization::{Deserializer, Serializer, V2DeflateSerializer};
 use hdrhistogram::Histogram;
-use std::collections::HashMap;
+use hyper::client::connect::HttpInfo;
+use reqwest::Response;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+// Distinct (endpoint, status) pairs tracked for tail composition; the rest share one bucket
+const MAX_TAIL_TAGS: usize = 64;
+const OTHER_TAG: &str = "other";
+
+// Distinct status codes given their own latency histogram; responses with others aren't
+// broken down
+const MAX_STATUS_CODE_HISTOGRAMS: usize = 32;
+
+// Distinct warnings kept for the result; later ones are dropped
+const MAX_WARNINGS: usize = 20;
+
+pub type Connections = Arc<Mutex<HashSet<(SocketAddr, SocketAddr)>>>;
+
+pub struct PhaseStats {
+    response_times: Histogram<u64>,
+    requests: u32,
+    failed: u32,
+}
+
+impl Default for PhaseStats {
+    fn default() -> Self {
+        Self {
+            response_times: latency_histogram(),
+            requests: 0,
+            failed: 0,
+        }
+    }
+}
 
 pub struct Statistics {
     pub total_requests: u32,
     pub successful_requests: u32,
     pub failed_requests: u32,
     pub response_times: Arc<Mutex<Histogram<u64>>>,
+    // Answers percentile queries instead of the histogram when the tdigest backend is chosen
+    pub latency_digest: Option<Arc<Mutex<TDigest>>>,
+    // Samples since the last time-series point, reset on every snapshot
+    pub interval_response_times: Arc<Mutex<Histogram<u64>>>,
+    pub queue_times: Arc<Mutex<Histogram<u64>>>,
+    // How far from its intended send time each scheduled request went out, in microseconds
+    pub schedule_errors: Arc<Mutex<Histogram<u64>>>,
+    // Time until response headers arrived, as opposed to the full response
+    pub ttfb_times: Arc<Mutex<Histogram<u64>>>,
+    // DNS lookup durations in microseconds, fed by the client's resolver
+    pub dns_times: Arc<Mutex<Histogram<u64>>>,
+    // Local and remote address of every connection a response came over, shared with
+    // the setup steps using the same client; the first `known_connections` were open
+    // before measurement
+    pub connections: Connections,
+    pub known_connections: usize,
     pub status_codes: Arc<Mutex<HashMap<u16, u32>>>,
+    // What each https origin negotiated in its setup probe handshake, and the latency of
+    // its responses
+    pub tls_origins: HashMap<String, Negotiated>,
+    pub tls_response_times: Arc<Mutex<HashMap<String, Histogram<u64>>>>,
+    // Latency of every response, passed or failed, by status code
+    pub status_code_response_times: Arc<Mutex<HashMap<u16, Histogram<u64>>>>,
     pub errors: Arc<Mutex<HashMap<String, u32>>>,
+    pub error_log: ErrorLog,
+    pub fault_injected_requests: u32,
+    pub fault_injected_failures: u32,
+    pub faults: Arc<Mutex<HashMap<String, u32>>>,
+    pub backends: Arc<Mutex<HashMap<String, u32>>>,
+    pub sticky_violations: u32,
+    pub validation_failures: u32,
+    pub validation_webhook_errors: u32,
+    pub retries: u32,
+    // Generated request body sizes in bytes
+    pub body_sizes: Arc<Mutex<Histogram<u64>>>,
+    pub endpoints: Arc<Mutex<HashMap<String, EndpointResult>>>,
+    // Latency of each endpoint's passed requests
+    pub endpoint_response_times: Arc<Mutex<HashMap<String, Histogram<u64>>>>,
+    pub groups: Arc<Mutex<HashMap<String, GroupResult>>>,
+    pub tagged_response_times: Arc<Mutex<HashMap<LatencyTag, Histogram<u64>>>>,
+    // Soft problems worth surfacing in the result, not just the log
+    pub warnings: Vec<String>,
+    // SSE events received per virtual user, and the gaps between them
+    pub sse_events: Arc<Mutex<HashMap<u32, u64>>>,
+    pub inter_event_times: Arc<Mutex<Histogram<u64>>>,
+    // Periodic checks of whether every shared concurrency permit was taken
+    pub pool_samples: u64,
+    pub pool_saturated_samples: u64,
+    pub interval_pool_saturated: bool,
+    // Requests in flight at each of those checks
+    pub in_flight: Histogram<u64>,
+    pub dimensions: Arc<Mutex<HashMap<String, HashMap<String, DimensionStats>>>>,
+    // How far behind schedule the most recent paced request was sent
+    pub schedule_lag_ms: Option<u64>,
+    // Per-request timeline, when the test asks for a trace
+    pub trace: Option<Trace>,
+    pub cache_hits: u32,
+    pub cache_misses: u32,
+    // Latency of the first successful response of the run
+    pub cold_start_ms: Option<u64>,
+    // Requests started before this aren't recorded, only counted as warm-up
+    pub measure_from: Option<Instant>,
+    pub warmup_requests: u32,
+    // Sum of interval mean latencies times interval length, over `latency_seconds` of
+    // intervals that had samples
+    pub weighted_latency: f64,
+    pub latency_seconds: f64,
+    // Whether the last completed request succeeded, and how often that has flipped
+    pub last_outcome: Option<bool>,
+    pub outcome_transitions: u32,
+    // Responses received, and how many of them came back with a Content-Encoding
+    pub responses_seen: u32,
+    pub compressed_responses: u32,
+    // Failures down to the network path (resets, refused or dropped connections) rather
+    // than to the target's answer
+    pub network_errors: u32,
+    // With resetHistogramPerPhase: the current phase's samples, and the closed phases'
+    // summaries in order
+    pub phase: Option<PhaseStats>,
+    pub phase_summaries: Vec<PhaseSummary>,
+    // Request and response body bytes, over `byte_samples` requests. Responses count as
+    // received on the wire, and again once decoded from any Content-Encoding.
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub bytes_decompressed: u64,
+    pub byte_samples: u32,
+}
+
+pub struct DimensionStats {
+    requests: u32,
+    successful: u32,
+    failed: u32,
+    response_times: Histogram<u64>,
+}
+
+pub struct IntervalLatency {
+    pub min: f64,
+    pub max: f64,
+    pub p95: f64,
+    // None when the interval had no samples
+    pub mean: Option<f64>,
+}
+
+// (endpoint, status code) a latency sample is attributed to
+type LatencyTag = (String, u16);
+
+fn latency_histogram() -> Histogram<u64> {
+    Histogram::<u64>::new_with_bounds(1, 60000, 3).unwrap()
+}
+
+// Compressed HDR V2 encoding, base64'd so it can travel inside JSON results
+pub fn encode_histogram(histogram: &Histogram<u64>) -> Result<String> {
+    let mut bytes = Vec::new();
+    V2DeflateSerializer::new()
+        .serialize(histogram, &mut bytes)
+        .map_err(|e| anyhow!("failed to serialize histogram: {:?}", e))?;
+    Ok(BASE64.encode(bytes))
+}
+
+// Adds `warning` unless it's already listed or the list is full
+pub fn push_warning(warnings: &mut Vec<String>, warning: String) {
+    if warnings.len() < MAX_WARNINGS && !warnings.contains(&warning) {
+        warnings.push(warning);
+    }
+}
+
+// Notes the connection `response` came over; the pool keeps a connection's address pair
+// for as long as it's open, so each new pair is a connect
+pub fn note_connection(connections: &Connections, response: &Response) {
+    if let Some(info) = response.extensions().get::<HttpInfo>() {
+        let pair = (info.local_addr(), info.remote_addr());
+        connections.lock().unwrap().insert(pair);
+    }
+}
+
+pub fn decode_histogram(encoded: &str) -> Result<Histogram<u64>> {
+    let bytes = BASE64.decode(encoded.trim())?;
+    Deserializer::new()
+        .deserialize(&mut bytes.as_slice())
+        .map_err(|e| anyhow!("invalid latencyHistogram: {:?}", e))
 }
 
 impl Statistics {
@@ -17,21 +201,235 @@ impl Statistics {
             total_requests: 0,
             successful_requests: 0,
             failed_requests: 0,
-            response_times: Arc::new(Mutex::new(
-                Histogram::<u64>::new_with_bounds(1, 60000, 3).unwrap(),
+            response_times: Arc::new(Mutex::new(latency_histogram())),
+            latency_digest: None,
+            interval_response_times: Arc::new(Mutex::new(latency_histogram())),
+            queue_times: Arc::new(Mutex::new(latency_histogram())),
+            schedule_errors: Arc::new(Mutex::new(
+                Histogram::<u64>::new_with_bounds(1, 60_000_000, 3).unwrap(),
+            )),
+            ttfb_times: Arc::new(Mutex::new(latency_histogram())),
+            dns_times: Arc::new(Mutex::new(
+                Histogram::<u64>::new_with_bounds(1, 60_000_000, 3).unwrap(),
             )),
+            connections: Arc::new(Mutex::new(HashSet::new())),
+            known_connections: 0,
             status_codes: Arc::new(Mutex::new(HashMap::new())),
+            tls_origins: HashMap::new(),
+            tls_response_times: Arc::new(Mutex::new(HashMap::new())),
+            status_code_response_times: Arc::new(Mutex::new(HashMap::new())),
             errors: Arc::new(Mutex::new(HashMap::new())),
+            error_log: ErrorLog::new(),
+            fault_injected_requests: 0,
+            fault_injected_failures: 0,
+            faults: Arc::new(Mutex::new(HashMap::new())),
+            backends: Arc::new(Mutex::new(HashMap::new())),
+            sticky_violations: 0,
+            validation_failures: 0,
+            validation_webhook_errors: 0,
+            retries: 0,
+            body_sizes: Arc::new(Mutex::new(Histogram::<u64>::new(3).unwrap())),
+            endpoints: Arc::new(Mutex::new(HashMap::new())),
+            endpoint_response_times: Arc::new(Mutex::new(HashMap::new())),
+            groups: Arc::new(Mutex::new(HashMap::new())),
+            tagged_response_times: Arc::new(Mutex::new(HashMap::new())),
+            warnings: Vec::new(),
+            sse_events: Arc::new(Mutex::new(HashMap::new())),
+            inter_event_times: Arc::new(Mutex::new(latency_histogram())),
+            pool_samples: 0,
+            pool_saturated_samples: 0,
+            interval_pool_saturated: false,
+            in_flight: Histogram::<u64>::new(3).unwrap(),
+            dimensions: Arc::new(Mutex::new(HashMap::new())),
+            schedule_lag_ms: None,
+            trace: None,
+            cache_hits: 0,
+            cache_misses: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            bytes_decompressed: 0,
+            byte_samples: 0,
+            cold_start_ms: None,
+            measure_from: None,
+            warmup_requests: 0,
+            weighted_latency: 0.0,
+            latency_seconds: 0.0,
+            last_outcome: None,
+            outcome_transitions: 0,
+            responses_seen: 0,
+            compressed_responses: 0,
+            network_errors: 0,
+            phase: None,
+            phase_summaries: vec![],
         }
     }
 
-    pub fn record_success(&mut self, response_time_ms: u64, status_code: u16) {
+    // Fresh statistics for setup requests, whose connections are noted in `connections`
+    pub fn sharing(connections: &Connections) -> Self {
+        Self {
+            connections: connections.clone(),
+            ..Self::new()
+        }
+    }
+
+    pub fn with_percentile_backend(backend: PercentileBackend) -> Self {
+        let mut stats = Self::new();
+        if backend == PercentileBackend::TDigest {
+            stats.latency_digest = Some(Arc::new(Mutex::new(TDigest::new(DEFAULT_COMPRESSION))));
+        }
+        stats
+    }
+
+    pub fn record_success(
+        &mut self,
+        endpoint: &str,
+        response_time_ms: u64,
+        ttfb_ms: u64,
+        status_code: u16,
+    ) {
+        self.total_requests += 1;
+        self.successful_requests += 1;
+        self.record_outcome(true);
+        self.record_phase(Some(response_time_ms), false);
+        self.ttfb_times.lock().unwrap().record(ttfb_ms).ok();
+
+        let recorded = self.response_times.lock().unwrap().record(response_time_ms);
+        if recorded.is_err() {
+            self.warn("latency above the 60s histogram range was left out of percentiles");
+        }
+
+        if let Some(digest) = &self.latency_digest {
+            digest.lock().unwrap().add(response_time_ms as f64);
+        }
+
+        let mut interval = self.interval_response_times.lock().unwrap();
+        interval.record(response_time_ms).ok();
+
+        self.record_status_code(status_code);
+
+        // Tag the sample so the slowest requests can be broken down later
+        let mut tagged = self.tagged_response_times.lock().unwrap();
+        let mut tag = (endpoint.to_string(), status_code);
+        if !tagged.contains_key(&tag) && tagged.len() >= MAX_TAIL_TAGS {
+            tag = (OTHER_TAG.to_string(), status_code);
+        }
+        tagged
+            .entry(tag)
+            .or_insert_with(latency_histogram)
+            .record(response_time_ms)
+            .ok();
+    }
+
+    // Counts the request as warm-up instead if it started before measurement did
+    pub fn record_warmup(&mut self, request_start: Instant) -> bool {
+        let warmup = self
+            .measure_from
+            .is_some_and(|measure_from| request_start < measure_from);
+        if warmup {
+            self.warmup_requests += 1;
+        }
+        warmup
+    }
+
+    // Keeps the run's first successful latency, returning whether this was it
+    pub fn record_cold_start(&mut self, response_time_ms: u64) -> bool {
+        if self.cold_start_ms.is_some() {
+            return false;
+        }
+        self.cold_start_ms = Some(response_time_ms);
+        true
+    }
+
+    // A success left out of every latency figure, e.g. an excluded cold start
+    pub fn record_success_without_latency(&mut self, status_code: u16) {
         self.total_requests += 1;
         self.successful_requests += 1;
+        self.record_outcome(true);
+        self.record_phase(None, false);
+        self.record_status_code(status_code);
+    }
 
-        let mut histogram = self.response_times.lock().unwrap();
-        histogram.record(response_time_ms).ok();
+    pub fn record_compression(&mut self, compressed: bool) {
+        self.responses_seen += 1;
+        if compressed {
+            self.compressed_responses += 1;
+        }
+    }
 
+    // Share of responses the server compressed, showing whether it honours
+    // Accept-Encoding; None before any response
+    pub fn get_compressed_response_fraction(&self) -> Option<f64> {
+        (self.responses_seen > 0)
+            .then(|| self.compressed_responses as f64 / self.responses_seen as f64)
+    }
+
+    pub fn record_network_error(&mut self) {
+        self.network_errors += 1;
+    }
+
+    // Share of requests lost to the network path; None before any request
+    pub fn get_network_error_fraction(&self) -> Option<f64> {
+        (self.total_requests > 0).then(|| self.network_errors as f64 / self.total_requests as f64)
+    }
+
+    // From here on, requests are also counted towards the current phase alone
+    pub fn start_phases(&mut self) {
+        self.phase = Some(PhaseStats::default());
+    }
+
+    // Closes the current phase, keeping its summary, and starts the next one afresh
+    pub fn end_phase(&mut self) {
+        let Some(phase) = self.phase.replace(PhaseStats::default()) else {
+            return;
+        };
+        self.phase_summaries.push(PhaseSummary {
+            requests: phase.requests,
+            failed: phase.failed,
+            average_response_time: phase.response_times.mean(),
+            p50_response_time: phase.response_times.value_at_percentile(50.0) as f64,
+            p95_response_time: phase.response_times.value_at_percentile(95.0) as f64,
+            p99_response_time: phase.response_times.value_at_percentile(99.0) as f64,
+        });
+    }
+
+    fn record_phase(&mut self, response_time_ms: Option<u64>, failed: bool) {
+        let Some(phase) = &mut self.phase else {
+            return;
+        };
+        phase.requests += 1;
+        if failed {
+            phase.failed += 1;
+        }
+        if let Some(response_time_ms) = response_time_ms {
+            phase.response_times.record(response_time_ms).ok();
+        }
+    }
+
+    // Counts flips between success and failure, in completion order
+    fn record_outcome(&mut self, success: bool) {
+        if self.last_outcome.is_some_and(|last| last != success) {
+            self.outcome_transitions += 1;
+        }
+        self.last_outcome = Some(success);
+    }
+
+    // Flips relative to what the same error rate would give if failures were scattered
+    // at random: near 1 for intermittent failures, near 0 when they come in one
+    // contiguous outage. None unless there were both successes and failures.
+    pub fn get_flakiness_score(&self) -> Option<f64> {
+        let requests = self.successful_requests + self.failed_requests;
+        if self.successful_requests == 0 || self.failed_requests == 0 {
+            return None;
+        }
+
+        let error_rate = self.failed_requests as f64 / requests as f64;
+        let expected = 2.0 * error_rate * (1.0 - error_rate) * (requests - 1) as f64;
+        Some(self.outcome_transitions as f64 / expected)
+    }
+
+    // Also called for responses that failed on their status, so the distribution
+    // shows every code the target returned
+    pub fn record_status_code(&self, status_code: u16) {
         let mut codes = self.status_codes.lock().unwrap();
         *codes.entry(status_code).or_insert(0) += 1;
     }
@@ -39,21 +437,367 @@ impl Statistics {
     pub fn record_failure(&mut self, error: String) {
         self.total_requests += 1;
         self.failed_requests += 1;
+        self.record_outcome(false);
+        self.record_phase(None, true);
+        self.error_log.record(&error);
 
         let mut errors = self.errors.lock().unwrap();
         *errors.entry(error).or_insert(0) += 1;
     }
 
+    // Fault-injected requests are tracked apart from the regular counters so
+    // their failures aren't attributed to the target
+    pub fn record_fault(&mut self, fault: &str, failed: bool) {
+        self.fault_injected_requests += 1;
+        if failed {
+            self.fault_injected_failures += 1;
+        }
+
+        let mut faults = self.faults.lock().unwrap();
+        *faults.entry(fault.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn record_backend(&mut self, backend: &str, violation: bool) {
+        if violation {
+            self.sticky_violations += 1;
+        }
+
+        let mut backends = self.backends.lock().unwrap();
+        *backends.entry(backend.to_string()).or_insert(0) += 1;
+    }
+
+    // `None` means the webhook itself couldn't be reached or answered garbage
+    pub fn record_validation(&mut self, passed: Option<bool>) {
+        match passed {
+            Some(true) => {}
+            Some(false) => self.validation_failures += 1,
+            None => self.validation_webhook_errors += 1,
+        }
+    }
+
+    pub fn record_retries(&mut self, retries: u32) {
+        self.retries += retries;
+    }
+
+    pub fn record_body_size(&mut self, bytes: usize) {
+        let mut histogram = self.body_sizes.lock().unwrap();
+        histogram.record(bytes as u64).ok();
+    }
+
+    // `status` is None when no response came back at all, `response_time_ms` when the
+    // request failed
+    pub fn record_endpoint(
+        &mut self,
+        name: &str,
+        status: Option<u16>,
+        response_time_ms: Option<u64>,
+        timed_out: bool,
+    ) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        let endpoint = endpoints.entry(name.to_string()).or_default();
+
+        endpoint.requests += 1;
+        match response_time_ms {
+            Some(response_time_ms) => {
+                endpoint.passed += 1;
+                self.endpoint_response_times
+                    .lock()
+                    .unwrap()
+                    .entry(name.to_string())
+                    .or_insert_with(latency_histogram)
+                    .record(response_time_ms)
+                    .ok();
+            }
+            None => endpoint.failed += 1,
+        }
+        if timed_out {
+            endpoint.timeouts += 1;
+        }
+        if let Some(status) = status {
+            *endpoint.status_code_distribution.entry(status).or_insert(0) += 1;
+        }
+    }
+
+    pub fn record_group(&mut self, group: &str, passed: bool) {
+        let mut groups = self.groups.lock().unwrap();
+        let group = groups.entry(group.to_string()).or_default();
+
+        group.requests += 1;
+        if passed {
+            group.successful += 1;
+        } else {
+            group.failed += 1;
+        }
+    }
+
+    // Sampled whenever a request of the group gets its permit
+    pub fn record_group_in_flight(&mut self, group: &str, in_flight: u32, concurrency: u32) {
+        let mut groups = self.groups.lock().unwrap();
+        let group = groups.entry(group.to_string()).or_default();
+
+        group.concurrency = concurrency;
+        group.peak_in_flight = group.peak_in_flight.max(in_flight);
+    }
+
+    // Time a request spent waiting behind the pacing schedule before it was sent
+    pub fn record_queue_time(&mut self, queue_time_ms: u64) {
+        self.schedule_lag_ms = Some(queue_time_ms);
+        let recorded = self.queue_times.lock().unwrap().record(queue_time_ms);
+        if recorded.is_err() {
+            self.warn("queue time above the 60s histogram range was left out of percentiles");
+        }
+    }
+
+    // Early and late sends alike
+    pub fn record_schedule_error(&mut self, intended: Instant) {
+        let now = Instant::now();
+        let error = now
+            .saturating_duration_since(intended)
+            .max(intended.saturating_duration_since(now));
+        let recorded = self
+            .schedule_errors
+            .lock()
+            .unwrap()
+            .record(error.as_micros() as u64);
+        if recorded.is_err() {
+            self.warn("schedule error above the 60s histogram range was left out of percentiles");
+        }
+    }
+
+    // In milliseconds; None when no request followed a schedule
+    pub fn get_schedule_error_percentile(&self, percentile: f64) -> Option<f64> {
+        let histogram = self.schedule_errors.lock().unwrap();
+        (!histogram.is_empty()).then(|| histogram.value_at_percentile(percentile) as f64 / 1000.0)
+    }
+
+    // `response_time_ms` is None for failed requests
+    pub fn record_dimensions(
+        &mut self,
+        tags: &HashMap<String, String>,
+        response_time_ms: Option<u64>,
+    ) {
+        let mut dimensions = self.dimensions.lock().unwrap();
+
+        for (dimension, value) in tags {
+            let stats = dimensions
+                .entry(dimension.clone())
+                .or_default()
+                .entry(value.clone())
+                .or_insert_with(|| DimensionStats {
+                    requests: 0,
+                    successful: 0,
+                    failed: 0,
+                    response_times: latency_histogram(),
+                });
+
+            stats.requests += 1;
+            match response_time_ms {
+                Some(response_time_ms) => {
+                    stats.successful += 1;
+                    stats.response_times.record(response_time_ms).ok();
+                }
+                None => stats.failed += 1,
+            }
+        }
+    }
+
+    pub fn record_bytes(&mut self, sent: u64, received: u64, decompressed: u64) {
+        self.bytes_sent += sent;
+        self.bytes_received += received;
+        self.bytes_decompressed += decompressed;
+        self.byte_samples += 1;
+    }
+
+    // Average request and response body sizes; None before any request
+    pub fn get_average_bytes(&self) -> Option<(f64, f64)> {
+        (self.byte_samples > 0).then(|| {
+            let samples = self.byte_samples as f64;
+            (
+                self.bytes_sent as f64 / samples,
+                self.bytes_received as f64 / samples,
+            )
+        })
+    }
+
+    // Bytes moved in both directions, failed requests included, per successful request
+    pub fn get_bytes_per_success(&self) -> Option<f64> {
+        (self.successful_requests > 0).then(|| {
+            (self.bytes_sent + self.bytes_received) as f64 / self.successful_requests as f64
+        })
+    }
+
+    pub fn record_cache_lookup(&mut self, hit: bool) {
+        if hit {
+            self.cache_hits += 1;
+        } else {
+            self.cache_misses += 1;
+        }
+    }
+
+    // None when no lookups were made
+    pub fn get_cache_hit_rate(&self) -> Option<f64> {
+        match self.cache_hits + self.cache_misses {
+            0 => None,
+            lookups => Some(self.cache_hits as f64 / lookups as f64),
+        }
+    }
+
+    // Whether another request would still make it into the trace
+    pub fn wants_trace(&self) -> bool {
+        self.trace.as_ref().is_some_and(Trace::has_room)
+    }
+
+    pub fn record_trace(&mut self, entry: TraceEntry) {
+        if let Some(trace) = &mut self.trace {
+            trace.record(entry);
+        }
+    }
+
+    pub fn record_pool_sample(&mut self, saturated: bool, in_flight: u32) {
+        self.pool_samples += 1;
+        self.in_flight.record(in_flight as u64).ok();
+        if saturated {
+            self.pool_saturated_samples += 1;
+            self.interval_pool_saturated = true;
+        }
+    }
+
+    // Whether the pool was saturated at any check since the previous call
+    pub fn take_interval_pool_saturated(&mut self) -> bool {
+        std::mem::take(&mut self.interval_pool_saturated)
+    }
+
+    pub fn record_sse_open(&mut self, connection: u32) {
+        self.sse_events
+            .lock()
+            .unwrap()
+            .entry(connection)
+            .or_insert(0);
+    }
+
+    // `gap_ms` is None for the first event of a stream
+    pub fn record_sse_event(&mut self, connection: u32, gap_ms: Option<u64>) {
+        *self
+            .sse_events
+            .lock()
+            .unwrap()
+            .entry(connection)
+            .or_insert(0) += 1;
+        if let Some(gap_ms) = gap_ms {
+            self.inter_event_times.lock().unwrap().record(gap_ms).ok();
+        }
+    }
+
+    pub fn warn(&mut self, warning: &str) {
+        push_warning(&mut self.warnings, warning.to_string());
+    }
+
+    // Min, max, p95 and mean latency of the samples since the previous call
+    pub fn take_interval_latency(&mut self) -> IntervalLatency {
+        let mut histogram = self.interval_response_times.lock().unwrap();
+        let latency = IntervalLatency {
+            min: histogram.min() as f64,
+            max: histogram.max() as f64,
+            p95: histogram.value_at_percentile(95.0) as f64,
+            mean: (!histogram.is_empty()).then(|| histogram.mean()),
+        };
+        histogram.reset();
+        latency
+    }
+
     pub fn get_percentile(&self, percentile: f64) -> f64 {
+        if let Some(digest) = &self.latency_digest {
+            return digest.lock().unwrap().quantile(percentile / 100.0);
+        }
+
+        let histogram = self.response_times.lock().unwrap();
+        histogram.value_at_percentile(percentile) as f64
+    }
+
+    pub fn get_latency_samples(&self) -> u64 {
+        self.response_times.lock().unwrap().len()
+    }
+
+    pub fn get_latency_histogram(&self) -> Option<String> {
         let histogram = self.response_times.lock().unwrap();
+        encode_histogram(&histogram).ok()
+    }
+
+    pub fn get_latency_digest(&self) -> Option<TDigest> {
+        self.latency_digest.as_ref().map(|digest| {
+            let mut digest = digest.lock().unwrap();
+            digest.compress();
+            digest.clone()
+        })
+    }
+
+    pub fn get_queue_time_percentile(&self, percentile: f64) -> f64 {
+        let histogram = self.queue_times.lock().unwrap();
+        histogram.value_at_percentile(percentile) as f64
+    }
+
+    pub fn get_ttfb_percentile(&self, percentile: f64) -> f64 {
+        let histogram = self.ttfb_times.lock().unwrap();
         histogram.value_at_percentile(percentile) as f64
     }
 
+    pub fn get_dns_time_percentile(&self, percentile: f64) -> f64 {
+        let histogram = self.dns_times.lock().unwrap();
+        histogram.value_at_percentile(percentile) as f64 / 1000.0
+    }
+
+    pub fn get_connections_established(&self) -> u64 {
+        let connections = self.connections.lock().unwrap().len();
+        connections.saturating_sub(self.known_connections) as u64
+    }
+
+    pub fn get_body_size_summary(&self) -> Option<BodySizeSummary> {
+        let histogram = self.body_sizes.lock().unwrap();
+        if histogram.is_empty() {
+            return None;
+        }
+
+        Some(BodySizeSummary {
+            count: histogram.len(),
+            min: histogram.min(),
+            max: histogram.max(),
+            mean: histogram.mean(),
+            p50: histogram.value_at_percentile(50.0),
+            p95: histogram.value_at_percentile(95.0),
+        })
+    }
+
+    pub fn get_in_flight_distribution(&self) -> Option<InFlightDistribution> {
+        if self.in_flight.is_empty() {
+            return None;
+        }
+
+        Some(InFlightDistribution {
+            samples: self.in_flight.len(),
+            mean: self.in_flight.mean(),
+            p50: self.in_flight.value_at_percentile(50.0),
+            p90: self.in_flight.value_at_percentile(90.0),
+            p99: self.in_flight.value_at_percentile(99.0),
+            max: self.in_flight.max(),
+        })
+    }
+
     pub fn get_average(&self) -> f64 {
         let histogram = self.response_times.lock().unwrap();
         histogram.mean()
     }
 
+    pub fn record_interval_mean(&mut self, mean: f64, interval_seconds: f64) {
+        self.weighted_latency += mean * interval_seconds;
+        self.latency_seconds += interval_seconds;
+    }
+
+    // Mean of the per-interval averages weighted by interval length, so busy stretches
+    // count no more than quiet ones; None before the first interval with samples
+    pub fn get_time_weighted_average(&self) -> Option<f64> {
+        (self.latency_seconds > 0.0).then(|| self.weighted_latency / self.latency_seconds)
+    }
+
     pub fn get_min(&self) -> f64 {
         let histogram = self.response_times.lock().unwrap();
         histogram.min() as f64
@@ -74,6 +818,218 @@ impl Statistics {
         errors.clone()
     }
 
+    pub fn get_faults(&self) -> HashMap<String, u32> {
+        let faults = self.faults.lock().unwrap();
+        faults.clone()
+    }
+
+    pub fn get_backends(&self) -> HashMap<String, u32> {
+        let backends = self.backends.lock().unwrap();
+        backends.clone()
+    }
+
+    // Only origins whose handshake was probed during setup are recorded
+    pub fn record_tls_latency(&mut self, url: &str, response_time_ms: u64) {
+        let Some(origin) = tls::origin(url).filter(|o| self.tls_origins.contains_key(o)) else {
+            return;
+        };
+        self.tls_response_times
+            .lock()
+            .unwrap()
+            .entry(origin)
+            .or_insert_with(latency_histogram)
+            .record(response_time_ms)
+            .ok();
+    }
+
+    // Origins' latencies merged by the TLS version their probe handshake negotiated
+    pub fn get_tls_latencies(&self) -> HashMap<String, TlsLatency> {
+        let histograms = self.tls_response_times.lock().unwrap();
+        let mut versions: HashMap<&str, (Histogram<u64>, Vec<String>)> = HashMap::new();
+
+        for (origin, histogram) in histograms.iter() {
+            let Some(negotiated) = self.tls_origins.get(origin) else {
+                continue;
+            };
+            let (merged, ciphers) = versions
+                .entry(&negotiated.version)
+                .or_insert_with(|| (latency_histogram(), Vec::new()));
+            merged.add(histogram).ok();
+            ciphers.push(negotiated.cipher.clone());
+        }
+
+        versions
+            .into_iter()
+            .map(|(version, (histogram, mut cipher_suites))| {
+                cipher_suites.sort();
+                cipher_suites.dedup();
+                let latency = TlsLatency {
+                    count: histogram.len(),
+                    average_response_time: histogram.mean(),
+                    p50_response_time: histogram.value_at_percentile(50.0) as f64,
+                    p95_response_time: histogram.value_at_percentile(95.0) as f64,
+                    p99_response_time: histogram.value_at_percentile(99.0) as f64,
+                    cipher_suites,
+                };
+                (version.to_string(), latency)
+            })
+            .collect()
+    }
+
+    pub fn record_status_latency(&mut self, status_code: u16, response_time_ms: u64) {
+        let mut histograms = self.status_code_response_times.lock().unwrap();
+        if !histograms.contains_key(&status_code) && histograms.len() >= MAX_STATUS_CODE_HISTOGRAMS
+        {
+            drop(histograms);
+            self.warn("too many distinct status codes; later ones have no latency breakdown");
+            return;
+        }
+        histograms
+            .entry(status_code)
+            .or_insert_with(latency_histogram)
+            .record(response_time_ms)
+            .ok();
+    }
+
+    pub fn get_status_code_latencies(&self) -> HashMap<u16, StatusCodeLatency> {
+        let histograms = self.status_code_response_times.lock().unwrap();
+
+        histograms
+            .iter()
+            .map(|(status_code, histogram)| {
+                let latency = StatusCodeLatency {
+                    count: histogram.len(),
+                    average_response_time: histogram.mean(),
+                    p50_response_time: histogram.value_at_percentile(50.0) as f64,
+                    p95_response_time: histogram.value_at_percentile(95.0) as f64,
+                    p99_response_time: histogram.value_at_percentile(99.0) as f64,
+                };
+                (*status_code, latency)
+            })
+            .collect()
+    }
+
+    pub fn get_endpoints(&self) -> HashMap<String, EndpointResult> {
+        let mut endpoints = self.endpoints.lock().unwrap().clone();
+        let response_times = self.endpoint_response_times.lock().unwrap();
+
+        for (name, endpoint) in endpoints.iter_mut() {
+            let Some(histogram) = response_times.get(name) else {
+                continue;
+            };
+            endpoint.average_response_time = histogram.mean();
+            endpoint.p50_response_time = histogram.value_at_percentile(50.0) as f64;
+            endpoint.p95_response_time = histogram.value_at_percentile(95.0) as f64;
+            endpoint.p99_response_time = histogram.value_at_percentile(99.0) as f64;
+        }
+
+        endpoints
+    }
+
+    pub fn get_groups(&self) -> HashMap<String, GroupResult> {
+        self.groups.lock().unwrap().clone()
+    }
+
+    pub fn get_dimension_breakdown(&self) -> HashMap<String, HashMap<String, DimensionBreakdown>> {
+        let dimensions = self.dimensions.lock().unwrap();
+
+        dimensions
+            .iter()
+            .map(|(dimension, values)| {
+                let values = values
+                    .iter()
+                    .map(|(value, stats)| {
+                        let breakdown = DimensionBreakdown {
+                            requests: stats.requests,
+                            successful: stats.successful,
+                            failed: stats.failed,
+                            average_response_time: stats.response_times.mean(),
+                            p50_response_time: stats.response_times.value_at_percentile(50.0)
+                                as f64,
+                            p95_response_time: stats.response_times.value_at_percentile(95.0)
+                                as f64,
+                            p99_response_time: stats.response_times.value_at_percentile(99.0)
+                                as f64,
+                        };
+                        (value.clone(), breakdown)
+                    })
+                    .collect();
+                (dimension.clone(), values)
+            })
+            .collect()
+    }
+
+    pub fn get_pool_saturation_percent(&self) -> f64 {
+        match self.pool_samples {
+            0 => 0.0,
+            samples => self.pool_saturated_samples as f64 * 100.0 / samples as f64,
+        }
+    }
+
+    pub fn get_sse_summary(&self, elapsed_seconds: f64) -> SseSummary {
+        let events = self.sse_events.lock().unwrap();
+        let gaps = self.inter_event_times.lock().unwrap();
+        let rate = |events: u64| match elapsed_seconds {
+            seconds if seconds > 0.0 => events as f64 / seconds,
+            _ => 0.0,
+        };
+
+        let mut per_connection: Vec<SseConnectionSummary> = events
+            .iter()
+            .map(|(connection, events)| SseConnectionSummary {
+                connection: *connection,
+                events: *events,
+                event_rate: rate(*events),
+            })
+            .collect();
+        per_connection.sort_by_key(|summary| summary.connection);
+
+        let events_received = events.values().sum();
+        SseSummary {
+            connections: events.len() as u32,
+            events_received,
+            event_rate: rate(events_received),
+            inter_event_p50: gaps.value_at_percentile(50.0) as f64,
+            inter_event_p95: gaps.value_at_percentile(95.0) as f64,
+            inter_event_p99: gaps.value_at_percentile(99.0) as f64,
+            per_connection,
+        }
+    }
+
+    // Which endpoint/status pairs make up the requests at or above `percentile`
+    pub fn get_tail_composition(&self, percentile: f64) -> TailBucket {
+        let threshold = self
+            .response_times
+            .lock()
+            .unwrap()
+            .value_at_percentile(percentile);
+        let tagged = self.tagged_response_times.lock().unwrap();
+
+        let mut entries: Vec<TailEntry> = tagged
+            .iter()
+            .filter(|(_, histogram)| histogram.max() >= threshold)
+            .map(|((endpoint, status_code), histogram)| TailEntry {
+                endpoint: endpoint.clone(),
+                status_code: *status_code,
+                count: histogram.count_between(threshold, histogram.max()),
+                share: 0.0,
+            })
+            .filter(|entry| entry.count > 0)
+            .collect();
+
+        let total: u64 = entries.iter().map(|entry| entry.count).sum();
+        for entry in &mut entries {
+            entry.share = entry.count as f64 / total as f64;
+        }
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.count));
+
+        TailBucket {
+            percentile,
+            threshold_ms: threshold as f64,
+            entries,
+        }
+    }
+
     pub fn error_rate(&self) -> f64 {
         if self.total_requests == 0 {
             0.0
@@ -82,4 +1038,3 @@ impl Statistics {
         }
     }
 }
-
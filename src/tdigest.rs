@@ -0,0 +1,226 @@
+// Merging t-digest: a compact latency sketch whose serialized centroids can be
+// combined across shards without going back to the raw samples
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_COMPRESSION: f64 = 100.0;
+
+// Samples collected before they're folded into the centroids
+const BUFFER_SIZE: usize = 500;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Centroid {
+    pub mean: f64,
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TDigest {
+    pub compression: f64,
+    pub count: f64,
+    pub min: f64,
+    pub max: f64,
+    pub centroids: Vec<Centroid>,
+    #[serde(skip)]
+    buffer: Vec<f64>,
+}
+
+impl TDigest {
+    pub fn new(compression: f64) -> Self {
+        Self {
+            compression,
+            count: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            centroids: vec![],
+            buffer: vec![],
+        }
+    }
+
+    pub fn add(&mut self, value: f64) {
+        self.buffer.push(value);
+        if self.buffer.len() >= BUFFER_SIZE {
+            self.compress();
+        }
+    }
+
+    // Folds another digest in; the result matches digesting both sample sets together
+    // to within the compression's accuracy. The worker only produces digests; merging
+    // happens on whatever combines shard results, so only the tests call it here
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn merge(&mut self, other: &TDigest) {
+        let mut other = other.clone();
+        other.compress();
+
+        self.compress();
+        self.centroids.extend(other.centroids);
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.rebuild();
+    }
+
+    // Value at quantile `q` (0.0 - 1.0), interpolated between centroid centres
+    pub fn quantile(&mut self, q: f64) -> f64 {
+        self.compress();
+
+        let (first, last) = match (self.centroids.first(), self.centroids.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return 0.0,
+        };
+        if self.centroids.len() == 1 {
+            return first.mean;
+        }
+
+        let target = q.clamp(0.0, 1.0) * self.count;
+        if target <= first.weight / 2.0 {
+            return interpolate(self.min, first.mean, target / (first.weight / 2.0));
+        }
+
+        let mut cumulative = 0.0;
+        for pair in self.centroids.windows(2) {
+            let (left, right) = (pair[0], pair[1]);
+            let left_centre = cumulative + left.weight / 2.0;
+            let right_centre = cumulative + left.weight + right.weight / 2.0;
+            if target <= right_centre {
+                let fraction = (target - left_centre) / (right_centre - left_centre);
+                return interpolate(left.mean, right.mean, fraction);
+            }
+            cumulative += left.weight;
+        }
+
+        let last_centre = self.count - last.weight / 2.0;
+        interpolate(
+            last.mean,
+            self.max,
+            (target - last_centre) / (last.weight / 2.0),
+        )
+    }
+
+    // Folds buffered samples into the centroids, e.g. before serializing
+    pub fn compress(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        for value in self.buffer.drain(..) {
+            self.centroids.push(Centroid {
+                mean: value,
+                weight: 1.0,
+            });
+            self.count += 1.0;
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.rebuild();
+    }
+
+    // Re-merges sorted centroids, letting each grow only as large as its quantile
+    // allows so the tails stay finely resolved
+    fn rebuild(&mut self) {
+        self.centroids.sort_by(|a, b| a.mean.total_cmp(&b.mean));
+
+        let mut merged: Vec<Centroid> = Vec::with_capacity(self.centroids.len());
+        let mut cumulative = 0.0;
+
+        for centroid in self.centroids.drain(..) {
+            if let Some(last) = merged.last_mut() {
+                let combined = last.weight + centroid.weight;
+                let q = (cumulative + combined / 2.0) / self.count;
+                let limit = 4.0 * self.count * q * (1.0 - q) / self.compression;
+
+                if combined <= limit {
+                    last.mean += (centroid.mean - last.mean) * centroid.weight / combined;
+                    last.weight = combined;
+                    continue;
+                }
+                cumulative += last.weight;
+            }
+            merged.push(centroid);
+        }
+
+        self.centroids = merged;
+    }
+}
+
+fn interpolate(from: f64, to: f64, fraction: f64) -> f64 {
+    from + (to - from) * fraction.clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest_of(values: impl Iterator<Item = u32>) -> TDigest {
+        let mut digest = TDigest::new(DEFAULT_COMPRESSION);
+        for value in values {
+            digest.add(value as f64);
+        }
+        digest
+    }
+
+    // 1..=n in a scrambled but deterministic order
+    fn scrambled(n: u32) -> impl Iterator<Item = u32> {
+        (0..n).map(move |i| (i * 7919) % n + 1)
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} isn't within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn quantiles_of_uniform_samples() {
+        let mut digest = digest_of(scrambled(10_000));
+
+        assert_eq!(digest.quantile(0.0), 1.0);
+        assert_eq!(digest.quantile(1.0), 10_000.0);
+        for (q, expected) in [(0.5, 5_000.0), (0.95, 9_500.0), (0.99, 9_900.0)] {
+            assert_close(digest.quantile(q), expected, 50.0);
+        }
+    }
+
+    #[test]
+    fn empty_and_single_sample_digests() {
+        assert_eq!(TDigest::new(DEFAULT_COMPRESSION).quantile(0.5), 0.0);
+
+        let mut digest = digest_of([42].into_iter());
+        assert_eq!(digest.quantile(0.01), 42.0);
+        assert_eq!(digest.quantile(0.99), 42.0);
+    }
+
+    #[test]
+    fn merge_matches_digesting_everything_at_once() {
+        let mut whole = digest_of(scrambled(10_000));
+        let mut evens = digest_of(scrambled(10_000).filter(|value| value % 2 == 0));
+        let odds = digest_of(scrambled(10_000).filter(|value| value % 2 == 1));
+
+        evens.merge(&odds);
+
+        assert_eq!(evens.count, 10_000.0);
+        assert_eq!(evens.min, 1.0);
+        assert_eq!(evens.max, 10_000.0);
+        for q in [0.1, 0.5, 0.9, 0.99] {
+            assert_close(evens.quantile(q), whole.quantile(q), 50.0);
+        }
+    }
+
+    #[test]
+    fn merge_survives_serialization() {
+        let mut first = digest_of(1..=1_000);
+        let second = digest_of(1_001..=2_000);
+        let mut second_json = second.clone();
+        second_json.compress();
+        let shipped: TDigest =
+            serde_json::from_str(&serde_json::to_string(&second_json).unwrap()).unwrap();
+
+        first.merge(&shipped);
+
+        assert_eq!(first.count, 2_000.0);
+        assert_close(first.quantile(0.5), 1_000.0, 20.0);
+    }
+}
@@ -0,0 +1,105 @@
+// Per-request placeholders in body strings and header values: `{{uuid}}`,
+// `{{timestamp}}` (Unix milliseconds) and `{{randomInt(min,max)}}` (inclusive), filled
+// in afresh for every request so no two payloads are identical
+use chrono::Utc;
+use rand::Rng;
+use regex::{Captures, Regex};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::sync::LazyLock;
+use uuid::Uuid;
+
+static PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\{\{\s*(uuid|timestamp|randomInt\(\s*(-?\d+)\s*,\s*(-?\d+)\s*\))\s*\}\}").unwrap()
+});
+
+// Other `{{...}}` placeholders, and a randomInt whose bounds are reversed or out of
+// range, are left as they are
+pub fn render(text: &str) -> Cow<'_, str> {
+    if !text.contains("{{") {
+        return Cow::Borrowed(text);
+    }
+
+    PLACEHOLDER.replace_all(text, |captures: &Captures| match &captures[1] {
+        "uuid" => Uuid::new_v4().to_string(),
+        "timestamp" => Utc::now().timestamp_millis().to_string(),
+        _ => match (captures[2].parse::<i64>(), captures[3].parse::<i64>()) {
+            (Ok(min), Ok(max)) if min <= max => rand::thread_rng().gen_range(min..=max).to_string(),
+            _ => captures[0].to_string(),
+        },
+    })
+}
+
+pub fn render_json(value: &Value) -> Value {
+    match value {
+        Value::String(text) => Value::String(render(text).into_owned()),
+        Value::Array(items) => Value::Array(items.iter().map(render_json).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, field)| (name.clone(), render_json(field)))
+                .collect(),
+        ),
+        _ => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn text_without_placeholders_is_borrowed() {
+        assert!(matches!(
+            render("plain {text}"),
+            Cow::Borrowed("plain {text}")
+        ));
+    }
+
+    #[test]
+    fn fills_each_placeholder_afresh() {
+        let rendered = render("{{uuid}} {{ uuid }}");
+        let (first, second) = rendered.split_once(' ').unwrap();
+        assert!(Uuid::parse_str(first).is_ok());
+        assert!(Uuid::parse_str(second).is_ok());
+        assert_ne!(first, second);
+
+        let before = Utc::now().timestamp_millis();
+        let timestamp: i64 = render("{{timestamp}}").parse().unwrap();
+        assert!((before..=Utc::now().timestamp_millis()).contains(&timestamp));
+    }
+
+    #[test]
+    fn random_ints_stay_within_inclusive_bounds() {
+        for _ in 0..200 {
+            let value: i64 = render("{{randomInt(-2, 2)}}").parse().unwrap();
+            assert!((-2..=2).contains(&value));
+        }
+        assert_eq!(render("{{randomInt(7,7)}}"), "7");
+    }
+
+    #[test]
+    fn leaves_unknown_and_invalid_placeholders_alone() {
+        assert_eq!(render("{{userId}}"), "{{userId}}");
+        assert_eq!(render("{{randomInt(5,1)}}"), "{{randomInt(5,1)}}");
+        assert_eq!(
+            render("{{randomInt(1,99999999999999999999)}}"),
+            "{{randomInt(1,99999999999999999999)}}"
+        );
+    }
+
+    #[test]
+    fn renders_strings_nested_in_json() {
+        let rendered = render_json(&json!({
+            "id": "{{randomInt(3,3)}}",
+            "tags": ["a-{{randomInt(1,1)}}", 5],
+            "nested": { "flag": true, "n": "{{randomInt(0,0)}}" },
+        }));
+
+        assert_eq!(
+            rendered,
+            json!({ "id": "3", "tags": ["a-1", 5], "nested": { "flag": true, "n": "0" } })
+        );
+    }
+}
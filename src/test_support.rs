@@ -0,0 +1,884 @@
+// Shared by the unit tests: test messages built from the few fields a test cares
+// about, a scripted HTTP/1.1 server, HTTP/2 and HTTPS ones and a minimal AMQP broker on
+// local ports, and outboxes with no broker behind them
+
+use crate::load_test::Outbox;
+use crate::types::LoadTestMessage;
+use amq_protocol::frame::{gen_frame, parse_frame, AMQPContentHeader, AMQPFrame, WriteContext};
+use amq_protocol::protocol::{basic, channel, connection, exchange, queue, AMQPClass};
+use amq_protocol::types::{ChannelId, FieldTable};
+use futures::future::BoxFuture;
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use lapin::BasicProperties;
+use openssl::asn1::Asn1Time;
+use openssl::bn::BigNum;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::ssl::{SslAcceptor, SslMethod, SslVersion};
+use openssl::x509::extension::SubjectAlternativeName;
+use openssl::x509::{X509Builder, X509NameBuilder, X509};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::convert::Infallible;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+
+// A single request to an unreachable target, with `fields` set on top
+pub fn message(fields: Value) -> LoadTestMessage {
+    let mut message = json!({
+        "testId": "test",
+        "targetUrl": "http://127.0.0.1:9/",
+        "method": "GET",
+        "concurrentUsers": 1,
+        "totalRequests": 1,
+        "durationSeconds": 0,
+        "requestsPerSecond": 0,
+    });
+    if let (Some(message), Value::Object(fields)) = (message.as_object_mut(), fields) {
+        message.extend(fields);
+    }
+    serde_json::from_value(message).unwrap()
+}
+
+// A request as the server received it
+#[derive(Debug, Clone)]
+pub struct Received {
+    // Position among all the requests the server received
+    pub index: usize,
+    pub method: String,
+    pub path: String,
+    // Names lowercased, in the order they were sent
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Received {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn body_text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+// What the server answers a request with
+#[derive(Debug, Clone)]
+pub struct Reply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    // Before the status line
+    head_delay: Duration,
+    // Between the headers and the body
+    body_delay: Duration,
+    // Sent with Transfer-Encoding: chunked, in chunks of this size, instead of a
+    // Content-Length
+    chunk_size: Option<usize>,
+    // Between chunks, e.g. to space out streamed events
+    chunk_delay: Duration,
+    // Resets the connection instead of answering
+    reset: bool,
+}
+
+impl Reply {
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: vec![],
+            head_delay: Duration::ZERO,
+            body_delay: Duration::ZERO,
+            chunk_size: None,
+            chunk_delay: Duration::ZERO,
+            reset: false,
+        }
+    }
+
+    // No answer at all: the connection is reset once the request is read
+    pub fn reset() -> Self {
+        Self {
+            reset: true,
+            ..Self::ok()
+        }
+    }
+
+    pub fn ok() -> Self {
+        Self::status(200)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn head_delay(mut self, delay: Duration) -> Self {
+        self.head_delay = delay;
+        self
+    }
+
+    pub fn body_delay(mut self, delay: Duration) -> Self {
+        self.body_delay = delay;
+        self
+    }
+
+    pub fn chunked(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    // Chunked, with `delay` before each chunk after the first
+    pub fn streamed(mut self, chunk_size: usize, delay: Duration) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self.chunk_delay = delay;
+        self
+    }
+}
+
+type Handler = dyn Fn(&Received) -> Reply + Send + Sync;
+
+// Answers every request with whatever the handler returns for it, keeping
+// connections alive until the client closes them
+pub struct MockServer {
+    address: String,
+    received: Arc<Mutex<Vec<Received>>>,
+}
+
+impl MockServer {
+    pub async fn start(handler: impl Fn(&Received) -> Reply + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = Self {
+            address: listener.local_addr().unwrap().to_string(),
+            received: Arc::default(),
+        };
+
+        let handler: Arc<Handler> = Arc::new(handler);
+        let received = server.received.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(serve(socket, handler.clone(), received.clone()));
+            }
+        });
+
+        server
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.address, path)
+    }
+
+    // Every request so far, in the order they came in
+    pub fn received(&self) -> Vec<Received> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+async fn serve(mut socket: TcpStream, handler: Arc<Handler>, received: Arc<Mutex<Vec<Received>>>) {
+    let mut buffer = Vec::new();
+
+    while let Some(mut request) = read_request(&mut socket, &mut buffer).await {
+        {
+            let mut received = received.lock().unwrap();
+            request.index = received.len();
+            received.push(request.clone());
+        }
+        let reply = handler(&request);
+        if reply.reset {
+            // Closing with a zero linger sends a RST rather than a FIN
+            socket.set_zero_linger().ok();
+            return;
+        }
+
+        let reason = StatusCode::from_u16(reply.status)
+            .ok()
+            .and_then(|status| status.canonical_reason())
+            .unwrap_or("");
+        let mut head = format!("HTTP/1.1 {} {}\r\n", reply.status, reason);
+        for (name, value) in &reply.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        match reply.chunk_size {
+            Some(_) => head.push_str("transfer-encoding: chunked\r\n"),
+            None => head.push_str(&format!("content-length: {}\r\n", reply.body.len())),
+        }
+        let close = request.header("connection") == Some("close");
+        if close {
+            head.push_str("connection: close\r\n");
+        }
+        head.push_str("\r\n");
+        sleep(reply.head_delay).await;
+        if socket.write_all(head.as_bytes()).await.is_err() {
+            return;
+        }
+
+        if request.method != "HEAD" {
+            sleep(reply.body_delay).await;
+            let parts = match reply.chunk_size {
+                Some(size) => chunk(&reply.body, size),
+                None => vec![reply.body],
+            };
+            for (i, part) in parts.iter().enumerate() {
+                if i > 0 {
+                    sleep(reply.chunk_delay).await;
+                }
+                if socket.write_all(part).await.is_err() {
+                    return;
+                }
+            }
+        }
+
+        if close {
+            return;
+        }
+    }
+}
+
+// A body in chunked transfer coding, one part per chunk and the empty chunk last
+fn chunk(body: &[u8], size: usize) -> Vec<Vec<u8>> {
+    let mut parts: Vec<Vec<u8>> = body
+        .chunks(size.max(1))
+        .map(|chunk| {
+            let mut encoded = format!("{:x}\r\n", chunk.len()).into_bytes();
+            encoded.extend_from_slice(chunk);
+            encoded.extend_from_slice(b"\r\n");
+            encoded
+        })
+        .collect();
+    parts.push(b"0\r\n\r\n".to_vec());
+    parts
+}
+
+// The next request on the connection; None once the client closes it
+async fn read_request(socket: &mut TcpStream, buffer: &mut Vec<u8>) -> Option<Received> {
+    let head_end = loop {
+        if let Some(end) = find(buffer, b"\r\n\r\n") {
+            break end + 4;
+        }
+        read_more(socket, buffer).await?;
+    };
+    let head = String::from_utf8_lossy(&buffer[..head_end]).into_owned();
+    buffer.drain(..head_end);
+
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    let mut request = Received {
+        index: 0,
+        method,
+        path,
+        headers,
+        body: vec![],
+    };
+
+    if request.header("transfer-encoding") == Some("chunked") {
+        loop {
+            let line_end = loop {
+                if let Some(end) = find(buffer, b"\r\n") {
+                    break end;
+                }
+                read_more(socket, buffer).await?;
+            };
+            let size = String::from_utf8_lossy(&buffer[..line_end]).into_owned();
+            let size = usize::from_str_radix(size.trim(), 16).ok()?;
+            buffer.drain(..line_end + 2);
+            while buffer.len() < size + 2 {
+                read_more(socket, buffer).await?;
+            }
+            request.body.extend(buffer.drain(..size));
+            buffer.drain(..2);
+            if size == 0 {
+                break;
+            }
+        }
+    } else {
+        let length = match request.header("content-length") {
+            Some(length) => length.parse().ok()?,
+            None => 0,
+        };
+        while buffer.len() < length {
+            read_more(socket, buffer).await?;
+        }
+        request.body = buffer.drain(..length).collect();
+    }
+
+    Some(request)
+}
+
+async fn read_more(socket: &mut TcpStream, buffer: &mut Vec<u8>) -> Option<()> {
+    let mut chunk = [0; 8192];
+    match socket.read(&mut chunk).await {
+        Ok(0) | Err(_) => None,
+        Ok(read) => {
+            buffer.extend_from_slice(&chunk[..read]);
+            Some(())
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+// Answers HTTP/2 requests, sent with prior knowledge over plain TCP, with a 200 after
+// `delay`, counting the connections opened and the most streams in flight at once
+pub struct MockH2Server {
+    address: String,
+    connections: Arc<AtomicUsize>,
+    max_streams: Arc<AtomicUsize>,
+}
+
+impl MockH2Server {
+    pub async fn start(delay: Duration) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = Self {
+            address: listener.local_addr().unwrap().to_string(),
+            connections: Arc::default(),
+            max_streams: Arc::default(),
+        };
+
+        let connections = server.connections.clone();
+        let max_streams = server.max_streams.clone();
+        let streams = Arc::new(AtomicUsize::new(0));
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                connections.fetch_add(1, Ordering::SeqCst);
+                let (streams, max_streams) = (streams.clone(), max_streams.clone());
+                let service = service_fn(move |_| {
+                    let (streams, max_streams) = (streams.clone(), max_streams.clone());
+                    async move {
+                        let open = streams.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_streams.fetch_max(open, Ordering::SeqCst);
+                        sleep(delay).await;
+                        streams.fetch_sub(1, Ordering::SeqCst);
+                        Ok::<_, Infallible>(hyper::Response::new(hyper::Body::from("ok")))
+                    }
+                });
+                let connection = Http::new()
+                    .http2_only(true)
+                    .serve_connection(socket, service);
+                tokio::spawn(connection);
+            }
+        });
+
+        server
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.address, path)
+    }
+
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    pub fn max_streams(&self) -> usize {
+        self.max_streams.load(Ordering::SeqCst)
+    }
+}
+
+// Answers every HTTPS request with a 200, negotiating at most `max_version`. Every
+// server presents the same self-signed certificate for localhost.
+pub struct MockTlsServer {
+    port: u16,
+}
+
+static CERTIFICATE: OnceLock<(PKey<Private>, X509)> = OnceLock::new();
+
+impl MockTlsServer {
+    pub fn start(max_version: SslVersion) -> Self {
+        let (key, certificate) = CERTIFICATE.get_or_init(self_signed);
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_private_key(key).unwrap();
+        acceptor.set_certificate(certificate).unwrap();
+        acceptor.set_max_proto_version(Some(max_version)).unwrap();
+        let acceptor = Arc::new(acceptor.build());
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for socket in listener.incoming().flatten() {
+                let acceptor = acceptor.clone();
+                std::thread::spawn(move || {
+                    let Ok(mut stream) = acceptor.accept(socket) else {
+                        return;
+                    };
+                    let mut buffer = Vec::new();
+                    let mut chunk = [0; 8192];
+                    loop {
+                        while let Some(end) = find(&buffer, b"\r\n\r\n") {
+                            buffer.drain(..end + 4);
+                            let reply = b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok";
+                            if stream.write_all(reply).is_err() {
+                                return;
+                            }
+                        }
+                        match stream.read(&mut chunk) {
+                            Ok(0) | Err(_) => return,
+                            Ok(read) => buffer.extend_from_slice(&chunk[..read]),
+                        }
+                    }
+                });
+            }
+        });
+
+        Self { port }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("https://localhost:{}{}", self.port, path)
+    }
+
+    // For clients to trust
+    pub fn certificate_pem() -> Vec<u8> {
+        CERTIFICATE.get_or_init(self_signed).1.to_pem().unwrap()
+    }
+}
+
+fn self_signed() -> (PKey<Private>, X509) {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", "localhost").unwrap();
+    let name = name.build();
+
+    let mut certificate = X509Builder::new().unwrap();
+    certificate.set_version(2).unwrap();
+    let serial = BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap();
+    certificate.set_serial_number(&serial).unwrap();
+    certificate.set_subject_name(&name).unwrap();
+    certificate.set_issuer_name(&name).unwrap();
+    certificate.set_pubkey(&key).unwrap();
+    let (issued, expires) = (Asn1Time::days_from_now(0), Asn1Time::days_from_now(1));
+    certificate.set_not_before(&issued.unwrap()).unwrap();
+    certificate.set_not_after(&expires.unwrap()).unwrap();
+    let names = SubjectAlternativeName::new()
+        .dns("localhost")
+        .ip("127.0.0.1")
+        .build(&certificate.x509v3_context(None, None))
+        .unwrap();
+    certificate.append_extension(names).unwrap();
+    certificate.sign(&key, MessageDigest::sha256()).unwrap();
+
+    (key, certificate.build())
+}
+
+// Just enough of an AMQP 0-9-1 broker for lapin to declare queues, publish and
+// consume. Deliveries still unacked when their connection goes away are requeued, as a
+// real broker does. `stop` drops every connection and stops listening until `restart`
+// brings it back on the same port.
+pub struct MockBroker {
+    address: String,
+    state: Arc<Mutex<BrokerState>>,
+    running: Mutex<CancellationToken>,
+}
+
+#[derive(Default)]
+struct BrokerState {
+    // Messages waiting for a consumer, by queue
+    queued: HashMap<String, VecDeque<Vec<u8>>>,
+    // Messages consumers acked, by queue
+    acked: HashMap<String, Vec<Vec<u8>>>,
+    // Messages clients published, by routing key
+    published: HashMap<String, Vec<Vec<u8>>>,
+}
+
+impl MockBroker {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let broker = Self {
+            address: listener.local_addr().unwrap().to_string(),
+            state: Arc::default(),
+            running: Mutex::new(CancellationToken::new()),
+        };
+        broker.listen(listener);
+        broker
+    }
+
+    pub fn url(&self) -> String {
+        format!("amqp://guest:guest@{}/%2f", self.address)
+    }
+
+    // Queues a message for whoever consumes `queue`
+    pub fn enqueue(&self, queue: &str, payload: impl Into<Vec<u8>>) {
+        let mut state = self.state.lock().unwrap();
+        state.queued.entry(queue.to_string()).or_default().push_back(payload.into());
+    }
+
+    pub fn acked(&self, queue: &str) -> Vec<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        state.acked.get(queue).cloned().unwrap_or_default()
+    }
+
+    pub fn published(&self, routing_key: &str) -> Vec<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        state.published.get(routing_key).cloned().unwrap_or_default()
+    }
+
+    // Drops every connection and stops accepting new ones
+    pub fn stop(&self) {
+        self.running.lock().unwrap().cancel();
+    }
+
+    pub async fn restart(&self) {
+        let listener = TcpListener::bind(&self.address).await.unwrap();
+        *self.running.lock().unwrap() = CancellationToken::new();
+        self.listen(listener);
+    }
+
+    fn listen(&self, listener: TcpListener) {
+        let stopped = self.running.lock().unwrap().clone();
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = stopped.cancelled() => return,
+                    accepted = listener.accept() => {
+                        let Ok((socket, _)) = accepted else { return };
+                        tokio::spawn(broker_connection(socket, state.clone(), stopped.clone()));
+                    }
+                }
+            }
+        });
+    }
+}
+
+async fn broker_connection(
+    mut socket: TcpStream,
+    state: Arc<Mutex<BrokerState>>,
+    stopped: CancellationToken,
+) {
+    let mut session = Session::default();
+    tokio::select! {
+        _ = stopped.cancelled() => {}
+        _ = session.run(&mut socket, &state) => {}
+    }
+
+    let mut state = state.lock().unwrap();
+    for (_, (_, queue, payload)) in session.unacked.into_iter().rev() {
+        state.queued.entry(queue).or_default().push_front(payload);
+    }
+}
+
+// One client connection
+#[derive(Default)]
+struct Session {
+    buffer: Vec<u8>,
+    // The first consumer of each queue: its channel and tag
+    consumers: HashMap<String, (ChannelId, String)>,
+    // Deliveries left unacked per channel before the next one waits, 0 for no limit
+    prefetch: HashMap<ChannelId, u16>,
+    // Deliveries not acked yet by delivery tag: the channel, queue and payload
+    unacked: BTreeMap<u64, (ChannelId, String, Vec<u8>)>,
+    last_delivery_tag: u64,
+    // A publish waiting for its content: the routing key, body size and body so far
+    publishing: Option<(String, u64, Vec<u8>)>,
+    generated_names: usize,
+}
+
+impl Session {
+    // Serves the connection until the client closes it
+    async fn run(&mut self, socket: &mut TcpStream, state: &Mutex<BrokerState>) -> Option<()> {
+        let mut chunk = [0; 8192];
+        loop {
+            while let Ok((rest, frame)) = parse_frame(self.buffer.as_slice()) {
+                let consumed = self.buffer.len() - rest.len();
+                self.buffer.drain(..consumed);
+                let replies = self.handle(frame, state);
+                send(socket, replies).await?;
+            }
+
+            let deliveries = self.deliver(state);
+            send(socket, deliveries).await?;
+
+            tokio::select! {
+                read = socket.read(&mut chunk) => match read {
+                    Ok(0) | Err(_) => return None,
+                    Ok(read) => self.buffer.extend_from_slice(&chunk[..read]),
+                },
+                // Picks up messages queued meanwhile
+                _ = sleep(Duration::from_millis(10)) => {}
+            }
+        }
+    }
+
+    fn handle(&mut self, frame: AMQPFrame, state: &Mutex<BrokerState>) -> Vec<AMQPFrame> {
+        let method = match frame {
+            AMQPFrame::ProtocolHeader(_) => {
+                let start = connection::Start {
+                    version_major: 0,
+                    version_minor: 9,
+                    server_properties: FieldTable::default(),
+                    mechanisms: "PLAIN".into(),
+                    locales: "en_US".into(),
+                };
+                return vec![reply(0, AMQPClass::Connection(connection::AMQPMethod::Start(start)))];
+            }
+            AMQPFrame::Header(_, _, header) => {
+                if let Some((_, size, _)) = &mut self.publishing {
+                    *size = header.body_size;
+                }
+                self.finish_publish(state);
+                return vec![];
+            }
+            AMQPFrame::Body(_, data) => {
+                if let Some((_, _, body)) = &mut self.publishing {
+                    body.extend(data);
+                }
+                self.finish_publish(state);
+                return vec![];
+            }
+            AMQPFrame::Heartbeat(_) => return vec![],
+            AMQPFrame::Method(channel, method) => (channel, method),
+        };
+
+        let (channel, answer) = match method {
+            (channel, AMQPClass::Connection(method)) => (
+                channel,
+                AMQPClass::Connection(match method {
+                    connection::AMQPMethod::StartOk(_) => {
+                        connection::AMQPMethod::Tune(connection::Tune {
+                            channel_max: 2047,
+                            frame_max: 131072,
+                            heartbeat: 0,
+                        })
+                    }
+                    connection::AMQPMethod::Open(_) => {
+                        connection::AMQPMethod::OpenOk(connection::OpenOk {})
+                    }
+                    connection::AMQPMethod::Close(_) => {
+                        connection::AMQPMethod::CloseOk(connection::CloseOk {})
+                    }
+                    _ => return vec![],
+                }),
+            ),
+            (channel, AMQPClass::Channel(method)) => (
+                channel,
+                AMQPClass::Channel(match method {
+                    channel::AMQPMethod::Open(_) => channel::AMQPMethod::OpenOk(channel::OpenOk {}),
+                    channel::AMQPMethod::Close(_) => {
+                        channel::AMQPMethod::CloseOk(channel::CloseOk {})
+                    }
+                    _ => return vec![],
+                }),
+            ),
+            (channel, AMQPClass::Exchange(exchange::AMQPMethod::Declare(declare))) => {
+                if declare.nowait {
+                    return vec![];
+                }
+                let ok = exchange::AMQPMethod::DeclareOk(exchange::DeclareOk {});
+                (channel, AMQPClass::Exchange(ok))
+            }
+            (channel, AMQPClass::Queue(queue::AMQPMethod::Declare(declare))) => {
+                let mut name = declare.queue.to_string();
+                if name.is_empty() {
+                    self.generated_names += 1;
+                    name = format!("amq.gen-{}", self.generated_names);
+                }
+                if declare.nowait {
+                    return vec![];
+                }
+                let ok = queue::AMQPMethod::DeclareOk(queue::DeclareOk {
+                    queue: name.into(),
+                    message_count: 0,
+                    consumer_count: 0,
+                });
+                (channel, AMQPClass::Queue(ok))
+            }
+            (channel, AMQPClass::Queue(queue::AMQPMethod::Bind(bind))) => {
+                if bind.nowait {
+                    return vec![];
+                }
+                (channel, AMQPClass::Queue(queue::AMQPMethod::BindOk(queue::BindOk {})))
+            }
+            (channel, AMQPClass::Basic(method)) => match method {
+                basic::AMQPMethod::Qos(qos) => {
+                    self.prefetch.insert(channel, qos.prefetch_count);
+                    (channel, AMQPClass::Basic(basic::AMQPMethod::QosOk(basic::QosOk {})))
+                }
+                basic::AMQPMethod::Consume(consume) => {
+                    let mut tag = consume.consumer_tag.to_string();
+                    if tag.is_empty() {
+                        self.generated_names += 1;
+                        tag = format!("amq.ctag-{}", self.generated_names);
+                    }
+                    self.consumers
+                        .entry(consume.queue.to_string())
+                        .or_insert_with(|| (channel, tag.clone()));
+                    if consume.nowait {
+                        return vec![];
+                    }
+                    let ok = basic::ConsumeOk {
+                        consumer_tag: tag.into(),
+                    };
+                    (channel, AMQPClass::Basic(basic::AMQPMethod::ConsumeOk(ok)))
+                }
+                basic::AMQPMethod::Publish(publish) => {
+                    self.publishing = Some((publish.routing_key.to_string(), u64::MAX, vec![]));
+                    return vec![];
+                }
+                basic::AMQPMethod::Ack(ack) => {
+                    let tags: Vec<u64> = match ack.multiple {
+                        true => self.unacked.range(..=ack.delivery_tag).map(|(tag, _)| *tag).collect(),
+                        false => vec![ack.delivery_tag],
+                    };
+                    let mut state = state.lock().unwrap();
+                    for tag in tags {
+                        if let Some((_, queue, payload)) = self.unacked.remove(&tag) {
+                            state.acked.entry(queue).or_default().push(payload);
+                        }
+                    }
+                    return vec![];
+                }
+                // Rejected messages are dropped, as if without a dead letter exchange
+                basic::AMQPMethod::Nack(basic::Nack { delivery_tag, .. })
+                | basic::AMQPMethod::Reject(basic::Reject { delivery_tag, .. }) => {
+                    self.unacked.remove(&delivery_tag);
+                    return vec![];
+                }
+                _ => return vec![],
+            },
+            _ => return vec![],
+        };
+
+        vec![reply(channel, answer)]
+    }
+
+    // Records a publish once all of its body came in
+    fn finish_publish(&mut self, state: &Mutex<BrokerState>) {
+        if !matches!(&self.publishing, Some((_, size, body)) if body.len() as u64 >= *size) {
+            return;
+        }
+        if let Some((routing_key, _, body)) = self.publishing.take() {
+            let mut state = state.lock().unwrap();
+            state.published.entry(routing_key).or_default().push(body);
+        }
+    }
+
+    // Hands queued messages to the consumers of their queue, as far as prefetch allows
+    fn deliver(&mut self, state: &Mutex<BrokerState>) -> Vec<AMQPFrame> {
+        let mut state = state.lock().unwrap();
+        let mut frames = vec![];
+
+        for (queue, (channel, tag)) in &self.consumers {
+            let Some(queued) = state.queued.get_mut(queue) else {
+                continue;
+            };
+            let prefetch = self.prefetch.get(channel).copied().unwrap_or(0);
+            loop {
+                let in_flight = self
+                    .unacked
+                    .values()
+                    .filter(|(unacked, _, _)| unacked == channel)
+                    .count();
+                if prefetch > 0 && in_flight >= usize::from(prefetch) {
+                    break;
+                }
+                let Some(payload) = queued.pop_front() else {
+                    break;
+                };
+
+                self.last_delivery_tag += 1;
+                let deliver = basic::Deliver {
+                    consumer_tag: tag.as_str().into(),
+                    delivery_tag: self.last_delivery_tag,
+                    redelivered: false,
+                    exchange: "".into(),
+                    routing_key: queue.as_str().into(),
+                };
+                let header = AMQPContentHeader {
+                    class_id: 60,
+                    body_size: payload.len() as u64,
+                    properties: basic::AMQPProperties::default(),
+                };
+                frames.push(reply(*channel, AMQPClass::Basic(basic::AMQPMethod::Deliver(deliver))));
+                frames.push(AMQPFrame::Header(*channel, 60, Box::new(header)));
+                frames.push(AMQPFrame::Body(*channel, payload.clone()));
+
+                self.unacked
+                    .insert(self.last_delivery_tag, (*channel, queue.clone(), payload));
+            }
+        }
+
+        frames
+    }
+}
+
+fn reply(channel: ChannelId, method: AMQPClass) -> AMQPFrame {
+    AMQPFrame::Method(channel, method)
+}
+
+async fn send(socket: &mut TcpStream, frames: Vec<AMQPFrame>) -> Option<()> {
+    for frame in frames {
+        let encoded = gen_frame(&frame)(WriteContext::from(Vec::new())).ok()?.write;
+        socket.write_all(&encoded).await.ok()?;
+    }
+    Some(())
+}
+
+// What an outbox took, as a broker would have been sent it
+pub struct Published {
+    pub queue: String,
+    pub payload: Vec<u8>,
+    pub properties: BasicProperties,
+}
+
+// Keeps everything published, in order
+impl Outbox for Mutex<Vec<Published>> {
+    fn publish<'a>(
+        &'a self,
+        queue: &'a str,
+        payload: &'a [u8],
+        properties: BasicProperties,
+    ) -> BoxFuture<'a, lapin::Result<()>> {
+        self.lock().unwrap().push(Published {
+            queue: queue.to_string(),
+            payload: payload.to_vec(),
+            properties,
+        });
+        Box::pin(async { Ok(()) })
+    }
+}
+
+// An outbox that refuses everything, like a broker that's gone
+pub struct Unavailable;
+
+impl Outbox for Unavailable {
+    fn publish<'a>(
+        &'a self,
+        _queue: &'a str,
+        _payload: &'a [u8],
+        _properties: BasicProperties,
+    ) -> BoxFuture<'a, lapin::Result<()>> {
+        Box::pin(async {
+            Err(lapin::Error::InvalidChannelState(
+                lapin::ChannelState::Closed,
+            ))
+        })
+    }
+}
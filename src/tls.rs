@@ -0,0 +1,142 @@
+// reqwest's native-tls connector doesn't expose the TLS version or cipher suite a
+// connection negotiated. So latencyByTlsVersion doesn't come from the measured
+// connections: each https origin gets one probe handshake of its own during setup,
+// through the same OpenSSL library and defaults the client uses, and the origin's
+// responses are grouped by what that probe negotiated.
+use crate::types::LoadTestMessage;
+use anyhow::{anyhow, Result};
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+use reqwest::Url;
+use std::collections::BTreeSet;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Negotiated {
+    pub version: String,
+    pub cipher: String,
+}
+
+// "host:port" of an https URL; None for anything else
+pub fn origin(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    if url.scheme() != "https" {
+        return None;
+    }
+    let (host, port) = (url.host_str()?, url.port_or_known_default()?);
+    Some(format!("{}:{}", host, port))
+}
+
+// Every https origin the test sends requests to
+pub fn origins(message: &LoadTestMessage) -> BTreeSet<String> {
+    let endpoints = message.endpoints.iter().chain(&message.steps).flatten();
+    std::iter::once(message.target_url.as_str())
+        .chain(endpoints.map(|endpoint| endpoint.url.as_str()))
+        .filter_map(origin)
+        .collect()
+}
+
+// Handshakes with `origin` and returns what was negotiated. Blocking.
+pub fn probe(origin: &str, timeout: Duration) -> Result<Negotiated> {
+    let (host, _) = origin
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow!("{} has no port", origin))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    let mut last_error = anyhow!("{} didn't resolve", origin);
+    for address in origin.to_socket_addrs()? {
+        let stream = match TcpStream::connect_timeout(&address, timeout) {
+            Ok(stream) => stream,
+            Err(e) => {
+                last_error = e.into();
+                continue;
+            }
+        };
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        let mut connector = SslConnector::builder(SslMethod::tls())?;
+        // Only reads what gets negotiated; the requests themselves verify the certificate
+        connector.set_verify(SslVerifyMode::NONE);
+        let stream = connector
+            .build()
+            .configure()?
+            .verify_hostname(false)
+            .connect(host, stream)
+            .map_err(|e| anyhow!("handshake with {} failed: {}", origin, e))?;
+
+        let ssl = stream.ssl();
+        let cipher = ssl
+            .current_cipher()
+            .map(|cipher| cipher.standard_name().unwrap_or(cipher.name()).to_string())
+            .unwrap_or_default();
+        return Ok(Negotiated {
+            version: ssl.version_str().to_string(),
+            cipher,
+        });
+    }
+
+    Err(last_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::{send_request, RequestPlan};
+    use crate::stats::Statistics;
+    use crate::test_support::{message, MockTlsServer};
+    use openssl::ssl::SslVersion;
+    use reqwest::{Certificate, Client};
+    use serde_json::json;
+    use tokio::sync::Mutex;
+
+    #[test]
+    fn origins_are_https_hosts_with_their_port() {
+        let message = message(json!({
+            "targetUrl": "https://example.com/a",
+            "endpoints": [
+                { "name": "b", "url": "https://example.com:8443/b", "weight": 1 },
+                { "name": "c", "url": "http://example.com/c", "weight": 1 },
+            ],
+        }));
+
+        let origins: Vec<_> = origins(&message).into_iter().collect();
+        assert_eq!(origins, ["example.com:443", "example.com:8443"]);
+    }
+
+    #[tokio::test]
+    async fn latency_is_grouped_by_negotiated_tls_version() {
+        let legacy = MockTlsServer::start(SslVersion::TLS1_2);
+        let modern = MockTlsServer::start(SslVersion::TLS1_3);
+
+        let stats = Mutex::new(Statistics::new());
+        for server in [&legacy, &modern] {
+            let origin = origin(&server.url("/")).unwrap();
+            let negotiated = probe(&origin, Duration::from_secs(5)).unwrap();
+            stats.lock().await.tls_origins.insert(origin, negotiated);
+        }
+
+        let certificate = Certificate::from_pem(&MockTlsServer::certificate_pem()).unwrap();
+        let client = Client::builder()
+            .add_root_certificate(certificate)
+            .build()
+            .unwrap();
+        for (server, requests) in [(&legacy, 2), (&modern, 1)] {
+            let message = message(json!({ "targetUrl": server.url("/") }));
+            for _ in 0..requests {
+                let plan = RequestPlan::default();
+                send_request(&client, &message, &stats, plan, &mut None, None, None).await;
+            }
+        }
+
+        let stats = stats.into_inner();
+        assert_eq!(stats.successful_requests, 3);
+        let latencies = stats.get_tls_latencies();
+        assert_eq!(latencies.len(), 2);
+        assert_eq!(latencies["TLSv1.2"].count, 2);
+        assert_eq!(latencies["TLSv1.3"].count, 1);
+        for latency in latencies.values() {
+            assert_eq!(latency.cipher_suites.len(), 1);
+        }
+    }
+}
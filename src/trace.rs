@@ -0,0 +1,84 @@
+// Per-request timeline in the Chrome trace-event format, viewable in chrome://tracing
+// or Perfetto. Capped at a fixed number of requests; later ones aren't traced.
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+pub struct TraceEntry {
+    pub name: String,
+    pub start: Instant,
+    pub duration: Duration,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+pub struct Trace {
+    entries: Vec<TraceEntry>,
+    capacity: usize,
+}
+
+impl Trace {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            capacity,
+        }
+    }
+
+    pub fn has_room(&self) -> bool {
+        self.entries.len() < self.capacity
+    }
+
+    pub fn record(&mut self, entry: TraceEntry) {
+        if self.has_room() {
+            self.entries.push(entry);
+        }
+    }
+
+    // Complete ("X") events timed from `start`. Overlapping requests are spread over
+    // lanes (`tid`) so the viewer never has to nest them.
+    pub fn to_chrome_json(&self, test_id: &str, start: Instant) -> Value {
+        let mut entries: Vec<&TraceEntry> = self.entries.iter().collect();
+        entries.sort_by_key(|entry| entry.start);
+
+        let mut lane_ends: Vec<Instant> = vec![];
+        let events: Vec<Value> = entries
+            .into_iter()
+            .map(|entry| {
+                let end = entry.start + entry.duration;
+                let lane = match lane_ends
+                    .iter()
+                    .position(|lane_end| *lane_end <= entry.start)
+                {
+                    Some(lane) => {
+                        lane_ends[lane] = end;
+                        lane
+                    }
+                    None => {
+                        lane_ends.push(end);
+                        lane_ends.len() - 1
+                    }
+                };
+
+                json!({
+                    "name": entry.name,
+                    "cat": "request",
+                    "ph": "X",
+                    "ts": entry.start.saturating_duration_since(start).as_micros() as u64,
+                    "dur": entry.duration.as_micros() as u64,
+                    "pid": 1,
+                    "tid": lane,
+                    "args": {
+                        "status": entry.status,
+                        "error": entry.error,
+                    },
+                })
+            })
+            .collect();
+
+        json!({
+            "traceEvents": events,
+            "displayTimeUnit": "ms",
+            "otherData": { "testId": test_id },
+        })
+    }
+}
@@ -1,5 +1,37 @@
-use serde::{Deserialize, Serialize};
+use crate::body;
+use crate::data_source::DataSource;
+use crate::expression::Expression;
+use crate::tdigest::TDigest;
+use anyhow::{bail, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::Utc;
+use regex::Regex;
+use reqwest::header::{HeaderName, HeaderValue};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
+use std::time::Duration;
+
+// Query parameter carrying the cacheBust value when the message doesn't name one
+pub const DEFAULT_CACHE_BUST_PARAM: &str = "cb";
+
+// Streams allowed on an HTTP/2 connection when the message doesn't say
+pub const DEFAULT_HTTP2_STREAMS: u32 = 100;
+
+// Largest body read or decoded when `maxDecompressedBytes` isn't set
+pub const DEFAULT_MAX_DECOMPRESSED_BYTES: u64 = 64 * 1024 * 1024;
+
+// Cap on `timeSeriesData` length before adjacent points get merged
+pub const DEFAULT_MAX_TIME_SERIES_POINTS: usize = 1000;
+
+// Per-request timeout when `requestTimeoutMs` isn't set
+pub const DEFAULT_REQUEST_TIMEOUT_MS: u64 = 30_000;
+
+// Requests kept in the timeline trace when `trace.maxRequests` isn't set
+pub const DEFAULT_TRACE_REQUESTS: u32 = 10_000;
+
+// Seconds of ramp-up ignored before `minExpectedRps` is checked
+pub const DEFAULT_WARMUP_SECONDS: u32 = 10;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LoadTestMessage {
@@ -14,16 +46,1169 @@ pub struct LoadTestMessage {
     pub total_requests: u32,
     #[serde(rename = "durationSeconds")]
     pub duration_seconds: u32,
+    // 0 sends as fast as the concurrency limit allows
     #[serde(rename = "requestsPerSecond")]
     pub requests_per_second: u32,
     pub headers: Option<HashMap<String, String>>,
     pub body: Option<serde_json::Value>,
+    #[serde(rename = "maxDecompressedBytes")]
+    pub max_decompressed_bytes: Option<u64>,
+    // Ask for gzip, deflate or br responses; off by default so existing tests keep
+    // measuring what the server sends uncompressed
+    #[serde(rename = "acceptCompression", default)]
+    pub accept_compression: bool,
+    #[serde(rename = "iterationsPerUser")]
+    pub iterations_per_user: Option<u32>,
+    #[serde(rename = "faultInjection")]
+    pub fault_injection: Option<FaultInjectionConfig>,
+    #[serde(rename = "stickySession")]
+    pub sticky_session: Option<StickySessionConfig>,
+    #[serde(rename = "validationWebhook")]
+    pub validation_webhook: Option<ValidationWebhookConfig>,
+    #[serde(rename = "arrivalDistribution")]
+    pub arrival_distribution: Option<ArrivalDistribution>,
+    // Closed by default. In openArrivalRate, requests arrive on schedule at
+    // `requestsPerSecond` however slowly the target answers; `concurrentUsers` only caps
+    // how many are in flight and `totalRequests` caps how many arrive.
+    pub mode: Option<LoadMode>,
+    // taskPerRequest by default. In userLoop, `concurrentUsers` persistent virtual users
+    // each send one request after another, sharing `requestsPerSecond` between them,
+    // until `durationSeconds` or `totalRequests` is reached
+    #[serde(rename = "concurrencyModel")]
+    pub concurrency_model: Option<ConcurrencyModel>,
+    pub seed: Option<u64>,
+    #[serde(rename = "checkpointIntervalSeconds")]
+    pub checkpoint_interval_seconds: Option<u32>,
+    #[serde(rename = "httpVersion")]
+    pub http_version: Option<HttpVersion>,
+    // HTTP/1.1: each connection carries one request at a time
+    #[serde(rename = "maxConnectionsPerOrigin")]
+    pub max_connections_per_origin: Option<u32>,
+    // HTTP/2: streams multiplexed over the single connection
+    #[serde(rename = "maxConcurrentStreams")]
+    pub max_concurrent_streams: Option<u32>,
+    // Raw binary body, decoded (and so validated) while the message is parsed
+    #[serde(
+        rename = "bodyBase64",
+        default,
+        deserialize_with = "deserialize_base64",
+        serialize_with = "serialize_base64"
+    )]
+    pub body_base64: Option<Vec<u8>>,
+    #[serde(rename = "bodyContentType")]
+    pub body_content_type: Option<String>,
+    // How `body` (and endpoint bodies) are encoded; json by default
+    #[serde(rename = "bodyType")]
+    pub body_type: Option<BodyType>,
+    #[serde(rename = "maxTimeSeriesPoints")]
+    pub max_time_series_points: Option<usize>,
+    // `retries` and the other retry settings sit at the top level of the message
+    #[serde(flatten)]
+    pub retry: RetryConfig,
+    // Abort early when the target can't sustain this rate after the warm-up window
+    #[serde(rename = "minExpectedRps")]
+    pub min_expected_rps: Option<f64>,
+    // The window minExpectedRps is only judged after; it doesn't hold requests out of
+    // statistics, excludedWarmupSeconds does
+    #[serde(rename = "warmupSeconds")]
+    pub warmup_seconds: Option<u32>,
+    // Circuit breaker: abort once the error rate (a percentage, like `errorRate`) goes
+    // over this, judged only after `abortMinSamples` requests
+    #[serde(rename = "abortOnErrorRate")]
+    pub abort_on_error_rate: Option<f64>,
+    #[serde(rename = "abortMinSamples")]
+    pub abort_min_samples: Option<u32>,
+    #[serde(rename = "resultEncoding")]
+    pub result_encoding: Option<ResultEncoding>,
+    // Weighted mix of targets; `targetUrl`/`method` are used when omitted
+    pub endpoints: Option<Vec<EndpointConfig>>,
+    // Escalating probe before the run that estimates the target's capacity, optionally
+    // setting `requestsPerSecond` relative to it
+    #[serde(rename = "capacityProbe")]
+    pub capacity_probe: Option<CapacityProbeConfig>,
+    // Pause between a virtual user's requests, on top of any RPS pacing
+    #[serde(rename = "thinkTimeMs")]
+    pub think_time_ms: Option<ThinkTimeConfig>,
+    // A user flow sent in order as one iteration of `iterationsPerUser`; values a step
+    // extracts fill `{{name}}` placeholders in the steps after it
+    pub steps: Option<Vec<EndpointConfig>>,
+    // Requests sent once, in order, before the load. The values they `extract` fill
+    // `{{name}}` placeholders of every later request; the test fails if one of them does.
+    pub bootstrap: Option<Vec<EndpointConfig>>,
+    // Health check run once before any load is sent
+    pub preflight: Option<PreflightConfig>,
+    #[serde(rename = "percentileBackend")]
+    pub percentile_backend: Option<PercentileBackend>,
+    // Connections opened and left idle in the pool before measurement starts
+    #[serde(rename = "prewarmConnections")]
+    pub prewarm_connections: Option<u32>,
+    // Target RPS as a function of `t` seconds, e.g. "100 + 50*sin(t/10)"
+    #[serde(rename = "loadExpression")]
+    pub load_expression: Option<String>,
+    // Target RPS in consecutive segments, an alternative to `loadExpression`. Once the
+    // segments run out no more requests are sent until `durationSeconds` elapses.
+    #[serde(rename = "rateSchedule")]
+    pub rate_schedule: Option<Vec<RateSegment>>,
+    // Gives each rateSchedule segment latency percentiles of its own samples alone, next
+    // to the cumulative ones
+    #[serde(rename = "resetHistogramPerPhase")]
+    pub reset_histogram_per_phase: Option<bool>,
+    #[serde(rename = "rateSmoothing")]
+    pub rate_smoothing: Option<RateSmoothing>,
+    // Shards of one test keep their combined rate at `globalRps` by sharing progress
+    #[serde(rename = "rateCoordination")]
+    pub rate_coordination: Option<RateCoordinationConfig>,
+    // Generated bodies whose size varies per request
+    #[serde(rename = "bodySizeDistribution")]
+    pub body_size_distribution: Option<BodySizeDistribution>,
+    // In-flight request cap per endpoint group, e.g. {"read": 90, "write": 10}
+    #[serde(rename = "groupConcurrency")]
+    pub group_concurrency: Option<HashMap<String, u32>>,
+    pub auth: Option<AuthConfig>,
+    // Every Nth request asks the server to close its connection, so the pool keeps
+    // opening fresh ones
+    #[serde(rename = "maxRequestsPerConnection")]
+    pub max_requests_per_connection: Option<u32>,
+    // Hard cap on requests sent, even when the test runs for a duration
+    #[serde(rename = "maxTotalRequests")]
+    pub max_total_requests: Option<u32>,
+    pub protocol: Option<Protocol>,
+    // Publish a Chrome-trace timeline of the run's requests
+    pub trace: Option<TraceConfig>,
+    // Serve repeated identical GETs locally for a while instead of resending them
+    #[serde(rename = "responseCache")]
+    pub response_cache: Option<ResponseCacheConfig>,
+    // Limit on everything before the first measured request: loading the data source,
+    // preflight, credentials, prewarming, bootstrap, the TLS probe handshakes and the
+    // capacity probe
+    #[serde(rename = "setupTimeoutSeconds")]
+    pub setup_timeout_seconds: Option<u32>,
+    // Grow concurrency linearly from 1 user to `concurrentUsers` over this window. The
+    // ramp counts towards `durationSeconds` rather than being added to it, so a ramp as
+    // long as the duration never reaches full concurrency.
+    #[serde(rename = "rampUpSeconds")]
+    pub ramp_up_seconds: Option<u32>,
+    // Requests taking longer fail into the "timeout" error bucket
+    #[serde(rename = "requestTimeoutMs")]
+    pub request_timeout_ms: Option<u64>,
+    // Skip per-second metrics for result-only consumers; `timeSeriesData` is still
+    // collected
+    #[serde(rename = "disableMetrics", default)]
+    pub disable_metrics: bool,
+    #[serde(rename = "resultVerbosity")]
+    pub result_verbosity: Option<ResultVerbosity>,
+    // Responses with any other status fail as "unexpected_status_<code>"
+    #[serde(rename = "expectedStatusCodes")]
+    pub expected_status_codes: Option<Vec<u16>>,
+    // Identities such as {"accountId": "42", "token": "..."}; virtual user N holds
+    // entry N (wrapping around) for the whole test
+    #[serde(rename = "virtualUsers")]
+    pub virtual_users: Option<Vec<HashMap<String, String>>>,
+    // CSV rows whose columns fill `{{column}}` placeholders, one row per request in
+    // turn, wrapping around when there are more requests than rows
+    #[serde(rename = "dataSource")]
+    pub data_source: Option<DataSourceConfig>,
+    // Probe the target after the load to check its latency returns to baseline
+    #[serde(rename = "verifyRecovery")]
+    pub verify_recovery: Option<VerifyRecoveryConfig>,
+    // Checks every response body must pass, failing the request otherwise
+    #[serde(rename = "bodyAssertions")]
+    pub body_assertions: Option<BodyAssertionsConfig>,
+    // Unique value on every request so caches in front of the target always miss
+    #[serde(rename = "cacheBust")]
+    pub cache_bust: Option<CacheBustConfig>,
+    // Leave the first successful response (see `coldStartMs`) out of latency figures
+    #[serde(rename = "excludeColdStart", default)]
+    pub exclude_cold_start: bool,
+    // Send requests during this opening window but leave them out of statistics and the
+    // time series, so cold caches don't skew percentiles
+    #[serde(rename = "excludedWarmupSeconds")]
+    pub excluded_warmup_seconds: Option<u32>,
+}
+
+fn deserialize_base64<'de, D>(deserializer: D) -> std::result::Result<Option<Vec<u8>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let encoded: Option<String> = Option::deserialize(deserializer)?;
+    encoded
+        .map(|encoded| BASE64.decode(encoded.trim()))
+        .transpose()
+        .map_err(|e| serde::de::Error::custom(format!("invalid bodyBase64: {}", e)))
+}
+
+fn serialize_base64<S>(bytes: &Option<Vec<u8>>, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    bytes
+        .as_ref()
+        .map(|bytes| BASE64.encode(bytes))
+        .serialize(serializer)
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BodyAssertionsConfig {
+    // Substring the body must contain
+    pub contains: Option<String>,
+    // Pattern the body must match somewhere
+    pub regex: Option<BodyRegex>,
+}
+
+// A `bodyAssertions.regex` compiled once when the message is parsed. A malformed
+// pattern doesn't reject the message; the test fails setup and says why instead.
+#[derive(Debug, Clone)]
+pub struct BodyRegex {
+    pub pattern: String,
+    pub compiled: std::result::Result<Regex, String>,
+}
+
+impl<'de> Deserialize<'de> for BodyRegex {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let pattern = String::deserialize(deserializer)?;
+        let compiled = Regex::new(&pattern).map_err(|e| e.to_string());
+        Ok(BodyRegex { pattern, compiled })
+    }
+}
+
+impl Serialize for BodyRegex {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.pattern.serialize(serializer)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum HttpVersion {
+    #[default]
+    #[serde(rename = "http1")]
+    Http1,
+    #[serde(rename = "http2")]
+    Http2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Protocol {
+    #[default]
+    Http,
+    // Every virtual user holds an event stream open for `durationSeconds`
+    Sse,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum PercentileBackend {
+    #[default]
+    #[serde(rename = "hdr")]
+    Hdr,
+    // Percentiles from a t-digest, also attached to the result for cross-shard merging
+    #[serde(rename = "tdigest")]
+    TDigest,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RateCoordinationConfig {
+    // Shared by every shard of the test, e.g. the coordinator's test id
+    pub group: String,
+    // Combined rate across all shards
+    #[serde(rename = "globalRps")]
+    pub global_rps: f64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RateSegment {
+    #[serde(rename = "durationSeconds")]
+    pub duration_seconds: u32,
+    pub rps: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RateSmoothing {
+    // Jump to each segment's rate as it starts
+    #[default]
+    Step,
+    // Ramp from the previous segment's rate over each segment
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LoadMode {
+    // Each request waits for a free slot before the next one is scheduled
+    #[default]
+    Closed,
+    // Arrivals follow the schedule; requests beyond the in-flight cap queue instead
+    OpenArrivalRate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BodyType {
+    #[default]
+    Json,
+    // application/x-www-form-urlencoded, from an object's fields
+    Form,
+    // multipart/form-data, from an object's fields and file parts
+    Multipart,
+    // A string sent as-is with `bodyContentType`
+    Raw,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConcurrencyModel {
+    // A task per request, bounded by the concurrency limit
+    #[default]
+    TaskPerRequest,
+    // A persistent task per virtual user, looping over its requests
+    UserLoop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ArrivalDistribution {
+    // Requests issued in evenly paced batches
+    #[default]
+    Uniform,
+    // Exponentially distributed gaps averaging `requestsPerSecond`
+    Poisson,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ResultEncoding {
+    #[default]
+    Json,
+    // Schema in proto/loadmaster.proto
+    Protobuf,
+}
+
+impl ResultEncoding {
+    // Sent as the AMQP content-type so consumers know how to decode the payload
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ResultEncoding::Json => "application/json",
+            ResultEncoding::Protobuf => "application/x-protobuf",
+        }
+    }
+}
+
+// How much of `TestResult` gets published; the scalar summary is always included
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ResultVerbosity {
+    // No distributions, per-endpoint breakdowns or time series
+    Summary,
+    // Everything except the time series and raw latency histograms/sketches
+    Standard,
+    #[default]
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BodySizeKind {
+    #[default]
+    Uniform,
+    Lognormal,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BodySizeDistribution {
+    #[serde(default)]
+    pub distribution: BodySizeKind,
+    // Bytes; every sample is clamped to this range
+    pub min: usize,
+    pub max: usize,
+    // Lognormal only: mean size in bytes and spread of the underlying normal
+    pub mean: Option<usize>,
+    pub sigma: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum AuthConfig {
+    #[serde(rename = "bearer")]
+    Bearer { token: String },
+    #[serde(rename = "basic")]
+    Basic {
+        #[serde(alias = "user")]
+        username: String,
+        #[serde(alias = "pass")]
+        password: String,
+    },
+    // A key sent in a header of its own, e.g. X-API-Key
+    #[serde(rename = "apiKey")]
+    ApiKey { header: String, value: String },
+    // Token fetched from `tokenUrl` and refreshed before it expires
+    #[serde(rename = "oauthClientCredentials")]
+    OAuthClientCredentials {
+        #[serde(rename = "tokenUrl")]
+        token_url: String,
+        #[serde(rename = "clientId")]
+        client_id: String,
+        #[serde(rename = "clientSecret")]
+        client_secret: String,
+        scope: Option<String>,
+    },
+}
+
+// Where the unique value goes; a query parameter when neither is set
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CacheBustConfig {
+    #[serde(rename = "queryParam")]
+    pub query_param: Option<String>,
+    pub header: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ResponseCacheConfig {
+    #[serde(rename = "ttlMs")]
+    pub ttl_ms: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TraceConfig {
+    // Requests after the first `maxRequests` are left out of the trace
+    #[serde(rename = "maxRequests")]
+    pub max_requests: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PreflightConfig {
+    pub url: String,
+    // Any 2xx passes when omitted
+    #[serde(rename = "expectedStatus")]
+    pub expected_status: Option<u16>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CapacityProbeConfig {
+    // The rate doubles every `stepSeconds` from `startRps` up to at most `maxRps`
+    #[serde(rename = "startRps")]
+    pub start_rps: Option<f64>,
+    #[serde(rename = "maxRps")]
+    pub max_rps: Option<f64>,
+    #[serde(rename = "stepSeconds")]
+    pub step_seconds: Option<u32>,
+    // Limits a rate must stay within to count as sustained
+    #[serde(rename = "maxErrorRate")]
+    pub max_error_rate: Option<f64>,
+    #[serde(rename = "maxP95Ms")]
+    pub max_p95_ms: Option<f64>,
+    // Run the main load at this percentage of the estimate, e.g. 80
+    #[serde(rename = "loadPercent")]
+    pub load_percent: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ThinkTimeConfig {
+    pub min: u64,
+    pub max: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DataSourceConfig {
+    // Inline CSV, or one fetched from `csvUrl` when the test starts
+    pub csv: Option<String>,
+    #[serde(rename = "csvUrl")]
+    pub csv_url: Option<String>,
+    // Column names when the CSV has no header row
+    pub columns: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct VerifyRecoveryConfig {
+    // GET target for the probes, `targetUrl` when omitted
+    pub url: Option<String>,
+    // How long the target gets to recover before it's reported as still degraded
+    #[serde(rename = "windowSeconds")]
+    pub window_seconds: u32,
+    // Latency the target counts as recovered at
+    #[serde(rename = "baselineP95Ms")]
+    pub baseline_p95_ms: f64,
+    #[serde(rename = "probeIntervalMs")]
+    pub probe_interval_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EndpointConfig {
+    pub name: String,
+    pub url: String,
+    // Defaults to the test's `method`
+    pub method: Option<String>,
+    // Relative share of requests, default 1
+    pub weight: Option<u32>,
+    // Statuses counted as success for this endpoint; any response passes when omitted
+    #[serde(rename = "expectedStatusCodes")]
+    pub expected_status_codes: Option<Vec<u16>>,
+    // Substring the response body must contain
+    #[serde(rename = "bodyContains")]
+    pub body_contains: Option<String>,
+    // Label such as "read" or "write" sharing a `groupConcurrency` limit
+    pub group: Option<String>,
+    // Dimensions like {"region": "eu", "userType": "premium"} results are sliced by
+    pub tags: Option<HashMap<String, String>>,
+    // Overrides the test's `requestTimeoutMs` for this endpoint
+    #[serde(rename = "requestTimeoutMs")]
+    pub request_timeout_ms: Option<u64>,
+    // Sent on top of the test's `headers`, replacing any of the same name
+    pub headers: Option<HashMap<String, String>>,
+    // Replaces the test's `body`
+    pub body: Option<serde_json::Value>,
+    // Name -> JSON path of values taken from the response for later `steps`, e.g.
+    // {"token": "$.auth.token"}; the request fails when one is missing
+    pub extract: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RetryConfig {
+    // Attempts after the first one, so a request goes out at most `retries + 1` times;
+    // none by default
+    #[serde(default)]
+    pub retries: u32,
+    // Delay before the first retry; later ones grow as `backoff` says
+    #[serde(rename = "retryBackoffMs")]
+    pub backoff_ms: Option<u64>,
+    // Exponential when omitted
+    pub backoff: Option<BackoffKind>,
+    // Statuses worth another attempt; any 5xx when omitted
+    #[serde(rename = "retryableStatusCodes")]
+    pub retryable_status_codes: Option<Vec<u16>>,
+}
+
+impl RetryConfig {
+    pub fn is_retryable(&self, status: u16) -> bool {
+        match &self.retryable_status_codes {
+            Some(codes) => codes.contains(&status),
+            None => (500..600).contains(&status),
+        }
+    }
+
+    // Wait before retry number `retry` (1-based)
+    pub fn backoff(&self, retry: u32) -> Option<Duration> {
+        let backoff_ms = self.backoff_ms?;
+        let multiplier = match self.backoff.unwrap_or_default() {
+            BackoffKind::Linear => retry as u64,
+            BackoffKind::Exponential => 1u64 << retry.saturating_sub(1).min(16),
+        };
+        Some(Duration::from_millis(backoff_ms.saturating_mul(multiplier)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BackoffKind {
+    // retryBackoffMs x the retry number
+    Linear,
+    // retryBackoffMs doubled on every retry
+    #[default]
+    Exponential,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FaultInjectionConfig {
+    // Fraction of requests (0.0 - 1.0) to corrupt
+    pub rate: f64,
+    // Faults to pick from; all kinds when omitted
+    pub faults: Option<Vec<FaultKind>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StickySessionConfig {
+    // Cookie set by the load balancer to pin a client to a backend
+    #[serde(rename = "cookieName")]
+    pub cookie_name: Option<String>,
+    // Response header whose value is echoed back on subsequent requests
+    #[serde(rename = "headerName")]
+    pub header_name: Option<String>,
+    // Response header identifying the backend that served the request (default `x-backend`)
+    #[serde(rename = "backendHeader")]
+    pub backend_header: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ValidationWebhookConfig {
+    pub url: String,
+    // Fraction of responses (0.0 - 1.0) sent for validation, default all
+    #[serde(rename = "sampleRate")]
+    pub sample_rate: Option<f64>,
+}
+
+// Response metadata posted to the validation webhook
+#[derive(Debug, Serialize)]
+pub struct ValidationRequest {
+    #[serde(rename = "testId")]
+    pub test_id: String,
+    pub url: String,
+    pub method: String,
+    #[serde(rename = "statusCode")]
+    pub status_code: u16,
+    #[serde(rename = "responseTimeMs")]
+    pub response_time_ms: u64,
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ValidationVerdict {
+    pub pass: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FaultKind {
+    TruncateBody,
+    InvalidHeader,
+    EarlyClose,
+}
+
+impl FaultKind {
+    pub const ALL: [FaultKind; 3] = [
+        FaultKind::TruncateBody,
+        FaultKind::InvalidHeader,
+        FaultKind::EarlyClose,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FaultKind::TruncateBody => "truncate_body",
+            FaultKind::InvalidHeader => "invalid_header",
+            FaultKind::EarlyClose => "early_close",
+        }
+    }
+}
+
+impl LoadTestMessage {
+    // How many requests can actually be in flight once protocol limits are applied
+    pub fn effective_concurrency(&self) -> u32 {
+        match self.http_version.unwrap_or_default() {
+            HttpVersion::Http1 => self
+                .concurrent_users
+                .min(self.max_connections_per_origin.unwrap_or(u32::MAX)),
+            HttpVersion::Http2 => self
+                .concurrent_users
+                .min(self.max_concurrent_streams.unwrap_or(DEFAULT_HTTP2_STREAMS)),
+        }
+    }
+
+    // Users allowed `elapsed` into the run, following `rampUpSeconds` when set
+    pub fn ramped_users(&self, elapsed: Duration) -> u32 {
+        let Some(ramp) = self.ramp_up_seconds.map(|seconds| seconds as f64) else {
+            return self.concurrent_users;
+        };
+        let progress = (elapsed.as_secs_f64() / ramp).min(1.0);
+        let extra_users = (self.concurrent_users.saturating_sub(1) as f64 * progress) as u32;
+        (1 + extra_users).min(self.concurrent_users)
+    }
+
+    pub fn resolved_plan(&self) -> ResolvedTestPlan {
+        let http_version = self.http_version.unwrap_or_default();
+        ResolvedTestPlan {
+            test_id: self.test_id.clone(),
+            target_url: self.target_url.clone(),
+            method: self.method.clone(),
+            concurrent_users: self.concurrent_users,
+            effective_concurrency: self.effective_concurrency(),
+            concurrency_model: self.concurrency_model(),
+            total_requests: self.total_requests,
+            duration_seconds: self.duration_seconds,
+            requests_per_second: self.requests_per_second,
+            iterations_per_user: self.iterations_per_user,
+            load_expression: self.load_expression.clone(),
+            arrival_distribution: self.arrival_distribution.unwrap_or_default(),
+            mode: self.mode.unwrap_or_default(),
+            http_version,
+            max_connections_per_origin: self.max_connections_per_origin,
+            max_concurrent_streams: match http_version {
+                HttpVersion::Http1 => None,
+                HttpVersion::Http2 => {
+                    Some(self.max_concurrent_streams.unwrap_or(DEFAULT_HTTP2_STREAMS))
+                }
+            },
+            max_requests_per_connection: self.max_requests_per_connection,
+            max_total_requests: self.max_total_requests,
+            warmup_seconds: self.warmup_seconds.unwrap_or(DEFAULT_WARMUP_SECONDS),
+            ramp_up_seconds: self.ramp_up_seconds,
+            request_timeout_ms: self
+                .request_timeout_ms
+                .unwrap_or(DEFAULT_REQUEST_TIMEOUT_MS),
+            max_time_series_points: self
+                .max_time_series_points
+                .unwrap_or(DEFAULT_MAX_TIME_SERIES_POINTS),
+            percentile_backend: self.percentile_backend.unwrap_or_default(),
+            result_encoding: self.result_encoding.unwrap_or_default(),
+            seed: self.seed,
+            timestamp: Utc::now().to_rfc3339(),
+        }
+    }
+
+    // Human-readable description of how concurrency maps onto connections and tasks
+    pub fn concurrency_model(&self) -> String {
+        let concurrency = self.effective_concurrency();
+        let connections = match self.http_version.unwrap_or_default() {
+            HttpVersion::Http1 => format!(
+                "http1: {} connection(s), 1 request per connection",
+                concurrency
+            ),
+            HttpVersion::Http2 => format!(
+                "http2: 1 connection, {} concurrent stream(s)",
+                concurrency
+            ),
+        };
+        match (self.iterations_per_user, self.concurrency_model) {
+            (None, None | Some(ConcurrencyModel::TaskPerRequest)) => connections,
+            _ => format!("{}, {} looping virtual user(s)", connections, self.concurrent_users),
+        }
+    }
+
+    // Whether requests go out from persistent virtual users, which hold per-user state
+    // like an identity or a session: with iterationsPerUser or the userLoop model
+    pub fn persistent_users(&self) -> bool {
+        self.iterations_per_user.is_some()
+            || self.concurrency_model == Some(ConcurrencyModel::UserLoop)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        // A test needs a request cap, a duration or an iteration count to know when to stop
+        if self.total_requests == 0
+            && self.duration_seconds == 0
+            && self.iterations_per_user.is_none()
+        {
+            bail!("either totalRequests, durationSeconds or iterationsPerUser must be positive");
+        }
+
+        if self.iterations_per_user == Some(0) {
+            bail!("iterationsPerUser must be positive");
+        }
+
+        if let Some(fault_injection) = &self.fault_injection {
+            if !(0.0..=1.0).contains(&fault_injection.rate) {
+                bail!("faultInjection.rate must be between 0 and 1");
+            }
+            if fault_injection.faults.as_ref().is_some_and(|faults| faults.is_empty()) {
+                bail!("faultInjection.faults must not be empty");
+            }
+        }
+
+        let rate_shaped = self.load_expression.is_some()
+            || self.rate_schedule.is_some()
+            || self.rate_coordination.is_some();
+        if self.arrival_distribution == Some(ArrivalDistribution::Poisson)
+            && self.requests_per_second == 0
+            && !rate_shaped
+        {
+            bail!("poisson arrivalDistribution requires requestsPerSecond or a rate shape");
+        }
+
+        if self.body.is_some() && self.body_base64.is_some() {
+            bail!("body and bodyBase64 are mutually exclusive");
+        }
+
+        // Every request reuses its body, so one that can't be encoded as `bodyType` would
+        // fail all of them the same way. Any JSON value can be sent as JSON.
+        if let Some(body_type) = self.body_type.filter(|&body_type| body_type != BodyType::Json) {
+            if self.body_base64.is_some() {
+                bail!("bodyType only applies to body, not bodyBase64");
+            }
+            let endpoint_bodies = self
+                .endpoints
+                .iter()
+                .chain(&self.steps)
+                .flatten()
+                .filter_map(|endpoint| endpoint.body.as_ref());
+            for body in self.body.iter().chain(endpoint_bodies) {
+                body::check(body_type, body)?;
+            }
+        }
+
+        if self.max_connections_per_origin == Some(0) || self.max_concurrent_streams == Some(0) {
+            bail!("maxConnectionsPerOrigin and maxConcurrentStreams must be positive");
+        }
+
+        // reqwest multiplexes every HTTP/2 request to an origin over one connection
+        if self.http_version == Some(HttpVersion::Http2)
+            && self.max_connections_per_origin.is_some_and(|connections| connections > 1)
+        {
+            bail!("http2 uses a single connection per origin; use maxConcurrentStreams instead");
+        }
+
+        if self.protocol == Some(Protocol::Sse) {
+            if self.duration_seconds == 0 {
+                bail!("sse protocol requires durationSeconds");
+            }
+            if self.iterations_per_user.is_some() || rate_shaped {
+                bail!("sse protocol doesn't support iterationsPerUser or a rate shape");
+            }
+            if self.ramp_up_seconds.is_some() {
+                bail!("sse protocol doesn't support rampUpSeconds");
+            }
+        }
+
+        if self.expected_status_codes.as_ref().is_some_and(Vec::is_empty) {
+            bail!("expectedStatusCodes must not be empty");
+        }
+
+        if self.request_timeout_ms == Some(0) {
+            bail!("requestTimeoutMs must be positive");
+        }
+
+        // Otherwise they'd be silently ignored
+        let retry = &self.retry;
+        if retry.retries == 0
+            && (retry.backoff_ms.is_some()
+                || retry.backoff.is_some()
+                || retry.retryable_status_codes.is_some())
+        {
+            bail!("retryBackoffMs, backoff and retryableStatusCodes require retries");
+        }
+
+        if self.ramp_up_seconds == Some(0) {
+            bail!("rampUpSeconds must be positive");
+        }
+
+        if self.setup_timeout_seconds == Some(0) {
+            bail!("setupTimeoutSeconds must be positive");
+        }
+
+        if self.response_cache.as_ref().is_some_and(|cache| cache.ttl_ms == 0) {
+            bail!("responseCache.ttlMs must be positive");
+        }
+
+        if self.cache_bust.is_some() && self.response_cache.is_some() {
+            bail!("cacheBust can't be combined with responseCache");
+        }
+
+        if self.trace.as_ref().is_some_and(|trace| trace.max_requests == Some(0)) {
+            bail!("trace.maxRequests must be positive");
+        }
+
+        if self.max_total_requests == Some(0) {
+            bail!("maxTotalRequests must be positive");
+        }
+
+        if self.max_requests_per_connection == Some(0) {
+            bail!("maxRequestsPerConnection must be positive");
+        }
+
+        // Connection-specific headers aren't allowed in HTTP/2
+        if self.http_version == Some(HttpVersion::Http2)
+            && self.max_requests_per_connection.is_some()
+        {
+            bail!("maxRequestsPerConnection requires http1");
+        }
+
+        if self.max_time_series_points.is_some_and(|max| max < 2) {
+            bail!("maxTimeSeriesPoints must be at least 2");
+        }
+
+        if self.checkpoint_interval_seconds == Some(0) {
+            bail!("checkpointIntervalSeconds must be positive");
+        }
+
+        if let Some(webhook) = &self.validation_webhook {
+            if webhook.sample_rate.is_some_and(|rate| !(0.0..=1.0).contains(&rate)) {
+                bail!("validationWebhook.sampleRate must be between 0 and 1");
+            }
+        }
+
+        if let Some(sticky) = &self.sticky_session {
+            // Session state lives on long-lived virtual users
+            if !self.persistent_users() {
+                bail!("stickySession requires iterationsPerUser or the userLoop concurrencyModel");
+            }
+            if sticky.cookie_name.is_none() && sticky.header_name.is_none() {
+                bail!("stickySession needs a cookieName or headerName");
+            }
+        }
+
+        if let Some(recovery) = &self.verify_recovery {
+            if recovery.window_seconds == 0 || recovery.probe_interval_ms == Some(0) {
+                bail!("verifyRecovery.windowSeconds and probeIntervalMs must be positive");
+            }
+            if recovery.baseline_p95_ms <= 0.0 {
+                bail!("verifyRecovery.baselineP95Ms must be positive");
+            }
+        }
+
+        if let Some(identities) = &self.virtual_users {
+            // Identities are held by long-lived virtual users
+            if !self.persistent_users() {
+                bail!("virtualUsers requires iterationsPerUser or the userLoop concurrencyModel");
+            }
+            if identities.is_empty() {
+                bail!("virtualUsers must not be empty");
+            }
+        }
+
+        if let Some(data_source) = &self.data_source {
+            if data_source.csv.is_some() == data_source.csv_url.is_some() {
+                bail!("dataSource needs exactly one of csv and csvUrl");
+            }
+            if data_source.columns.as_ref().is_some_and(Vec::is_empty) {
+                bail!("dataSource.columns must not be empty");
+            }
+            if let Some(csv) = &data_source.csv {
+                DataSource::parse(csv, data_source.columns.as_deref())?;
+            }
+            if self.protocol == Some(Protocol::Sse) {
+                bail!("sse protocol doesn't support dataSource");
+            }
+        }
+
+        if let Some(warmup) = self.excluded_warmup_seconds {
+            if warmup == 0 {
+                bail!("excludedWarmupSeconds must be positive");
+            }
+            if self.duration_seconds <= warmup {
+                bail!("excludedWarmupSeconds requires a longer durationSeconds");
+            }
+        }
+
+        if self.max_decompressed_bytes == Some(0) {
+            bail!("maxDecompressedBytes must be positive");
+        }
+
+        if self.min_expected_rps.is_some_and(|rps| rps <= 0.0) {
+            bail!("minExpectedRps must be positive");
+        }
+
+        if self.mode == Some(LoadMode::OpenArrivalRate) {
+            if self.requests_per_second == 0 && !rate_shaped {
+                bail!("openArrivalRate mode requires requestsPerSecond or a rate shape");
+            }
+            if self.iterations_per_user.is_some() || self.protocol == Some(Protocol::Sse) {
+                bail!("openArrivalRate mode doesn't support iterationsPerUser or sse");
+            }
+        }
+
+        if self.concurrency_model == Some(ConcurrencyModel::UserLoop) {
+            if self.mode == Some(LoadMode::OpenArrivalRate) || rate_shaped {
+                bail!("userLoop concurrencyModel doesn't support openArrivalRate or a rate shape");
+            }
+            if self.protocol == Some(Protocol::Sse) {
+                bail!("sse protocol doesn't support concurrencyModel");
+            }
+        }
+
+        if let Some(expression) = &self.load_expression {
+            Expression::parse(expression)?;
+            if self.iterations_per_user.is_some() {
+                bail!("loadExpression can't be combined with iterationsPerUser");
+            }
+        }
+
+        if self.reset_histogram_per_phase == Some(true) && self.rate_schedule.is_none() {
+            bail!("resetHistogramPerPhase requires rateSchedule, whose segments are the phases");
+        }
+
+        if let Some(segments) = &self.rate_schedule {
+            if self.load_expression.is_some() || self.iterations_per_user.is_some() {
+                bail!("rateSchedule can't be combined with loadExpression or iterationsPerUser");
+            }
+            if self.duration_seconds == 0 {
+                bail!("rateSchedule requires durationSeconds");
+            }
+            if segments.is_empty() {
+                bail!("rateSchedule must not be empty");
+            }
+            if segments
+                .iter()
+                .any(|segment| {
+                    segment.duration_seconds == 0 || !segment.rps.is_finite() || segment.rps < 0.0
+                })
+            {
+                bail!("rateSchedule segments need a positive durationSeconds and rps >= 0");
+            }
+        }
+
+        if let Some(coordination) = &self.rate_coordination {
+            if self.load_expression.is_some()
+                || self.rate_schedule.is_some()
+                || self.iterations_per_user.is_some()
+            {
+                bail!(
+                    "rateCoordination can't be combined with loadExpression, rateSchedule or \
+                     iterationsPerUser"
+                );
+            }
+            if coordination.group.is_empty() {
+                bail!("rateCoordination.group must not be empty");
+            }
+            if !coordination.global_rps.is_finite() || coordination.global_rps <= 0.0 {
+                bail!("rateCoordination.globalRps must be positive");
+            }
+        }
+
+        if let Some(sizes) = &self.body_size_distribution {
+            if sizes.min > sizes.max {
+                bail!("bodySizeDistribution.min must not exceed max");
+            }
+            if sizes.sigma.is_some_and(|sigma| sigma <= 0.0) {
+                bail!("bodySizeDistribution.sigma must be positive");
+            }
+            if self.body.is_some() || self.body_base64.is_some() {
+                bail!("bodySizeDistribution generates the body; drop body and bodyBase64");
+            }
+        }
+
+        if let Some(endpoints) = &self.endpoints {
+            if endpoints.is_empty() {
+                bail!("endpoints must not be empty");
+            }
+            if endpoints.iter().any(|endpoint| endpoint.weight == Some(0)) {
+                bail!("endpoint weights must be positive");
+            }
+            if endpoints.iter().any(|endpoint| endpoint.request_timeout_ms == Some(0)) {
+                bail!("endpoint requestTimeoutMs must be positive");
+            }
+        }
+
+        if self
+            .abort_on_error_rate
+            .is_some_and(|rate| !(0.0..=100.0).contains(&rate))
+        {
+            bail!("abortOnErrorRate must be a percentage between 0 and 100");
+        }
+        if self.abort_min_samples.is_some() && self.abort_on_error_rate.is_none() {
+            bail!("abortMinSamples requires abortOnErrorRate");
+        }
+
+        if let Some(AuthConfig::ApiKey { header, value }) = &self.auth {
+            if HeaderName::from_bytes(header.as_bytes()).is_err()
+                || HeaderValue::from_str(value).is_err()
+            {
+                bail!("auth.header and auth.value must make a valid header");
+            }
+        }
+
+        if let Some(probe) = &self.capacity_probe {
+            let positive = |value: Option<f64>| value.is_none_or(|v| v.is_finite() && v > 0.0);
+            if !positive(probe.start_rps)
+                || !positive(probe.max_rps)
+                || !positive(probe.max_p95_ms)
+                || !positive(probe.load_percent)
+                || probe.step_seconds == Some(0)
+            {
+                bail!("capacityProbe values must be positive");
+            }
+            if probe.max_error_rate.is_some_and(|rate| !(0.0..=1.0).contains(&rate)) {
+                bail!("capacityProbe.maxErrorRate must be between 0 and 1");
+            }
+            if self.protocol == Some(Protocol::Sse) {
+                bail!("sse protocol doesn't support capacityProbe");
+            }
+            // The probe's estimate replaces `requestsPerSecond`
+            let fixed_rate = rate_shaped || self.iterations_per_user.is_some();
+            if probe.load_percent.is_some() && fixed_rate {
+                bail!("capacityProbe.loadPercent needs a fixed requestsPerSecond load");
+            }
+        }
+
+        if let Some(think_time) = &self.think_time_ms {
+            if think_time.min > think_time.max {
+                bail!("thinkTimeMs.min must not exceed thinkTimeMs.max");
+            }
+            // Without virtual users holding permits there's no one to pause
+            if self.mode == Some(LoadMode::OpenArrivalRate)
+                || self.protocol == Some(Protocol::Sse)
+            {
+                bail!("thinkTimeMs doesn't apply to openArrivalRate mode or sse");
+            }
+        }
+
+        if let Some(steps) = &self.steps {
+            if self.iterations_per_user.is_none() {
+                bail!("steps requires iterationsPerUser");
+            }
+            if self.endpoints.is_some() {
+                bail!("steps and endpoints are mutually exclusive");
+            }
+            if steps.is_empty() {
+                bail!("steps must not be empty");
+            }
+        }
+
+        if self.bootstrap.as_ref().is_some_and(Vec::is_empty) {
+            bail!("bootstrap must not be empty");
+        }
+
+        if let Some(groups) = &self.group_concurrency {
+            if groups.values().any(|&concurrency| concurrency == 0) {
+                bail!("groupConcurrency limits must be positive");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TestStatus {
+    Running,
+    Completed,
+    Aborted,
+}
+
+impl TestStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TestStatus::Running => "running",
+            TestStatus::Completed => "completed",
+            TestStatus::Aborted => "aborted",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AbortReason {
+    // Target stayed under `minExpectedRps` after the warm-up window
+    ThroughputBelowMinimum,
+    // Target failed the health check, so no load was sent
+    PreflightFailed,
+    // `maxTotalRequests` was reached before the test would otherwise have ended
+    BudgetExhausted,
+    // An operator stopped the test through the cancel_tests exchange
+    Cancelled,
+    // Setup ran past `setupTimeoutSeconds`, so no load was sent
+    SetupTimeout,
+    // The test as configured can't run, e.g. a malformed `bodyAssertions.regex`; the
+    // result's warnings say why
+    SetupError,
+    // The error rate went over `abortOnErrorRate`
+    ErrorRateExceeded,
+}
+
+impl AbortReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AbortReason::ThroughputBelowMinimum => "throughput_below_minimum",
+            AbortReason::PreflightFailed => "preflight_failed",
+            AbortReason::BudgetExhausted => "budget_exhausted",
+            AbortReason::Cancelled => "cancelled",
+            AbortReason::SetupTimeout => "setup_timeout",
+            AbortReason::SetupError => "setup_error",
+            AbortReason::ErrorRateExceeded => "error_rate_exceeded",
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
 pub struct TestResult {
     #[serde(rename = "testId")]
     pub test_id: String,
+    // Set on checkpoint snapshots published while the test is still running
+    pub partial: bool,
+    pub status: TestStatus,
+    #[serde(rename = "abortReason")]
+    pub abort_reason: Option<AbortReason>,
     #[serde(rename = "totalRequests")]
     pub total_requests: u32,
     #[serde(rename = "successfulRequests")]
@@ -32,6 +1217,35 @@ pub struct TestResult {
     pub failed_requests: u32,
     #[serde(rename = "averageResponseTime")]
     pub average_response_time: f64,
+    // Interval averages weighted by interval length rather than by request count
+    #[serde(rename = "timeWeightedAvgLatency")]
+    pub time_weighted_avg_latency: Option<f64>,
+    // Under rateCoordination: the rate all shards were after, and what they achieved
+    // together as far as this shard heard
+    #[serde(rename = "globalTargetRps")]
+    pub global_target_rps: Option<f64>,
+    #[serde(rename = "aggregateRps")]
+    pub aggregate_rps: Option<f64>,
+    // Flips between success and failure across the request stream; a high
+    // `flakinessScore` at a moderate error rate means intermittent failures rather
+    // than an outage
+    #[serde(rename = "outcomeTransitions")]
+    pub outcome_transitions: u32,
+    #[serde(rename = "flakinessScore")]
+    pub flakiness_score: Option<f64>,
+    // Share of responses that came back with a Content-Encoding
+    #[serde(rename = "compressedResponseFraction")]
+    pub compressed_response_fraction: Option<f64>,
+    // Highest rate the capacityProbe found the target sustaining
+    #[serde(rename = "capacityEstimateRps")]
+    pub capacity_estimate_rps: Option<f64>,
+    // Share of requests that failed on the network path rather than with a response
+    #[serde(rename = "networkErrorFraction")]
+    pub network_error_fraction: Option<f64>,
+    // Response latency by status code, failures included, e.g. to tell slow 429s from
+    // fast 200s
+    #[serde(rename = "statusCodeLatencies")]
+    pub status_code_latencies: HashMap<u16, StatusCodeLatency>,
     #[serde(rename = "minResponseTime")]
     pub min_response_time: f64,
     #[serde(rename = "maxResponseTime")]
@@ -42,26 +1256,431 @@ pub struct TestResult {
     pub p95_response_time: f64,
     #[serde(rename = "p99ResponseTime")]
     pub p99_response_time: f64,
+    #[serde(rename = "queueTimeP95")]
+    pub queue_time_p95: f64,
+    // How far from their intended send time scheduled requests went out, early or late,
+    // in milliseconds. Only for runs that keep to a schedule: open arrivals, a rate
+    // shape, poisson arrivals or paced userLoop users.
+    #[serde(rename = "scheduleErrorP50")]
+    pub schedule_error_p50: Option<f64>,
+    #[serde(rename = "scheduleErrorP95")]
+    pub schedule_error_p95: Option<f64>,
+    #[serde(rename = "scheduleErrorP99")]
+    pub schedule_error_p99: Option<f64>,
+    // Time to first byte (response headers), excluding the body transfer
+    #[serde(rename = "ttfbP50")]
+    pub ttfb_p50: f64,
+    #[serde(rename = "ttfbP95")]
+    pub ttfb_p95: f64,
+    #[serde(rename = "ttfbP99")]
+    pub ttfb_p99: f64,
+    // Per DNS lookup, which only new connections need
+    #[serde(rename = "dnsTimeP95")]
+    pub dns_time_p95: f64,
+    // New connections measured requests got responses over, by local and remote address;
+    // ones opened during setup and reused aren't new
+    #[serde(rename = "connectionsEstablished")]
+    pub connections_established: u64,
+    // Deduplicated soft issues such as clamped settings or dropped metrics
+    pub warnings: Vec<String>,
+    // Only for the sse protocol
+    pub sse: Option<SseSummary>,
+    // Share of the run every shared concurrency permit was taken, so requests queued
+    // in the worker rather than at the target
+    #[serde(rename = "poolSaturationPercent")]
+    pub pool_saturation_percent: f64,
+    #[serde(rename = "inFlightDistribution")]
+    pub in_flight_distribution: Option<InFlightDistribution>,
+    // Every request carried a unique cacheBust value
+    #[serde(rename = "cacheBusted")]
+    pub cache_busted: bool,
+    // Latency of the run's first successful response, typically a cold start for
+    // serverless targets
+    #[serde(rename = "coldStartMs")]
+    pub cold_start_ms: Option<u64>,
+    // Sent during excludedWarmupSeconds, and counted nowhere else
+    #[serde(rename = "warmupRequests")]
+    pub warmup_requests: u32,
+    // Dimension -> value -> stats, from endpoint `tags`
+    #[serde(rename = "breakdownByDimension")]
+    pub breakdown_by_dimension: HashMap<String, HashMap<String, DimensionBreakdown>>,
+    // WORKER_REGION of the worker that ran the test
+    pub region: Option<String>,
+    // Stopped through the cancel_tests exchange; counts cover what ran until then
+    pub cancelled: bool,
+    // Share of cacheable GETs answered by `responseCache`; those never count as requests
+    #[serde(rename = "cacheHits")]
+    pub cache_hits: u32,
+    #[serde(rename = "cacheHitRate")]
+    pub cache_hit_rate: Option<f64>,
+    // Body sizes only; headers and framing aren't counted
+    #[serde(rename = "avgRequestBytes")]
+    pub avg_request_bytes: Option<f64>,
+    #[serde(rename = "avgResponseBytes")]
+    pub avg_response_bytes: Option<f64>,
+    #[serde(rename = "bytesPerSuccessfulRequest")]
+    pub bytes_per_successful_request: Option<f64>,
+    // Response body bytes served by the target as they came over the wire, and their
+    // rate over the whole run
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: u64,
+    #[serde(rename = "throughputBytesPerSecond")]
+    pub throughput_bytes_per_second: f64,
+    // The same bodies once decoded from gzip, deflate or br
+    #[serde(rename = "decompressedBytes")]
+    pub decompressed_bytes: u64,
+    // With verifyRecovery: seconds after the load until latency was back at baseline,
+    // or recoveryFailed when it wasn't within the window
+    #[serde(rename = "recoveredWithinSeconds")]
+    pub recovered_within_seconds: Option<f64>,
+    #[serde(rename = "recoveryFailed")]
+    pub recovery_failed: bool,
+    #[serde(rename = "effectiveConcurrency")]
+    pub effective_concurrency: u32,
+    // Average requests actually in flight, by Little's law (rps x mean latency)
+    #[serde(rename = "achievedConcurrency")]
+    pub achieved_concurrency: f64,
+    #[serde(rename = "concurrencyModel")]
+    pub concurrency_model: String,
     #[serde(rename = "requestsPerSecond")]
     pub requests_per_second: f64,
+    // Successful requests only
+    #[serde(rename = "goodputRps")]
+    pub goodput_rps: f64,
     #[serde(rename = "errorRate")]
     pub error_rate: f64,
     #[serde(rename = "statusCodeDistribution")]
     pub status_code_distribution: HashMap<u16, u32>,
+    // Response latency of https origins by the TLS version a separate setup handshake with
+    // each origin negotiated, not what the measured connections did, with the cipher
+    // suites seen for it
+    #[serde(rename = "latencyByTlsVersion")]
+    pub latency_by_tls_version: HashMap<String, TlsLatency>,
     #[serde(rename = "errorDistribution")]
     pub error_distribution: HashMap<String, u32>,
+    #[serde(rename = "faultInjectedRequests")]
+    pub fault_injected_requests: u32,
+    #[serde(rename = "faultInjectedFailures")]
+    pub fault_injected_failures: u32,
+    #[serde(rename = "faultDistribution")]
+    pub fault_distribution: HashMap<String, u32>,
+    #[serde(rename = "backendDistribution")]
+    pub backend_distribution: HashMap<String, u32>,
+    #[serde(rename = "stickyViolations")]
+    pub sticky_violations: u32,
+    #[serde(rename = "validationFailures")]
+    pub validation_failures: u32,
+    #[serde(rename = "validationWebhookErrors")]
+    pub validation_webhook_errors: u32,
+    // Extra attempts made after connection errors, retryable statuses or a 401 that
+    // refreshed the credentials, across all requests; each request makes at most
+    // `retry.retries` of them, or one when that's 0 and it got a 401
+    #[serde(rename = "totalRetries")]
+    pub total_retries: u32,
+    // Metrics discarded because the local buffer overflowed during a broker outage
+    #[serde(rename = "droppedMetrics")]
+    pub dropped_metrics: u32,
+    // Assertion outcomes keyed by endpoint name
+    #[serde(rename = "endpointResults")]
+    pub endpoint_results: HashMap<String, EndpointResult>,
+    #[serde(rename = "groupResults")]
+    pub group_results: HashMap<String, GroupResult>,
+    #[serde(rename = "sampleAdequacy")]
+    pub sample_adequacy: SampleAdequacy,
+    #[serde(rename = "tailComposition")]
+    pub tail_composition: Vec<TailBucket>,
+    // Scheduled vs achieved rate for each `rateSchedule` segment
+    #[serde(rename = "segmentResults")]
+    pub segment_results: Vec<SegmentResult>,
+    #[serde(rename = "timeToSteadyStateSeconds")]
+    pub time_to_steady_state_seconds: Option<f64>,
+    #[serde(rename = "rampStabilityScore")]
+    pub ramp_stability_score: Option<f64>,
+    #[serde(rename = "worstInterval")]
+    pub worst_interval: Option<WorstInterval>,
+    // Seconds from the worst error-rate spike back to baseline; None without a spike
+    // or if it never recovered
+    #[serde(rename = "errorRecoverySeconds")]
+    pub error_recovery_seconds: Option<f64>,
+    // Sizes actually generated, with bodySizeDistribution
+    #[serde(rename = "bodySizeSummary")]
+    pub body_size_summary: Option<BodySizeSummary>,
+    // Full latency histogram (base64 HDR V2), so percentiles can be recomputed later
+    #[serde(rename = "latencyHistogram")]
+    pub latency_histogram: Option<String>,
+    // Mergeable latency sketch, only with the tdigest percentile backend
+    #[serde(rename = "latencyDigest")]
+    pub latency_digest: Option<TDigest>,
     #[serde(rename = "timeSeriesData")]
     pub time_series_data: Vec<TimeSeriesPoint>,
+    #[serde(rename = "workerVersion")]
+    pub worker_version: String,
+    pub capabilities: Vec<String>,
 }
 
-#[derive(Debug, Serialize)]
+impl TestResult {
+    // Empties the fields `verbosity` leaves out
+    pub fn apply_verbosity(&mut self, verbosity: ResultVerbosity) {
+        if verbosity < ResultVerbosity::Full {
+            self.time_series_data.clear();
+            self.latency_histogram = None;
+            self.latency_digest = None;
+        }
+
+        if verbosity < ResultVerbosity::Standard {
+            self.status_code_distribution.clear();
+            self.status_code_latencies.clear();
+            self.latency_by_tls_version.clear();
+            self.error_distribution.clear();
+            self.fault_distribution.clear();
+            self.backend_distribution.clear();
+            self.tail_composition.clear();
+            self.segment_results.clear();
+            self.endpoint_results.clear();
+            self.group_results.clear();
+            self.breakdown_by_dimension.clear();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BodySizeSummary {
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    pub mean: f64,
+    pub p50: u64,
+    pub p95: u64,
+}
+
+// Requests in flight, sampled every few milliseconds over the run; a wide spread
+// means bursty rather than steady concurrency
+#[derive(Debug, Clone, Serialize)]
+pub struct InFlightDistribution {
+    pub samples: u64,
+    pub mean: f64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+// Time-series point with the highest p95, to point incident analysis at the right moment
+#[derive(Debug, Clone, Serialize)]
+pub struct WorstInterval {
+    pub timestamp: i64,
+    pub p95: f64,
+    #[serde(rename = "errorRate")]
+    pub error_rate: f64,
+}
+
+// Latency of the responses from origins whose probe handshake negotiated one TLS version
+#[derive(Debug, Clone, Serialize)]
+pub struct TlsLatency {
+    pub count: u64,
+    #[serde(rename = "averageResponseTime")]
+    pub average_response_time: f64,
+    #[serde(rename = "p50ResponseTime")]
+    pub p50_response_time: f64,
+    #[serde(rename = "p95ResponseTime")]
+    pub p95_response_time: f64,
+    #[serde(rename = "p99ResponseTime")]
+    pub p99_response_time: f64,
+    #[serde(rename = "cipherSuites")]
+    pub cipher_suites: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EndpointResult {
+    pub requests: u32,
+    pub passed: u32,
+    pub failed: u32,
+    #[serde(rename = "statusCodeDistribution")]
+    pub status_code_distribution: HashMap<u16, u32>,
+    // Failures that hit the endpoint's timeout
+    pub timeouts: u32,
+    // Latency of the passed requests
+    #[serde(rename = "averageResponseTime")]
+    pub average_response_time: f64,
+    #[serde(rename = "p50ResponseTime")]
+    pub p50_response_time: f64,
+    #[serde(rename = "p95ResponseTime")]
+    pub p95_response_time: f64,
+    #[serde(rename = "p99ResponseTime")]
+    pub p99_response_time: f64,
+}
+
+// Outcome of the requests tagged with one dimension value
+#[derive(Debug, Clone, Serialize)]
+pub struct DimensionBreakdown {
+    pub requests: u32,
+    pub successful: u32,
+    pub failed: u32,
+    #[serde(rename = "averageResponseTime")]
+    pub average_response_time: f64,
+    #[serde(rename = "p50ResponseTime")]
+    pub p50_response_time: f64,
+    #[serde(rename = "p95ResponseTime")]
+    pub p95_response_time: f64,
+    #[serde(rename = "p99ResponseTime")]
+    pub p99_response_time: f64,
+}
+
+// Latency of the responses that came back with one status code
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusCodeLatency {
+    pub count: u64,
+    #[serde(rename = "averageResponseTime")]
+    pub average_response_time: f64,
+    #[serde(rename = "p50ResponseTime")]
+    pub p50_response_time: f64,
+    #[serde(rename = "p95ResponseTime")]
+    pub p95_response_time: f64,
+    #[serde(rename = "p99ResponseTime")]
+    pub p99_response_time: f64,
+}
+
+// Whether enough latency samples were collected for each reported percentile to mean much
+#[derive(Debug, Clone, Serialize)]
+pub struct SampleAdequacy {
+    pub samples: u64,
+    // Samples needed for every reported percentile to be reliable
+    #[serde(rename = "suggestedMinimumSamples")]
+    pub suggested_minimum_samples: u64,
+    pub percentiles: Vec<PercentileConfidence>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PercentileConfidence {
+    pub percentile: f64,
+    #[serde(rename = "minimumSamples")]
+    pub minimum_samples: u64,
+    pub reliable: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GroupResult {
+    // Configured in-flight limit
+    pub concurrency: u32,
+    #[serde(rename = "peakInFlight")]
+    pub peak_in_flight: u32,
+    pub requests: u32,
+    pub successful: u32,
+    pub failed: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SseSummary {
+    // Streams that were opened successfully, counted once per virtual user
+    pub connections: u32,
+    #[serde(rename = "eventsReceived")]
+    pub events_received: u64,
+    #[serde(rename = "eventRate")]
+    pub event_rate: f64,
+    // Gap between consecutive events on the same stream
+    #[serde(rename = "interEventP50")]
+    pub inter_event_p50: f64,
+    #[serde(rename = "interEventP95")]
+    pub inter_event_p95: f64,
+    #[serde(rename = "interEventP99")]
+    pub inter_event_p99: f64,
+    #[serde(rename = "perConnection")]
+    pub per_connection: Vec<SseConnectionSummary>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SseConnectionSummary {
+    pub connection: u32,
+    pub events: u64,
+    #[serde(rename = "eventRate")]
+    pub event_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentResult {
+    #[serde(rename = "startSeconds")]
+    pub start_seconds: f64,
+    #[serde(rename = "durationSeconds")]
+    pub duration_seconds: u32,
+    // Averages over the time-series points within the segment
+    #[serde(rename = "targetRps")]
+    pub target_rps: f64,
+    #[serde(rename = "achievedRps")]
+    pub achieved_rps: f64,
+    // Only with resetHistogramPerPhase
+    pub phase: Option<PhaseSummary>,
+}
+
+// Requests and latency within one phase of the run, from that phase's samples alone
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseSummary {
+    pub requests: u32,
+    pub failed: u32,
+    #[serde(rename = "averageResponseTime")]
+    pub average_response_time: f64,
+    #[serde(rename = "p50ResponseTime")]
+    pub p50_response_time: f64,
+    #[serde(rename = "p95ResponseTime")]
+    pub p95_response_time: f64,
+    #[serde(rename = "p99ResponseTime")]
+    pub p99_response_time: f64,
+}
+
+// Breakdown of the requests at or above a latency percentile
+#[derive(Debug, Clone, Serialize)]
+pub struct TailBucket {
+    pub percentile: f64,
+    #[serde(rename = "thresholdMs")]
+    pub threshold_ms: f64,
+    pub entries: Vec<TailEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TailEntry {
+    pub endpoint: String,
+    #[serde(rename = "statusCode")]
+    pub status_code: u16,
+    pub count: u64,
+    // Fraction of the bucket's requests
+    pub share: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct TimeSeriesPoint {
     pub timestamp: i64,
+    // Length of the window this point covers; grows when points are downsampled
+    #[serde(rename = "intervalSeconds")]
+    pub interval_seconds: f64,
     pub rps: f64,
+    #[serde(rename = "goodputRps")]
+    pub goodput_rps: f64,
+    // Rate achieved within this interval only
+    #[serde(rename = "intervalRps")]
+    pub interval_rps: f64,
+    // Rate `loadExpression` or `rateSchedule` asked for at this point
+    #[serde(rename = "targetRps")]
+    pub target_rps: Option<f64>,
     #[serde(rename = "avgResponseTime")]
     pub avg_response_time: f64,
+    // Latency spread of the samples within this interval only
+    #[serde(rename = "minResponseTime")]
+    pub min_response_time: f64,
+    #[serde(rename = "maxResponseTime")]
+    pub max_response_time: f64,
+    #[serde(rename = "p95ResponseTime")]
+    pub p95_response_time: f64,
     #[serde(rename = "errorRate")]
     pub error_rate: f64,
+    // Share of this interval's requests that failed
+    #[serde(rename = "intervalErrorRate")]
+    pub interval_error_rate: f64,
+    // Requests queued for a free concurrency permit at some point in this interval
+    #[serde(rename = "poolSaturated")]
+    pub pool_saturated: bool,
+    // How late the latest paced request went out; keeps growing when the target rate
+    // can't be reached
+    #[serde(rename = "scheduleLagMs")]
+    pub schedule_lag_ms: Option<f64>,
 }
 
 #[derive(Debug, Serialize)]
@@ -83,5 +1702,229 @@ pub struct Metric {
     pub error_message: Option<String>,
     #[serde(rename = "activeUsers")]
     pub active_users: u32,
+    pub region: Option<String>,
+}
+
+// Published to the cancel_tests exchange to stop a running or waiting test early
+#[derive(Debug, Deserialize)]
+pub struct CancelTestMessage {
+    #[serde(rename = "testId")]
+    pub test_id: String,
+}
+
+// Parameters the worker actually runs with, once defaults and protocol caps are applied
+#[derive(Debug, Serialize)]
+pub struct ResolvedTestPlan {
+    #[serde(rename = "testId")]
+    pub test_id: String,
+    #[serde(rename = "targetUrl")]
+    pub target_url: String,
+    pub method: String,
+    // As submitted, before `effectiveConcurrency` caps it
+    #[serde(rename = "concurrentUsers")]
+    pub concurrent_users: u32,
+    #[serde(rename = "effectiveConcurrency")]
+    pub effective_concurrency: u32,
+    #[serde(rename = "concurrencyModel")]
+    pub concurrency_model: String,
+    #[serde(rename = "totalRequests")]
+    pub total_requests: u32,
+    #[serde(rename = "durationSeconds")]
+    pub duration_seconds: u32,
+    #[serde(rename = "requestsPerSecond")]
+    pub requests_per_second: u32,
+    #[serde(rename = "iterationsPerUser")]
+    pub iterations_per_user: Option<u32>,
+    #[serde(rename = "loadExpression")]
+    pub load_expression: Option<String>,
+    #[serde(rename = "arrivalDistribution")]
+    pub arrival_distribution: ArrivalDistribution,
+    pub mode: LoadMode,
+    #[serde(rename = "httpVersion")]
+    pub http_version: HttpVersion,
+    #[serde(rename = "maxConnectionsPerOrigin")]
+    pub max_connections_per_origin: Option<u32>,
+    #[serde(rename = "maxConcurrentStreams")]
+    pub max_concurrent_streams: Option<u32>,
+    #[serde(rename = "maxRequestsPerConnection")]
+    pub max_requests_per_connection: Option<u32>,
+    #[serde(rename = "maxTotalRequests")]
+    pub max_total_requests: Option<u32>,
+    #[serde(rename = "warmupSeconds")]
+    pub warmup_seconds: u32,
+    #[serde(rename = "rampUpSeconds")]
+    pub ramp_up_seconds: Option<u32>,
+    #[serde(rename = "requestTimeoutMs")]
+    pub request_timeout_ms: u64,
+    #[serde(rename = "maxTimeSeriesPoints")]
+    pub max_time_series_points: usize,
+    #[serde(rename = "percentileBackend")]
+    pub percentile_backend: PercentileBackend,
+    #[serde(rename = "resultEncoding")]
+    pub result_encoding: ResultEncoding,
+    pub seed: Option<u64>,
+    pub timestamp: String,
+}
+
+// Heartbeat published to the progress queue while a test runs
+#[derive(Debug, Serialize)]
+pub struct TestProgress {
+    #[serde(rename = "testId")]
+    pub test_id: String,
+    #[serde(rename = "percentComplete")]
+    pub percent_complete: f64,
+    #[serde(rename = "elapsedSeconds")]
+    pub elapsed_seconds: f64,
+    #[serde(rename = "requestsCompleted")]
+    pub requests_completed: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkerAnnouncement {
+    #[serde(rename = "consumerTag")]
+    pub consumer_tag: String,
+    #[serde(rename = "workerVersion")]
+    pub worker_version: String,
+    pub capabilities: Vec<String>,
+    pub region: Option<String>,
+    pub timestamp: String,
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::message;
+    use serde_json::json;
+
+    fn retry(config: serde_json::Value) -> RetryConfig {
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn linear_backoff_grows_with_the_retry_number() {
+        let retry = retry(json!({ "retries": 3, "retryBackoffMs": 100, "backoff": "linear" }));
+
+        assert_eq!(retry.backoff(1), Some(Duration::from_millis(100)));
+        assert_eq!(retry.backoff(2), Some(Duration::from_millis(200)));
+        assert_eq!(retry.backoff(3), Some(Duration::from_millis(300)));
+    }
+
+    #[test]
+    fn exponential_backoff_doubles_and_stays_bounded() {
+        let retry = retry(json!({ "retries": 3, "retryBackoffMs": 100 }));
+
+        assert_eq!(retry.backoff(0), Some(Duration::from_millis(100)));
+        assert_eq!(retry.backoff(1), Some(Duration::from_millis(100)));
+        assert_eq!(retry.backoff(2), Some(Duration::from_millis(200)));
+        assert_eq!(retry.backoff(4), Some(Duration::from_millis(800)));
+        assert_eq!(retry.backoff(40), retry.backoff(17));
+    }
+
+    #[test]
+    fn no_backoff_without_retry_backoff_ms() {
+        assert_eq!(retry(json!({ "retries": 2 })).backoff(1), None);
+    }
+
+    #[test]
+    fn retry_settings_sit_at_the_top_level() {
+        let retry = message(json!({ "retries": 2, "retryBackoffMs": 50 })).retry;
+        assert_eq!(retry.retries, 2);
+        assert_eq!(retry.backoff_ms, Some(50));
+
+        assert_eq!(message(json!({})).retry.retries, 0);
+    }
+
+    #[test]
+    fn retry_settings_without_retries_are_rejected() {
+        for settings in [
+            json!({ "retryBackoffMs": 50 }),
+            json!({ "backoff": "linear" }),
+            json!({ "retryableStatusCodes": [429] }),
+        ] {
+            assert!(message(settings.clone()).validate().is_err(), "{}", settings);
+        }
+
+        assert!(message(json!({ "retries": 1, "retryBackoffMs": 50 })).validate().is_ok());
+    }
+
+    #[test]
+    fn basic_auth_accepts_user_and_pass() {
+        let auth: AuthConfig =
+            serde_json::from_value(json!({ "type": "basic", "user": "ann", "pass": "s3cret" }))
+                .unwrap();
+
+        assert!(matches!(
+            auth,
+            AuthConfig::Basic { username, password } if username == "ann" && password == "s3cret"
+        ));
+    }
+
+    #[test]
+    fn retries_5xx_unless_codes_are_listed() {
+        let default = retry(json!({ "retries": 1 }));
+        assert!(default.is_retryable(503));
+        assert!(!default.is_retryable(429));
+
+        let listed = retry(json!({ "retries": 1, "retryableStatusCodes": [429] }));
+        assert!(listed.is_retryable(429));
+        assert!(!listed.is_retryable(503));
+    }
+
+    #[test]
+    fn a_test_needs_something_to_stop_it() {
+        let unbounded = message(json!({ "totalRequests": 0, "durationSeconds": 0 }));
+        assert!(unbounded.validate().is_err());
+
+        let timed = message(json!({ "totalRequests": 0, "durationSeconds": 30 }));
+        assert!(timed.validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_base64_body_fails_to_parse() {
+        let mut fields = serde_json::to_value(message(json!({}))).unwrap();
+        fields["bodyBase64"] = json!("not base64!");
+
+        let error = serde_json::from_value::<LoadTestMessage>(fields).unwrap_err();
+        assert!(
+            error.to_string().contains("invalid bodyBase64"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn unencodable_body_is_rejected_up_front() {
+        // A form field can't hold an object, whichever endpoint's body it's in
+        let nested = message(json!({
+            "bodyType": "form",
+            "body": { "name": "a" },
+            "endpoints": [{
+                "name": "b",
+                "url": "http://127.0.0.1:9/b",
+                "body": { "address": { "city": "c" } },
+            }],
+        }));
+
+        let error = nested.validate().unwrap_err();
+        assert_eq!(error.to_string(), "form body fields can't be objects");
+    }
+
+    #[test]
+    fn virtual_users_need_persistent_users() {
+        let identities = json!([{ "token": "a" }, { "token": "b" }]);
+
+        let per_request = message(json!({ "virtualUsers": identities }));
+        let error = per_request.validate().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "virtualUsers requires iterationsPerUser or the userLoop concurrencyModel"
+        );
+
+        let iterations = message(json!({ "virtualUsers": identities, "iterationsPerUser": 1 }));
+        assert!(iterations.validate().is_ok());
+
+        let user_loop =
+            message(json!({ "virtualUsers": identities, "concurrencyModel": "userLoop" }));
+        assert!(user_loop.validate().is_ok());
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use uuid::Uuid;
+
+pub const WORKER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// What a coordinator can ask of this build: the protocols it speaks, and the optional
+// message fields and control messages it understands, which an older worker would
+// silently ignore. Features are lowercase and hyphenated, protocols keep their usual
+// spelling; results and metrics every worker reports aren't listed.
+pub const CAPABILITIES: &[&str] = &[
+    "http/1.1",
+    "h2c",
+    "h2",
+    "https",
+    "duration-only",
+    "max-decompressed-bytes",
+    "iterations-per-user",
+    "fault-injection",
+    "sticky-sessions",
+    "validation-webhook",
+    "poisson-arrivals",
+    "seeded-rng",
+    "checkpoints",
+    "binary-bodies",
+    "retries",
+    "min-expected-rps",
+    "protobuf-results",
+    "weighted-endpoints",
+    "preflight",
+    "tdigest",
+    "prewarm-connections",
+    "load-expressions",
+    "body-size-distribution",
+    "endpoint-groups",
+    "auth-providers",
+    "connection-cycling",
+    "request-budget",
+    "sse",
+    "dimension-breakdown",
+    "request-trace",
+    "cancellation",
+    "response-cache",
+    "setup-timeout",
+    "ramp-up",
+    "request-timeout",
+    "disable-metrics",
+    "result-verbosity",
+    "expected-status-codes",
+    "virtual-users",
+    "verify-recovery",
+    "body-assertions",
+    "open-arrival-rate",
+    "retry-backoff",
+    "cache-bust",
+    "cold-start",
+    "exclude-warmup",
+    "rate-schedule",
+    "endpoint-timeouts",
+    "endpoint-overrides",
+    "request-templates",
+    "rate-coordination",
+    "data-source",
+    "steps",
+    "think-time",
+    "capacity-probe",
+    "error-rate-abort",
+    "concurrency-model",
+    "body-types",
+    "phase-histograms",
+    "response-decompression",
+    "api-key-auth",
+    "bootstrap",
+];
+
+// Load tests run at once, unless MAX_CONCURRENT_TESTS overrides it
+const DEFAULT_MAX_CONCURRENT_TESTS: usize = 4;
+
+// Metric payloads kept while the broker is unreachable, unless METRICS_BUFFER_SIZE overrides it
+const DEFAULT_METRICS_BUFFER_SIZE: usize = 1000;
+
+pub fn capabilities() -> Vec<String> {
+    CAPABILITIES.iter().map(|c| c.to_string()).collect()
+}
+
+pub fn metrics_buffer_size() -> usize {
+    env::var("METRICS_BUFFER_SIZE")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(DEFAULT_METRICS_BUFFER_SIZE)
+}
+
+pub fn max_concurrent_tests() -> usize {
+    env::var("MAX_CONCURRENT_TESTS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .filter(|&count| count > 0)
+        .unwrap_or(DEFAULT_MAX_CONCURRENT_TESTS)
+}
+
+// Result status -> queue overrides from RESULT_ROUTES, e.g. "aborted=test_results_failed";
+// statuses without a route go to the default results queue
+pub fn result_routes() -> HashMap<String, String> {
+    env::var("RESULT_ROUTES")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|route| {
+            let (status, queue) = route.split_once('=')?;
+            Some((status.trim().to_string(), queue.trim().to_string()))
+        })
+        .filter(|(status, queue)| !status.is_empty() && !queue.is_empty())
+        .collect()
+}
+
+// Shared secret results are signed with, from RESULT_SIGNING_SECRET; unsigned when unset
+pub fn signing_secret() -> Option<Vec<u8>> {
+    env::var("RESULT_SIGNING_SECRET")
+        .ok()
+        .filter(|secret| !secret.is_empty())
+        .map(String::into_bytes)
+}
+
+// Region this worker sends load from, from WORKER_REGION, so coordinators can break
+// geo-distributed tests down by origin
+pub fn region() -> Option<String> {
+    env::var("WORKER_REGION")
+        .ok()
+        .map(|region| region.trim().to_string())
+        .filter(|region| !region.is_empty())
+}
+
+// Whether results and metrics are published persistent (delivery mode 2), from
+// PERSISTENT_RESULTS and PERSISTENT_METRICS, so they survive a broker restart in their
+// durable queues. Results are by default; metrics, superseded every second, aren't.
+pub fn persistent_results() -> bool {
+    flag("PERSISTENT_RESULTS").unwrap_or(true)
+}
+
+pub fn persistent_metrics() -> bool {
+    flag("PERSISTENT_METRICS").unwrap_or(false)
+}
+
+fn flag(name: &str) -> Option<bool> {
+    match env::var(name).ok()?.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" => Some(true),
+        "0" | "false" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+// Consumer tag for this instance, unique per process unless WORKER_CONSUMER_TAG pins it,
+// so several workers can consume from the same broker side by side
+pub fn consumer_tag() -> String {
+    env::var("WORKER_CONSUMER_TAG").unwrap_or_else(|_| generated_consumer_tag())
+}
+
+fn generated_consumer_tag() -> String {
+    let suffix = Uuid::new_v4().simple().to_string();
+    format!("loadmaster_worker-{}-{}", hostname(), &suffix[..8])
+}
+
+fn hostname() -> String {
+    env::var("HOSTNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consumer_tags_are_distinct_and_name_the_host() {
+        let prefix = format!("loadmaster_worker-{}-", hostname());
+
+        let tags: Vec<String> = (0..3).map(|_| generated_consumer_tag()).collect();
+
+        for tag in &tags {
+            assert!(tag.starts_with(&prefix), "{}", tag);
+            assert_eq!(tag.len(), prefix.len() + 8, "{}", tag);
+        }
+        assert_ne!(tags[0], tags[1]);
+        assert_ne!(tags[1], tags[2]);
+        assert_ne!(tags[0], tags[2]);
+    }
+
+    #[test]
+    fn capabilities_are_distinct_hyphenated_names() {
+        for (index, capability) in CAPABILITIES.iter().enumerate() {
+            assert!(
+                capability
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "-./".contains(c)),
+                "{}",
+                capability
+            );
+            assert!(!CAPABILITIES[..index].contains(capability), "{}", capability);
+        }
+    }
+}